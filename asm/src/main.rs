@@ -0,0 +1,38 @@
+use chip8::{asm, Platform};
+use clap::Parser;
+use std::fs;
+use std::process::exit;
+
+// assembles CHIP-8 source (the mnemonics printed by `dbg --disasm`) into a ROM image
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    source: String,
+    /// the ROM to write (`<source>.ch8` by default)
+    #[clap(short, long)]
+    output: Option<String>,
+    /// interpreter the ROM is for; decides the load address of the labels
+    #[clap(long, default_value = "chip8")]
+    platform: Platform,
+}
+
+fn main() {
+    let args = Args::parse();
+    let src = fs::read_to_string(&args.source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", args.source, e);
+        exit(1);
+    });
+    let rom = asm::assemble(&src, args.platform.start).unwrap_or_else(|e| {
+        eprintln!("{}: {}", args.source, e);
+        exit(1);
+    });
+    let output = args.output.unwrap_or_else(|| {
+        let stem = args.source.strip_suffix(".8s").unwrap_or(&args.source);
+        format!("{}.ch8", stem)
+    });
+    if let Err(e) = fs::write(&output, &rom) {
+        eprintln!("{}: {}", output, e);
+        exit(1);
+    }
+    println!("{}: {} bytes", output, rom.len());
+}
@@ -0,0 +1,6 @@
+// `libchip8.so` (`chip8.dll`, `libchip8.dylib`) for C callers of `include/chip8.h`, and
+// `chip8.wasm` with `--no-default-features --features wasm`.
+#[cfg(feature = "ffi")]
+pub use chip8::ffi::*;
+#[cfg(feature = "wasm")]
+pub use chip8::wasm::*;
@@ -0,0 +1,31 @@
+// measures the cycles per second of the core on the synthetic ROMs of `chip8::romgen`, and
+// writes them for trying a frontend: `cargo run --release -p chip8 --example bench [-- <dir>]`.
+use chip8::romgen::Workload;
+use chip8::Chip;
+use std::time::Instant;
+use std::{env, fs};
+
+const CYCLES: u32 = 1_000_000;
+
+fn main() {
+    let dir = env::args().nth(1);
+    for w in Workload::ALL {
+        let rom = w.rom();
+        if let Some(dir) = &dir {
+            let path = format!("{}/{}.ch8", dir, w.name());
+            fs::write(&path, &rom).unwrap_or_else(|e| panic!("{}: {}", path, e));
+        }
+        let mut chip = Chip::headless();
+        chip.load_slice(&rom).unwrap();
+        let started = Instant::now();
+        for _ in 0..CYCLES {
+            chip.cycle().unwrap();
+        }
+        let secs = started.elapsed().as_secs_f64();
+        println!(
+            "{:<7}{:>8.2} Mcycles/s",
+            w.name(),
+            CYCLES as f64 / secs / 1e6
+        );
+    }
+}
@@ -0,0 +1,35 @@
+// runs several independent CHIP-8 machines in one process, one per thread.
+//
+// Each Chip owns its registers, RAM, timers and random number generator, so instances
+// never observe each other. Peripherals are not required to be `Send`; build each Chip
+// on the thread which drives it.
+use chip8::Chip;
+use std::thread;
+
+// counts V0 up forever and stores its BCD representation at 0x300.
+const COUNTER: [u8; 10] = [
+    0x60, 0x00, // LD V0, 0
+    0xA3, 0x00, // LD I, 0x300
+    0x70, 0x01, // ADD V0, 1
+    0xF0, 0x33, // LD B, V0
+    0x12, 0x04, // JP 0x204
+];
+
+fn main() {
+    let handles: Vec<_> = (1..=8)
+        .map(|n| {
+            thread::spawn(move || {
+                let mut chip = Chip::headless();
+                chip.load_slice(&COUNTER).unwrap();
+                for _ in 0..n * 30 {
+                    chip.cycle().unwrap();
+                }
+                (n, chip.ram.buf[0x300..0x303].to_vec())
+            })
+        })
+        .collect();
+    for h in handles {
+        let (n, bcd) = h.join().unwrap();
+        println!("instance {}: counter={:?}", n, bcd);
+    }
+}
@@ -0,0 +1,11 @@
+// arbitrary bytes as a ROM, run headless for a few frames.
+#![no_main]
+use chip8::Chip;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|rom: &[u8]| {
+    let mut chip = Chip::headless();
+    if chip.load_slice(rom).is_ok() {
+        let _ = chip.run_headless(10_000);
+    }
+});
@@ -0,0 +1,29 @@
+// one instruction from arbitrary registers and RAM: the first 55 bytes are the registers
+// (V0-VF, I, PC, SP, DT, ST and the stack), the rest RAM from address 0.
+#![no_main]
+use chip8::{fuzz_step, CpuView};
+use libfuzzer_sys::fuzz_target;
+
+const STATE: usize = 16 + 2 + 2 + 3 + 32;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < STATE {
+        return;
+    }
+    let (state, ram) = data.split_at(STATE);
+    let word = |at: usize| u16::from_be_bytes([state[at], state[at + 1]]);
+    let mut stack = [0; 16];
+    for (n, s) in stack.iter_mut().enumerate() {
+        *s = word(23 + n * 2);
+    }
+    let state = CpuView {
+        v: state[..16].try_into().unwrap(),
+        i: word(16),
+        pc: word(18),
+        sp: state[20],
+        dt: state[21],
+        st: state[22],
+        stack,
+    };
+    let _ = fuzz_step(ram, state);
+});
@@ -0,0 +1,50 @@
+/* C API of the chip8 crate, built with
+ *   cargo build -p chip8-capi --release
+ * All functions but chip8_new take a handle returned by chip8_new. */
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define CHIP8_OK 0
+#define CHIP8_EXIT 1
+#define CHIP8_TRAP (-1)
+
+typedef struct Chip8 Chip8;
+
+typedef struct Chip8Registers {
+    uint8_t v[16];
+    uint16_t i;
+    uint16_t pc;
+    uint8_t sp;
+    uint8_t dt;
+    uint8_t st;
+    uint16_t stack[16];
+} Chip8Registers;
+
+Chip8 *chip8_new(void);
+void chip8_free(Chip8 *chip);
+/* copies the ROM to the platform's start address (0x200, 0x600 for eti-660); returns the number
+   of bytes loaded, or 0 for an empty ROM or one which does not fit in RAM */
+size_t chip8_load(Chip8 *chip, const uint8_t *rom, size_t len);
+/* "chip8", "vip", "schip", "xochip", "eti-660" or "dream-6800"; returns -1 for an unknown name */
+int32_t chip8_set_platform(Chip8 *chip, const char *name);
+void chip8_set_seed(Chip8 *chip, uint64_t seed);
+/* executes one instruction: CHIP8_OK, CHIP8_TRAP, or CHIP8_EXIT once 00FD halted the machine (until
+   chip8_reset) */
+int32_t chip8_step(Chip8 *chip);
+/* counts the timers down; call it at 60Hz */
+void chip8_tick(Chip8 *chip);
+/* back to the power-on state with the ROM still loaded */
+void chip8_reset(Chip8 *chip);
+size_t chip8_width(const Chip8 *chip);
+size_t chip8_height(const Chip8 *chip);
+/* one palette index per pixel, row by row; returns the number of bytes copied */
+size_t chip8_framebuffer(const Chip8 *chip, uint8_t *out, size_t len);
+void chip8_set_key(Chip8 *chip, uint8_t key, bool down);
+bool chip8_beeping(const Chip8 *chip);
+void chip8_registers(const Chip8 *chip, Chip8Registers *out);
+
+#endif
@@ -0,0 +1,287 @@
+// assembler for the mnemonics printed by `disasm` (Cowgod's syntax) with labels.
+//
+// One statement per line: `[label:] [MNEMONIC operands]`, `;` starts a comment.
+// Numbers are decimal, `0x` hex or `0b` binary; a label stands for its address wherever an
+// address or a byte is expected. `DB` and `DW` emit bytes and big-endian words.
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    // 1-based source line
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AsmError {}
+
+struct Statement<'a> {
+    line: usize,
+    op: String,
+    args: Vec<&'a str>,
+}
+
+// assembles `src` into a ROM image loaded at `origin` (`PROGRAM_START` or `Platform::start`).
+pub fn assemble(src: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut addr = origin as usize;
+    for (n, line) in src.lines().enumerate() {
+        let err = |message: String| AsmError {
+            line: n + 1,
+            message,
+        };
+        let mut text = line.split(';').next().unwrap_or("").trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return Err(err(format!("invalid label `{}`", label)));
+            }
+            if labels.insert(label.to_string(), addr as u16).is_some() {
+                return Err(err(format!("label `{}` defined twice", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (op, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args: Vec<&str> = rest
+            .split(',')
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .collect();
+        let s = Statement {
+            line: n + 1,
+            op: op.to_ascii_uppercase(),
+            args,
+        };
+        addr += size(&s);
+        if addr > 0x1000 {
+            return Err(err("program does not fit in RAM".to_string()));
+        }
+        statements.push(s);
+    }
+    let mut out = Vec::new();
+    for s in &statements {
+        encode(s, &labels, &mut out).map_err(|message| AsmError {
+            line: s.line,
+            message,
+        })?;
+    }
+    Ok(out)
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && reg(s).is_none()
+}
+
+// bytes emitted by a statement, known before labels are resolved.
+fn size(s: &Statement) -> usize {
+    match s.op.as_str() {
+        "DB" => s.args.len(),
+        "DW" => 2 * s.args.len(),
+        // `LD I, NNNN` beyond 0xFFF is the XO-CHIP `F000 NNNN`
+        "LD" if s.args.len() == 2 && s.args[0].eq_ignore_ascii_case("I") => {
+            match number(s.args[1]) {
+                Some(n) if n > 0xFFF => 4,
+                _ => 2,
+            }
+        }
+        _ => 2,
+    }
+}
+
+fn number(s: &str) -> Option<u32> {
+    let s = s.to_ascii_lowercase();
+    if let Some(hex) = s.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u32::from_str_radix(bin, 2).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+// V0..VF
+fn reg(s: &str) -> Option<u16> {
+    let hex = s.strip_prefix('V').or_else(|| s.strip_prefix('v'))?;
+    match hex.len() {
+        1 => u16::from_str_radix(hex, 16).ok(),
+        _ => None,
+    }
+}
+
+fn encode(s: &Statement, labels: &BTreeMap<String, u16>, out: &mut Vec<u8>) -> Result<(), String> {
+    let value = |a: &str, max: u32| -> Result<u16, String> {
+        let n = match number(a) {
+            Some(n) => n,
+            None => *labels
+                .get(a)
+                .ok_or_else(|| format!("unknown label or number `{}`", a))?
+                as u32,
+        };
+        if n > max {
+            return Err(format!("`{}` is out of range (max 0x{:X})", a, max));
+        }
+        Ok(n as u16)
+    };
+    // the n-th operand as a number or label up to `max`
+    let arg = |n: usize, max: u32| value(s.args[n], max);
+    let v = |a: &str| reg(a).ok_or_else(|| format!("expected a register V0-VF, found `{}`", a));
+    // operands are matched case-insensitively; labels are case sensitive.
+    let upper: Vec<String> = s.args.iter().map(|a| a.to_ascii_uppercase()).collect();
+    let upper: Vec<&str> = upper.iter().map(|a| a.as_str()).collect();
+    let op = s.op.as_str();
+    let word = match (op, upper.as_slice()) {
+        ("DB", _) => {
+            for n in 0..s.args.len() {
+                out.push(arg(n, 0xFF)? as u8);
+            }
+            return Ok(());
+        }
+        ("DW", _) => {
+            for n in 0..s.args.len() {
+                out.extend_from_slice(&arg(n, 0xFFFF)?.to_be_bytes());
+            }
+            return Ok(());
+        }
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SCD", [_]) => 0x00C0 | arg(0, 0xF)?,
+        ("SCU", [_]) => 0x00D0 | arg(0, 0xF)?,
+        ("SCR", []) => 0x00FB,
+        ("SCL", []) => 0x00FC,
+        ("EXIT", []) => 0x00FD,
+        ("LOW", []) => 0x00FE,
+        ("HIGH", []) => 0x00FF,
+        ("AUDIO", []) => 0xF002,
+        ("PLANE", [_]) => 0xF001 | arg(0, 0xF)? << 8,
+        ("SYS", [_]) => arg(0, 0xFFF)?,
+        ("JP", [_]) => 0x1000 | arg(0, 0xFFF)?,
+        ("JP", ["V0", _]) => 0xB000 | arg(1, 0xFFF)?,
+        ("CALL", [_]) => 0x2000 | arg(0, 0xFFF)?,
+        ("SE", [x, y]) => match reg(y) {
+            Some(y) => 0x5000 | v(x)? << 8 | y << 4,
+            None => 0x3000 | v(x)? << 8 | arg(1, 0xFF)?,
+        },
+        ("SNE", [x, y]) => match reg(y) {
+            Some(y) => 0x9000 | v(x)? << 8 | y << 4,
+            None => 0x4000 | v(x)? << 8 | arg(1, 0xFF)?,
+        },
+        ("SAVE", [x, y]) => 0x5002 | v(x)? << 8 | v(y)? << 4,
+        ("LOAD", [x, y]) => 0x5003 | v(x)? << 8 | v(y)? << 4,
+        ("LD", ["I", _]) => match number(s.args[1]) {
+            Some(n) if n > 0xFFF => {
+                out.extend_from_slice(&[0xF0, 0x00]);
+                arg(1, 0xFFFF)?
+            }
+            _ => 0xA000 | arg(1, 0xFFF)?,
+        },
+        ("LD", ["DT", x]) => 0xF015 | v(x)? << 8,
+        ("LD", ["ST", x]) => 0xF018 | v(x)? << 8,
+        ("LD", ["F", x]) => 0xF029 | v(x)? << 8,
+        ("LD", ["HF", x]) => 0xF030 | v(x)? << 8,
+        ("LD", ["B", x]) => 0xF033 | v(x)? << 8,
+        ("LD", ["[I]", x]) => 0xF055 | v(x)? << 8,
+        ("LD", ["R", x]) => 0xF075 | v(x)? << 8,
+        ("LD", [x, "DT"]) => 0xF007 | v(x)? << 8,
+        ("LD", [x, "K"]) => 0xF00A | v(x)? << 8,
+        ("LD", [x, "[I]"]) => 0xF065 | v(x)? << 8,
+        ("LD", [x, "R"]) => 0xF085 | v(x)? << 8,
+        ("LD", [x, y]) => match reg(y) {
+            Some(y) => 0x8000 | v(x)? << 8 | y << 4,
+            None => 0x6000 | v(x)? << 8 | arg(1, 0xFF)?,
+        },
+        ("ADD", ["I", x]) => 0xF01E | v(x)? << 8,
+        ("ADD", [x, y]) => match reg(y) {
+            Some(y) => 0x8004 | v(x)? << 8 | y << 4,
+            None => 0x7000 | v(x)? << 8 | arg(1, 0xFF)?,
+        },
+        ("OR" | "AND" | "XOR" | "SUB" | "SHR" | "SUBN" | "SHL", [x, ys @ ..]) if ys.len() <= 1 => {
+            let n = match op {
+                "OR" => 1,
+                "AND" => 2,
+                "XOR" => 3,
+                "SUB" => 5,
+                "SHR" => 6,
+                "SUBN" => 7,
+                _ => 0xE,
+            };
+            // `SHR VX` shifts VX in place
+            let y = v(ys.first().unwrap_or(x))?;
+            0x8000 | v(x)? << 8 | y << 4 | n
+        }
+        ("RND", [x, _]) => 0xC000 | v(x)? << 8 | arg(1, 0xFF)?,
+        ("DRW", [x, y, _]) => 0xD000 | v(x)? << 8 | v(y)? << 4 | arg(2, 0xF)?,
+        ("SKP", [x]) => 0xE09E | v(x)? << 8,
+        ("SKNP", [x]) => 0xE0A1 | v(x)? << 8,
+        ("PITCH", [x]) => 0xF03A | v(x)? << 8,
+        _ => {
+            return Err(format!(
+                "unknown instruction `{} {}`",
+                s.op,
+                s.args.join(", ")
+            ))
+        }
+    };
+    out.extend_from_slice(&word.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm;
+
+    #[test]
+    fn assembles_labels_and_data() {
+        let src = "
+            ; draws a digit forever
+            start:  LD V0, 0x20   ; x
+                    ld v1, 10
+                    LD I, digit
+            loop:   DRW V0, V1, 5
+                    JP loop
+            digit:  DB 0xF0, 0x90, 0b11110000, 144, 0xF0
+        ";
+        let rom = assemble(src, 0x200).unwrap();
+        assert_eq!(
+            rom,
+            [
+                0x60, 0x20, 0x61, 0x0A, 0xA2, 0x0A, 0xD0, 0x15, 0x12, 0x06, 0xF0, 0x90, 0xF0, 0x90,
+                0xF0
+            ]
+        );
+        let err = assemble("CLS\nJP nowhere", 0x200).unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(assemble("LD V0, 256", 0x200).is_err());
+        assert!(assemble("a:\na:", 0x200).is_err());
+    }
+
+    #[test]
+    fn reassembles_the_disassembly() {
+        for op in 0..=0xFFFFu16 {
+            let text = disasm::mnemonic(op);
+            if text == "LD I, long" {
+                continue;
+            }
+            let rom = assemble(&text, 0x200).unwrap();
+            assert_eq!(rom, op.to_be_bytes(), "{}", text);
+        }
+        let long = [0xF0, 0x00, 0x12, 0x34];
+        assert_eq!(assemble("LD I, 0x1234", 0x200).unwrap(), long);
+    }
+}
@@ -0,0 +1,106 @@
+// disassembler: opcodes to mnemonics in the style of Cowgod's reference ("LD V1, 0x20",
+// "DRW V3, V4, 5"), with the SUPER-CHIP and XO-CHIP extensions.
+use super::opcode;
+use alloc::format;
+use alloc::string::{String, ToString};
+
+// returns the mnemonic of `op`; words which are not opcodes are shown as `DW 0xNNNN`.
+pub fn mnemonic(op: u16) -> String {
+    match opcode::decode(op) {
+        Some(o) => o.to_string(),
+        None => format!("DW 0x{:04X}", op),
+    }
+}
+
+// one disassembled instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    // the instruction bytes: 2, 4 for `F000 NNNN`, or 1 for a trailing odd byte
+    pub bytes: [u8; 4],
+    pub len: usize,
+    pub text: String,
+}
+
+// disassembles `bytes` loaded at `origin` word by word, e.g. a ROM at `PROGRAM_START`.
+pub fn disassemble(bytes: &[u8], origin: u16) -> Disassembly<'_> {
+    Disassembly {
+        bytes,
+        origin,
+        offset: 0,
+    }
+}
+
+pub struct Disassembly<'a> {
+    bytes: &'a [u8],
+    origin: u16,
+    offset: usize,
+}
+
+impl Iterator for Disassembly<'_> {
+    type Item = Line;
+    fn next(&mut self) -> Option<Line> {
+        let rest = self.bytes.get(self.offset..)?;
+        let addr = self.origin.wrapping_add(self.offset as u16);
+        let mut bytes = [0; 4];
+        let (len, text) = match rest {
+            [0xF0, 0x00, hi, lo, ..] => {
+                let nnnn = u16::from_be_bytes([*hi, *lo]);
+                (4, format!("LD I, 0x{:04X}", nnnn))
+            }
+            [hi, lo, ..] => (2, mnemonic(u16::from_be_bytes([*hi, *lo]))),
+            [b] => (1, format!("DB 0x{:02X}", b)),
+            [] => return None,
+        };
+        bytes[..len].copy_from_slice(&rest[..len]);
+        self.offset += len;
+        Some(Line {
+            addr,
+            bytes,
+            len,
+            text,
+        })
+    }
+}
+
+// formats the line as `addr: bytes  mnemonic`.
+impl core::fmt::Display for Line {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:03X}: ", self.addr)?;
+        for b in &self.bytes[..self.len] {
+            write!(f, "{:02X}", b)?;
+        }
+        write!(f, "{:width$}{}", "", self.text, width = 10 - 2 * self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(mnemonic(0x6120), "LD V1, 0x20");
+        assert_eq!(mnemonic(0xD345), "DRW V3, V4, 5");
+        assert_eq!(mnemonic(0x8AB6), "SHR VA, VB");
+        assert_eq!(mnemonic(0x812E), "SHL V1, V2");
+        assert_eq!(mnemonic(0xF265), "LD V2, [I]");
+        assert_eq!(mnemonic(0x8128), "DW 0x8128");
+    }
+
+    #[test]
+    fn disassembles_a_rom() {
+        let rom = [0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xAB];
+        let lines: Vec<String> = disassemble(&rom, 0x200).map(|l| l.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "200: 00E0      CLS",
+                "202: F0001234  LD I, 0x1234",
+                "206: 1200      JP 0x200",
+                "208: AB        DB 0xAB",
+            ]
+        );
+    }
+}
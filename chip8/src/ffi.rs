@@ -0,0 +1,201 @@
+// C bindings (`ffi` feature) declared in `include/chip8.h`; build the shared library with
+// `cargo build -p chip8-capi --release`. A handle owns one machine; every function takes the
+// handle returned by `chip8_new` until `chip8_free`.
+use super::{Chip, Keypad, NullDisplay};
+use alloc::boxed::Box;
+use core::ffi::{c_char, CStr};
+
+pub struct Chip8 {
+    chip: Chip,
+    keypad: Keypad,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Chip8Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    pub stack: [u16; 16],
+}
+
+// step results.
+pub const CHIP8_OK: i32 = 0;
+pub const CHIP8_EXIT: i32 = 1;
+pub const CHIP8_TRAP: i32 = -1;
+
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    let keypad = Keypad::new();
+    // the caller copies the framebuffer itself.
+    let chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+    Box::into_raw(Box::new(Chip8 { chip, keypad }))
+}
+
+/// # Safety
+/// `chip` is null or a handle from `chip8_new` not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip: *mut Chip8) {
+    if !chip.is_null() {
+        drop(Box::from_raw(chip));
+    }
+}
+
+/// # Safety
+/// `chip` is a live handle and `rom` points to `len` readable bytes. Returns the number of bytes
+/// loaded, or 0 for an empty ROM or one which does not fit in RAM.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load(chip: *mut Chip8, rom: *const u8, len: usize) -> usize {
+    if rom.is_null() {
+        return 0;
+    }
+    (*chip)
+        .chip
+        .load_slice(core::slice::from_raw_parts(rom, len))
+        .unwrap_or(0)
+}
+
+/// # Safety
+/// `chip` is a live handle and `name` a NUL-terminated platform name (`Platform::ALL`).
+/// Returns 0, or -1 for an unknown name.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_platform(chip: *mut Chip8, name: *const c_char) -> i32 {
+    let platform = match CStr::from_ptr(name).to_str().map(str::parse) {
+        Ok(Ok(p)) => p,
+        _ => return -1,
+    };
+    (*chip).chip.set_platform(&platform);
+    0
+}
+
+/// # Safety
+/// `chip` is a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_seed(chip: *mut Chip8, seed: u64) {
+    (*chip).chip.set_seed(seed);
+}
+
+/// # Safety
+/// `chip` is a live handle. Executes one instruction; returns `CHIP8_OK`, `CHIP8_EXIT` once
+/// 00FD halted the machine (until `chip8_reset`) or `CHIP8_TRAP` when it cannot go on.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip: *mut Chip8) -> i32 {
+    match (*chip).chip.step() {
+        Ok(_) if (*chip).chip.halted() => CHIP8_EXIT,
+        Ok(_) => CHIP8_OK,
+        Err(_) => CHIP8_TRAP,
+    }
+}
+
+/// # Safety
+/// `chip` is a live handle. Counts the timers down once; call it at 60Hz.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick(chip: *mut Chip8) {
+    (*chip).chip.tick_60hz();
+}
+
+/// # Safety
+/// `chip` is a live handle. Returns to the power-on state with the ROM still loaded.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(chip: *mut Chip8) {
+    (*chip).chip.reset();
+}
+
+/// # Safety
+/// `chip` is a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_width(chip: *const Chip8) -> usize {
+    (*chip).chip.framebuffer().width()
+}
+
+/// # Safety
+/// `chip` is a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_height(chip: *const Chip8) -> usize {
+    (*chip).chip.framebuffer().height()
+}
+
+/// # Safety
+/// `chip` is a live handle and `out` points to `len` writable bytes. Copies one palette index
+/// per pixel, row by row, and returns the number of bytes copied.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip: *const Chip8, out: *mut u8, len: usize) -> usize {
+    if out.is_null() {
+        return 0;
+    }
+    let pixels = (*chip).chip.framebuffer().pixels();
+    let n = pixels.len().min(len);
+    core::slice::from_raw_parts_mut(out, n).copy_from_slice(&pixels[..n]);
+    n
+}
+
+/// # Safety
+/// `chip` is a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip: *mut Chip8, key: u8, down: bool) {
+    let keypad = &(*chip).keypad;
+    if down {
+        keypad.press(key);
+    } else {
+        keypad.release(key);
+    }
+}
+
+/// # Safety
+/// `chip` is a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_beeping(chip: *const Chip8) -> bool {
+    (*chip).chip.registers().st > 0
+}
+
+/// # Safety
+/// `chip` is a live handle and `out` points to a writable `Chip8Registers`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_registers(chip: *const Chip8, out: *mut Chip8Registers) {
+    let r = (*chip).chip.registers();
+    *out = Chip8Registers {
+        v: r.v,
+        i: r.i,
+        pc: r.pc,
+        sp: r.sp,
+        dt: r.dt,
+        st: r.st,
+        stack: r.stack,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drives_a_machine_through_the_c_api() {
+        // LD V0, K ; LD V1, 0x2A ; EXIT
+        let rom = [0xF0, 0x0A, 0x61, 0x2A, 0x00, 0xFD];
+        unsafe {
+            let chip = chip8_new();
+            assert_eq!(chip8_load(chip, rom.as_ptr(), rom.len()), rom.len());
+            assert_eq!(chip8_step(chip), CHIP8_OK);
+            chip8_set_key(chip, 5, true);
+            chip8_step(chip);
+            chip8_set_key(chip, 5, false);
+            chip8_step(chip);
+            assert_eq!(chip8_step(chip), CHIP8_OK);
+            assert_eq!(chip8_step(chip), CHIP8_EXIT);
+            assert_eq!(chip8_step(chip), CHIP8_EXIT);
+            let mut r = Chip8Registers::default();
+            chip8_registers(chip, &mut r);
+            assert_eq!((r.v[0], r.v[1]), (5, 0x2A));
+            let mut frame = [1u8; 64 * 32];
+            assert_eq!(
+                chip8_framebuffer(chip, frame.as_mut_ptr(), frame.len()),
+                64 * 32
+            );
+            assert!(frame.iter().all(|&p| p == 0));
+            chip8_free(chip);
+        }
+    }
+}
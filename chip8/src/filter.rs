@@ -0,0 +1,272 @@
+// post-processing of the framebuffer before a frontend presents it.
+//
+// A frontend turns its palette-indexed pixels into a `Frame` and runs it through a `Pipeline`
+// of `FrameFilter`s, so that effects are implemented once for every frontend.
+use super::{Palette, Rgb};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    // row-major
+    pub pixels: Vec<Rgb>,
+}
+
+impl Frame {
+    // maps the palette indexes returned by `pixel` to colors.
+    pub fn from_indexed<F: Fn(usize, usize) -> u8>(
+        width: usize,
+        height: usize,
+        pixel: F,
+        palette: &Palette,
+    ) -> Self {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.push(palette.0[pixel(x, y) as usize & 3]);
+            }
+        }
+        Frame {
+            width,
+            height,
+            pixels,
+        }
+    }
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+    // the frame as a binary PPM (P6) image.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for p in &self.pixels {
+            out.extend_from_slice(&[p.0, p.1, p.2]);
+        }
+        out
+    }
+    // writes the frame as an 8-bit RGB PNG image.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: std::io::Write>(&self, w: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = self.pixels.iter().flat_map(|p| [p.0, p.1, p.2]).collect();
+        encoder.write_header()?.write_image_data(&data)
+    }
+}
+
+pub trait FrameFilter: Send {
+    // filters may change the size of the frame.
+    fn apply(&mut self, frame: Frame) -> Frame;
+}
+
+// filters applied in order.
+#[derive(Default)]
+pub struct Pipeline {
+    filters: Vec<Box<dyn FrameFilter>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+    pub fn with<F: FrameFilter + 'static>(mut self, filter: F) -> Self {
+        self.push(filter);
+        self
+    }
+    pub fn push<F: FrameFilter + 'static>(&mut self, filter: F) {
+        self.filters.push(Box::new(filter));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+    pub fn process(&mut self, frame: Frame) -> Frame {
+        self.filters
+            .iter_mut()
+            .fold(frame, |f, filter| filter.apply(f))
+    }
+}
+
+impl core::fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Pipeline({} filters)", self.filters.len())
+    }
+}
+
+impl core::str::FromStr for Pipeline {
+    type Err = String;
+    // parses comma separated filters: `decay[=percent]`, `scale=n`, `border=n[:#rrggbb]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut p = Pipeline::new();
+        for spec in s.split(',').map(|f| f.trim()).filter(|f| !f.is_empty()) {
+            let (name, arg) = match spec.split_once('=') {
+                Some((n, a)) => (n, Some(a)),
+                None => (spec, None),
+            };
+            let invalid = || format!("invalid filter: {}", spec);
+            match (name, arg) {
+                ("decay", None) => p.push(Decay::new(50)),
+                ("decay", Some(a)) => p.push(Decay::new(a.parse().map_err(|_| invalid())?)),
+                ("scale", Some(a)) => match a.parse() {
+                    Ok(n) if n > 0 => p.push(Scale(n)),
+                    _ => return Err(invalid()),
+                },
+                ("border", Some(a)) => {
+                    let (width, color) = match a.split_once(':') {
+                        Some((w, c)) => (w, c.parse::<Palette>()?.0[0]),
+                        None => (a, Rgb(0x80, 0x80, 0x80)),
+                    };
+                    p.push(Border {
+                        width: width.parse().map_err(|_| invalid())?,
+                        color,
+                    });
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(p)
+    }
+}
+
+// anti-flicker: blends every frame with the previous output so that pixels erased and
+// redrawn within a few frames do not blink. `keep` is the weight (percent) of the previous output.
+pub struct Decay {
+    keep: u32,
+    previous: Option<Frame>,
+}
+
+impl Decay {
+    pub fn new(keep: u32) -> Self {
+        Decay {
+            keep: keep.min(100),
+            previous: None,
+        }
+    }
+}
+
+impl FrameFilter for Decay {
+    fn apply(&mut self, mut frame: Frame) -> Frame {
+        if let Some(prev) = &self.previous {
+            if prev.width == frame.width && prev.height == frame.height {
+                let mix = |c: u8, p: u8| {
+                    ((c as u32 * (100 - self.keep) + p as u32 * self.keep) / 100) as u8
+                };
+                for (c, p) in frame.pixels.iter_mut().zip(&prev.pixels) {
+                    *c = Rgb(mix(c.0, p.0), mix(c.1, p.1), mix(c.2, p.2));
+                }
+            }
+        }
+        self.previous = Some(frame.clone());
+        frame
+    }
+}
+
+// nearest-neighbor scaling by an integer factor.
+pub struct Scale(pub usize);
+
+impl FrameFilter for Scale {
+    fn apply(&mut self, frame: Frame) -> Frame {
+        let (w, h) = (frame.width * self.0, frame.height * self.0);
+        let mut pixels = Vec::with_capacity(w * h);
+        for y in 0..h {
+            for x in 0..w {
+                pixels.push(frame.get(x / self.0, y / self.0));
+            }
+        }
+        Frame {
+            width: w,
+            height: h,
+            pixels,
+        }
+    }
+}
+
+// a frame of `width` pixels around the screen.
+pub struct Border {
+    pub width: usize,
+    pub color: Rgb,
+}
+
+impl FrameFilter for Border {
+    fn apply(&mut self, frame: Frame) -> Frame {
+        let b = self.width;
+        let (w, h) = (frame.width + 2 * b, frame.height + 2 * b);
+        let mut pixels = vec![self.color; w * h];
+        for y in 0..frame.height {
+            let row = &frame.pixels[y * frame.width..(y + 1) * frame.width];
+            pixels[(y + b) * w + b..(y + b) * w + b + frame.width].copy_from_slice(row);
+        }
+        Frame {
+            width: w,
+            height: h,
+            pixels,
+        }
+    }
+}
+
+// maps every color with a function, e.g. for color-blind friendly or monochrome output.
+pub struct MapColors<F>(pub F);
+
+impl<F: FnMut(Rgb) -> Rgb + Send> FrameFilter for MapColors<F> {
+    fn apply(&mut self, mut frame: Frame) -> Frame {
+        frame.pixels.iter_mut().for_each(|c| *c = (self.0)(*c));
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(pixels: &[u8], width: usize) -> Frame {
+        Frame::from_indexed(
+            width,
+            pixels.len() / width,
+            |x, y| pixels[y * width + x],
+            &Palette::default(),
+        )
+    }
+
+    #[test]
+    fn scale_and_border() {
+        let mut p: Pipeline = "scale=2, border=1:#ff0000".parse().unwrap();
+        let f = p.process(frame(&[0, 1], 2));
+        assert_eq!((f.width, f.height), (6, 4));
+        assert_eq!(f.get(0, 0), Rgb(0xff, 0, 0));
+        assert_eq!(f.get(2, 2), Rgb(0, 0, 0));
+        assert_eq!(f.get(3, 2), Rgb(0xff, 0xff, 0xff));
+        assert_eq!(f.get(5, 3), Rgb(0xff, 0, 0));
+        assert!("scale=0".parse::<Pipeline>().is_err());
+        assert!("blur".parse::<Pipeline>().is_err());
+    }
+
+    #[test]
+    fn decay_fades_erased_pixels() {
+        let mut p = Pipeline::new()
+            .with(Decay::new(50))
+            .with(MapColors(|c: Rgb| Rgb(c.0, 0, 0)));
+        p.process(frame(&[1], 1));
+        assert_eq!(p.process(frame(&[0], 1)).get(0, 0), Rgb(0x7f, 0, 0));
+        assert_eq!(p.process(frame(&[0], 1)).get(0, 0), Rgb(0x3f, 0, 0));
+    }
+
+    #[test]
+    fn exports_images() {
+        let f = frame(&[0, 1, 3, 0], 2);
+        let ppm = f.to_ppm();
+        assert!(ppm.starts_with(b"P6\n2 2\n255\n"));
+        assert_eq!(
+            &ppm[ppm.len() - 9..ppm.len() - 3],
+            &[0xff, 0xff, 0xff, 0x55, 0x55, 0x55]
+        );
+        #[cfg(feature = "png")]
+        {
+            let mut png = Vec::new();
+            f.write_png(&mut png).unwrap();
+            assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        }
+    }
+}
@@ -0,0 +1,279 @@
+// keyboard keys of the hex keypad for the frontends, from a preset or a TOML/JSON file:
+//
+//     preset = "azerty"                         # optional, the map to start from
+//     keypad = ["1234", "qwer", "asdf", "zxcv"] # the keypad rows 1 2 3 C / 4 5 6 D / ...
+//     split = ["6789", "yuio", "hjkl", "bnm,"]  # the right-hand machine of a split screen
+//
+// or `{"preset": "azerty", "keypad": [...]}`.
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+// the CHIP-8 hex keypad, row by row.
+pub const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+type Rows = [&'static str; 4];
+
+// name, keypad rows and split rows; the keyboard layouts keep the keys at the same positions,
+// `cosmac` takes the key labelled with the digit instead (shifted on a US keyboard for split).
+const PRESETS: [(&str, Rows, Rows); 4] = [
+    (
+        "qwerty",
+        ["1234", "qwer", "asdf", "zxcv"],
+        ["6789", "yuio", "hjkl", "bnm,"],
+    ),
+    (
+        "azerty",
+        ["1234", "azer", "qsdf", "wxcv"],
+        ["6789", "yuio", "hjkl", "bn,;"],
+    ),
+    (
+        "qwertz",
+        ["1234", "qwer", "asdf", "yxcv"],
+        ["6789", "zuio", "hjkl", "bnm,"],
+    ),
+    (
+        "cosmac",
+        ["123c", "456d", "789e", "a0bf"],
+        ["!@#C", "$%^D", "&*(E", "A)BF"],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    // four keys per keypad row
+    pub keypad: [String; 4],
+    pub split: [String; 4],
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::preset(PRESETS[0].0).unwrap()
+    }
+}
+
+impl KeyMap {
+    pub const PRESETS: [&'static str; 4] = [PRESETS[0].0, PRESETS[1].0, PRESETS[2].0, PRESETS[3].0];
+
+    pub fn preset(name: &str) -> Option<KeyMap> {
+        let (_, keypad, split) = PRESETS.iter().find(|(n, _, _)| *n == name)?;
+        Some(KeyMap {
+            keypad: keypad.map(String::from),
+            split: split.map(String::from),
+        })
+    }
+    // the keypad key of a keyboard key.
+    pub fn key(&self, c: char) -> Option<u8> {
+        lookup(&self.keypad, c)
+    }
+    // the keypad key of the right-hand machine of a split screen.
+    pub fn split_key(&self, c: char) -> Option<u8> {
+        lookup(&self.split, c)
+    }
+    // parses the contents of a keymap file (TOML or JSON, see the top of this module).
+    pub fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut tokens = tokenize(text)?.into_iter();
+        let (mut preset, mut keypad, mut split) = (None, None, None);
+        let mut rows = Vec::new();
+        while let Some(key) = tokens.next() {
+            let key = match key {
+                Token::Str(k) => k,
+                Token::Punct('{' | '}' | ',') => continue,
+                t => return Err(format!("expected a key, found {}", t)),
+            };
+            match tokens.next() {
+                Some(Token::Punct('=' | ':')) => {}
+                _ => return Err(format!("expected `=` after {}", key)),
+            }
+            let value = match tokens.next() {
+                Some(Token::Str(v)) => v,
+                Some(Token::Punct('[')) => {
+                    rows.clear();
+                    loop {
+                        match tokens.next() {
+                            Some(Token::Str(r)) => rows.push(r),
+                            Some(Token::Punct(',')) => {}
+                            Some(Token::Punct(']')) => break,
+                            _ => return Err(format!("unterminated list of {}", key)),
+                        }
+                    }
+                    String::new()
+                }
+                _ => return Err(format!("expected a value for {}", key)),
+            };
+            match key.as_str() {
+                "preset" => preset = Some(KeyMap::preset(&value).ok_or_else(|| unknown(&value))?),
+                "keypad" => keypad = Some(to_rows(&key, &rows)?),
+                "split" => split = Some(to_rows(&key, &rows)?),
+                _ => return Err(format!("unknown key `{}` (preset, keypad or split)", key)),
+            }
+        }
+        let mut m = preset.unwrap_or_default();
+        if let Some(rows) = keypad {
+            m.keypad = rows;
+        }
+        if let Some(rows) = split {
+            m.split = rows;
+        }
+        Ok(m)
+    }
+}
+
+impl core::str::FromStr for KeyMap {
+    type Err = String;
+    // a preset name (KeyMap::PRESETS), or with `std` the path of a keymap file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(m) = KeyMap::preset(s) {
+            return Ok(m);
+        }
+        #[cfg(feature = "std")]
+        if let Ok(text) = std::fs::read_to_string(s) {
+            return KeyMap::parse(&text).map_err(|e| format!("{}: {}", s, e));
+        }
+        Err(unknown(s))
+    }
+}
+
+// the preset's name, or the rows as a JSON keymap which `KeyMap::parse` reads back.
+impl core::fmt::Display for KeyMap {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match KeyMap::PRESETS
+            .iter()
+            .find(|n| KeyMap::preset(n).as_ref() == Some(self))
+        {
+            Some(name) => write!(f, "{}", name),
+            None => write!(
+                f,
+                "{{\"keypad\": {:?}, \"split\": {:?}}}",
+                self.keypad, self.split
+            ),
+        }
+    }
+}
+
+fn unknown(name: &str) -> String {
+    format!(
+        "unknown keymap `{}` (a file or one of {})",
+        name,
+        KeyMap::PRESETS.join(", ")
+    )
+}
+
+fn lookup(rows: &[String; 4], c: char) -> Option<u8> {
+    rows.iter()
+        .zip(KEYPAD)
+        .flat_map(|(keys, hex)| keys.chars().zip(hex))
+        .find(|(k, _)| *k == c)
+        .map(|(_, v)| v)
+}
+
+fn to_rows(key: &str, rows: &[String]) -> Result<[String; 4], String> {
+    if rows.len() != 4 || rows.iter().any(|r| r.chars().count() != 4) {
+        return Err(format!("{} takes four rows of four keys", key));
+    }
+    Ok([
+        rows[0].clone(),
+        rows[1].clone(),
+        rows[2].clone(),
+        rows[3].clone(),
+    ])
+}
+
+enum Token {
+    // a quoted string or a bare word
+    Str(String),
+    Punct(char),
+}
+
+impl core::fmt::Display for Token {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Punct(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+// splits TOML or JSON into strings and punctuation, dropping `#` comments.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '"' | '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') if c == '"' => match chars.next() {
+                            Some(e) => s.push(e),
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some(q) if q == c => break,
+                        Some(ch) => s.push(ch),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            '{' | '}' | '[' | ']' | '=' | ':' | ',' => tokens.push(Token::Punct(c)),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = String::from(c);
+                while let Some(ch) = chars.next_if(|ch| ch.is_alphanumeric() || *ch == '_') {
+                    s.push(ch);
+                }
+                tokens.push(Token::Str(s));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_map_keys_to_the_keypad() {
+        let m = KeyMap::default();
+        assert_eq!(
+            (m.key('1'), m.key('r'), m.key('x')),
+            (Some(1), Some(0xD), Some(0))
+        );
+        assert_eq!(m.split_key(','), Some(0xF));
+        let m = KeyMap::preset("cosmac").unwrap();
+        assert!((0..16).all(|k| m.key(char::from_digit(k, 16).unwrap()) == Some(k as u8)));
+        assert_eq!("azerty".parse::<KeyMap>().unwrap().key('a'), Some(4));
+        assert!("dvorak".parse::<KeyMap>().is_err());
+    }
+
+    #[test]
+    fn parses_toml_and_json() {
+        let toml =
+            "# my keys\npreset = \"azerty\"\nkeypad = ['1234', \"qwer\", \"asdf\", \"zxcv\"]\n";
+        let m = KeyMap::parse(toml).unwrap();
+        assert_eq!(m.key('q'), Some(4));
+        assert_eq!(m.split, KeyMap::preset("azerty").unwrap().split);
+        let json = r#"{"split": ["1234", "qwer", "asdf", "zxcv"], "preset": "qwertz"}"#;
+        let m = KeyMap::parse(json).unwrap();
+        assert_eq!((m.key('y'), m.split_key('z')), (Some(0xA), Some(0xA)));
+        assert!(KeyMap::parse("keypad = [\"123\"]").is_err());
+        assert!(KeyMap::parse("layout = \"qwerty\"").is_err());
+    }
+
+    #[test]
+    fn displays_as_a_preset_or_a_keymap_file() {
+        assert_eq!(KeyMap::preset("azerty").unwrap().to_string(), "azerty");
+        let mut m = KeyMap::default();
+        m.keypad[0] = "\"'\\#".to_string();
+        let text = m.to_string();
+        assert!(text.starts_with("{\"keypad\": [\"\\\"'"), "{}", text);
+        assert_eq!(KeyMap::parse(&text), Ok(m));
+    }
+}
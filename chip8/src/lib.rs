@@ -1,20 +1,141 @@
+// features: `std` (paced `run`, `Speed`, `FrameStep`, loading from `Read`), `alloc` (`Chip`
+// and the tool modules; `Core` needs no allocator), `rand` (CXNN from an entropy-seeded
+// generator), `log` (opcode tracing and warnings) and `wasm` (wasm-bindgen bindings in `wasm`),
+// `ffi` (C bindings in `ffi`) and `png` (`filter::Frame::write_png`).
+#![cfg_attr(not(feature = "std"), no_std)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// without the `log` feature the log macros compile to nothing but still check their arguments.
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        debug!($($arg)*)
+    };
+}
+
+#[cfg(feature = "alloc")]
+pub mod asm;
+#[cfg(feature = "alloc")]
+pub mod disasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "alloc")]
+pub mod filter;
+#[cfg(feature = "alloc")]
+pub mod keymap;
+pub mod opcode;
+#[cfg(feature = "alloc")]
+pub mod profile;
+#[cfg(feature = "alloc")]
+pub mod romdb;
+#[cfg(feature = "alloc")]
+pub mod romgen;
+#[cfg(feature = "alloc")]
+pub mod testrom;
+#[cfg(feature = "alloc")]
+pub mod usage;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 use self::Control::{Jump, Next, Skip};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::collections::{BTreeSet, VecDeque};
+#[cfg(feature = "alloc")]
+use alloc::format;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(feature = "log")]
 use log::*;
-use rand;
+#[cfg(feature = "rand")]
+use rand::{rngs::SmallRng, FromEntropy, Rng, RngCore};
+#[cfg(feature = "std")]
 use std::io::Read;
-use std::sync::{Arc, Mutex};
+#[cfg(feature = "std")]
+use std::sync::atomic::AtomicU16;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+#[cfg(feature = "std")]
 use std::thread;
+#[cfg(feature = "std")]
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "alloc")]
 pub struct Chip {
     pub cpu: CPU,
     pub ram: Ram,
     pub display: Box<dyn Display>,
     pub keyboard: Box<dyn Keyboard>,
+    framebuffer: FrameBuffer,
+    pub audio: Box<dyn Audio>,
+    #[cfg(feature = "std")]
+    speed: Speed,
+    #[cfg(feature = "std")]
+    frame_step: FrameStep,
+    #[cfg(feature = "std")]
+    stopper: Stopper,
+    // the sound timer was non-zero at the last check
+    beeping: bool,
+    // RAM after the last load, restored by `reset`
+    power_on: Option<Vec<u8>>,
+    rewind: Option<Rewind>,
+    // execution counts of `set_profiling`
+    profile: Option<profile::Profile>,
+    // requests and keys of the `ChipController`s, once one was handed out
+    #[cfg(feature = "std")]
+    remote: Option<Remote>,
+    // addresses where `run` stops before executing the instruction
+    breakpoints: BTreeSet<u16>,
+    // watchpoints, and the one hit by the last instruction
+    watches: Vec<Watch>,
+    watch_hit: Option<Watch>,
+    // called before and after each instruction, and whether one of them paused the machine
+    pre_hook: Option<Box<Hook>>,
+    post_hook: Option<Box<Hook>>,
+    hook_paused: bool,
+    // called with the screen at every 60Hz tick
+    frame_hook: Option<Box<FrameHook>>,
+    // cycles per 60Hz frame of `run_headless`
+    frame_cycles: u32,
+    // a jump to itself ends the runs (`Stopped::SelfJump`)
+    stop_on_self_jump: bool,
 }
+
+// see `Chip::set_hook`.
+#[cfg(feature = "alloc")]
+pub type Hook = dyn FnMut(&CpuView, &Ram, &opcode::Opcode) -> HookAction;
+// see `Chip::set_frame_hook`.
+#[cfg(feature = "alloc")]
+pub type FrameHook = dyn FnMut(&FrameBuffer);
+// the period of the timers and of the frames of `run`.
+#[cfg(feature = "std")]
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
 const HEAD_OF_SPRITE: usize = 0;
+const HEAD_OF_BIG_SPRITE: usize = 0x50;
 const HEAD_OF_PROGRAM: u16 = 0x200;
+// end of the built-in fonts, and where programs are loaded
+pub const FONT_END: usize = HEAD_OF_BIG_SPRITE + 0x10 * 10;
+pub const PROGRAM_START: usize = HEAD_OF_PROGRAM as usize;
+// cycles per frame of `run_headless` before `set_platform` (600Hz)
+#[cfg(feature = "alloc")]
+const DEFAULT_FRAME_CYCLES: u32 = 10;
 
+#[cfg(feature = "alloc")]
 impl Chip {
     pub fn new(dsp: Box<dyn Display>, kbd: Box<dyn Keyboard>) -> Chip {
         let mut chip = Chip {
@@ -22,21 +143,850 @@ impl Chip {
             ram: Ram::new(),
             display: dsp,
             keyboard: kbd,
+            framebuffer: FrameBuffer::new(WIDTH, HEIGHT),
+            audio: Box::new(Silent),
+            #[cfg(feature = "std")]
+            speed: Speed::new(),
+            #[cfg(feature = "std")]
+            frame_step: FrameStep::new(),
+            #[cfg(feature = "std")]
+            stopper: Stopper::new(),
+            beeping: false,
+            power_on: None,
+            rewind: None,
+            profile: None,
+            #[cfg(feature = "std")]
+            remote: None,
+            breakpoints: BTreeSet::new(),
+            watches: Vec::new(),
+            watch_hit: None,
+            pre_hook: None,
+            post_hook: None,
+            hook_paused: false,
+            frame_hook: None,
+            frame_cycles: DEFAULT_FRAME_CYCLES,
+            stop_on_self_jump: false,
         };
         chip.ram
-            .load_slice(HEAD_OF_SPRITE as u16, &SPRITES.concat());
+            .load_slice(HEAD_OF_SPRITE as u16, SPRITES.as_flattened());
+        chip.ram
+            .load_slice(HEAD_OF_BIG_SPRITE as u16, BIG_SPRITES.as_flattened());
         chip
     }
-    pub fn run(&mut self, hz: u32) {
-        self.cpu
-            .run(hz, &mut self.ram, &mut self.display, &self.keyboard)
+    // a machine without display, keyboard and sound, e.g. for `run_headless`.
+    pub fn headless() -> Chip {
+        Chip::new(Box::new(NullDisplay), Box::new(NullKeyboard))
+    }
+    // starts a `ChipBuilder`; the parts not given default to the null devices.
+    pub fn builder() -> ChipBuilder {
+        ChipBuilder::default()
+    }
+    // runs until the program leaves RAM, halts, reaches a breakpoint or is stopped by its
+    // `Stopper`; stops at the first trap. `hz` is the clock unless `Speed::set_cpu_hz` gives
+    // another one, read at every frame.
+    // The instruction `run` starts at is executed even if it has a breakpoint, so calling it
+    // again continues from a breakpoint.
+    #[cfg(feature = "std")]
+    pub fn run(&mut self, hz: u32) -> Result<Stopped, Trap> {
+        self.run_with(hz, |_, _| {})
+    }
+    // runs like `run` and calls `before_cycle` with the number of executed cycles and the machine
+    // before each cycle.
+    #[cfg(feature = "std")]
+    pub fn run_with<F: FnMut(u64, &Chip)>(
+        &mut self,
+        hz: u32,
+        before_cycle: F,
+    ) -> Result<Stopped, Trap> {
+        let stopped = self.run_frames(hz, before_cycle);
+        // the last frame may not have been shown yet.
+        if self.framebuffer.is_dirty() {
+            self.present();
+        }
+        stopped
+    }
+    #[cfg(feature = "std")]
+    fn run_frames<F: FnMut(u64, &Chip)>(
+        &mut self,
+        hz: u32,
+        mut before_cycle: F,
+    ) -> Result<Stopped, Trap> {
+        let mut cycles = 0;
+        // cycles left in the current frame, and the clock's remainder of 60 carried over so
+        // that e.g. 500 Hz runs 8 or 9 cycles a frame
+        let mut frame_left = 0;
+        let mut carry = 0;
+        let mut resets = self.stopper.resets();
+        // when the next frame is due; frames are paced against it rather than against the
+        // previous frame, so that the sleeps do not add up to a drift.
+        let mut next_frame = Instant::now();
+        loop {
+            if self.stopper.is_stopped() {
+                return Ok(Stopped::Requested);
+            }
+            if self.stopper.resets() != resets {
+                resets = self.stopper.resets();
+                self.reset();
+                frame_left = 0;
+            }
+            if frame_left == 0 {
+                if !self.speed.turbo() {
+                    let now = Instant::now();
+                    thread::sleep(next_frame.saturating_duration_since(now));
+                    // a late frame is not made up for by running the next ones faster.
+                    next_frame = next_frame.max(now) + FRAME;
+                }
+                self.frame_step.wait_frame();
+                self.serve_requests();
+                self.tick_60hz();
+                // the speed scales the instructions per frame; the timers stay at 60 Hz
+                let clock = self.speed.cpu_hz().unwrap_or(hz) * self.speed.percent() / 100 + carry;
+                (frame_left, carry) = ((clock / 60).max(1), clock % 60);
+            }
+            if let Some(stopped) = self.stop_before(cycles) {
+                return Ok(stopped);
+            }
+            before_cycle(cycles, self);
+            let step = self.step()?;
+            cycles += 1;
+            frame_left -= 1;
+            if let Some(stopped) = self.stop_after(&step) {
+                return Ok(stopped);
+            }
+        }
+    }
+    // why a run stops before executing the next instruction, after `cycles` cycles.
+    fn stop_before(&self, cycles: u64) -> Option<Stopped> {
+        // inst's length is 2 bytes.
+        if usize::from(self.cpu.pc + 1) >= RAM_SIZE {
+            return Some(Stopped::LeftRam);
+        }
+        if self.cpu.halted {
+            return Some(Stopped::Halted);
+        }
+        if cycles > 0 && self.at_breakpoint() {
+            return Some(Stopped::Breakpoint(self.cpu.pc));
+        }
+        if self.stop_on_self_jump && self.at_self_jump() {
+            return Some(Stopped::SelfJump(self.cpu.pc));
+        }
+        None
+    }
+    // the next instruction is a JP to its own address, which loops forever: many ROMs end so.
+    pub fn at_self_jump(&self) -> bool {
+        let pc = self.cpu.pc;
+        let word = self.ram.buf.get(usize::from(pc)..usize::from(pc) + 2);
+        word.is_some_and(|w| u16::from_be_bytes([w[0], w[1]]) == 0x1000 | pc)
+    }
+    // makes the runs return `Stopped::SelfJump` instead of spinning in a final `JP` to itself.
+    pub fn set_stop_on_self_jump(&mut self, on: bool) {
+        self.stop_on_self_jump = on;
+    }
+    // why a run stops after the instruction of `step`.
+    fn stop_after(&self, step: &Step) -> Option<Stopped> {
+        if self.hook_paused {
+            return Some(Stopped::Paused(self.cpu.pc));
+        }
+        self.watch_hit.map(|watch| Stopped::Watchpoint {
+            watch,
+            pc: step.addr,
+        })
+    }
+    // runs like `run` as fast as possible and without threads, for at most `max_cycles`
+    // cycles (`Stopped::CycleLimit`), counting the timers down every `cycles_per_frame`
+    // cycles; compare `framebuffer().hash()` and `state_hash()` with known values to test ROMs.
+    pub fn run_headless(&mut self, max_cycles: u64) -> Result<Stopped, Trap> {
+        self.run_until(StopCondition::Cycles(max_cycles))
+    }
+    // runs like `run_headless` until `until` is met, or the machine stops for one of the
+    // reasons of `run` (halting, breakpoints, watchpoints, hooks and the `Stopper`).
+    pub fn run_until(&mut self, until: StopCondition) -> Result<Stopped, Trap> {
+        self.run_until_with(until, |_, _| {})
+    }
+    // runs like `run_until` and calls `before_cycle` like `run_with` does, e.g. to play input.
+    pub fn run_until_with<F: FnMut(u64, &Chip)>(
+        &mut self,
+        until: StopCondition,
+        mut before_cycle: F,
+    ) -> Result<Stopped, Trap> {
+        let frame = u64::from(self.frame_cycles);
+        let mut cycles = 0;
+        // cycles since the last instruction which drew or scrolled
+        let mut quiet = 0;
+        loop {
+            match until {
+                StopCondition::Cycles(n) if cycles >= n => return Ok(Stopped::CycleLimit),
+                StopCondition::Pc(addr) if cycles > 0 && self.cpu.pc == addr => {
+                    return Ok(Stopped::Reached(addr))
+                }
+                StopCondition::DisplaySettled(n) if quiet >= n => return Ok(Stopped::Settled),
+                _ => {}
+            }
+            if cycles % frame == 0 {
+                #[cfg(feature = "std")]
+                {
+                    if self.stopper.is_stopped() {
+                        return Ok(Stopped::Requested);
+                    }
+                    if matches!(until, StopCondition::Deadline(t) if Instant::now() >= t) {
+                        return Ok(Stopped::Deadline);
+                    }
+                    self.serve_requests();
+                }
+                self.tick_60hz();
+            }
+            if let Some(stopped) = self.stop_before(cycles) {
+                return Ok(stopped);
+            }
+            before_cycle(cycles, self);
+            let step = self.step()?;
+            cycles += 1;
+            quiet = if step.display_changed { 0 } else { quiet + 1 };
+            if let Some(stopped) = self.stop_after(&step) {
+                return Ok(stopped);
+            }
+        }
+    }
+    // cycles per 60Hz frame of `run_headless`; `set_platform` sets it from the platform's speed.
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.frame_cycles
+    }
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        self.frame_cycles = cycles.max(1);
+    }
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+    // returns false when there was no breakpoint at `addr`.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+    // the next instruction has a breakpoint; for loops driving `cycle` or `step` themselves.
+    pub fn at_breakpoint(&self) -> bool {
+        !self.cpu.vblank_wait && !self.cpu.key_wait && self.breakpoints.contains(&self.cpu.pc)
+    }
+    // a DXYN under the display-wait quirk left the rest of the frame idle: `step` executes
+    // nothing (`Control::Wait`) until the next `tick_60hz`.
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.cpu.vblank_wait
+    }
+    // FX0A is waiting for a key to be pressed and released.
+    pub fn waiting_for_key(&self) -> bool {
+        self.cpu.key_wait
+    }
+    // makes `run` stop after an instruction writing RAM in the range or changing the register.
+    pub fn add_watchpoint(&mut self, watch: Watch) {
+        if !self.watches.contains(&watch) {
+            self.watches.push(watch);
+        }
+        if matches!(watch, Watch::Ram { .. }) && self.ram.writes.is_none() {
+            self.ram.writes = Some(Vec::new());
+        }
+    }
+    // returns false when `watch` was not set.
+    pub fn remove_watchpoint(&mut self, watch: Watch) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|w| *w != watch);
+        if !self.watches.iter().any(|w| matches!(w, Watch::Ram { .. })) {
+            self.ram.writes = None;
+        }
+        self.watches.len() != before
+    }
+    pub fn watchpoints(&self) -> &[Watch] {
+        &self.watches
+    }
+    // the watchpoint hit by the last `cycle` or `step`, if any.
+    pub fn watch_hit(&self) -> Option<Watch> {
+        self.watch_hit
+    }
+    // calls `hook` with the registers, RAM and the instruction about to be executed, before every
+    // instruction of `step`, `cycle` and `run` (words which are not opcodes, i.e. 0000, skip it).
+    // `Pause` leaves the instruction unexecuted and makes `run` return `Stopped::Paused`; the
+    // hook is asked again when the machine continues.
+    pub fn set_hook(
+        &mut self,
+        hook: impl FnMut(&CpuView, &Ram, &opcode::Opcode) -> HookAction + 'static,
+    ) {
+        self.pre_hook = Some(Box::new(hook));
+    }
+    // calls `hook` after every instruction with the registers and RAM it left and the instruction.
+    pub fn set_post_hook(
+        &mut self,
+        hook: impl FnMut(&CpuView, &Ram, &opcode::Opcode) -> HookAction + 'static,
+    ) {
+        self.post_hook = Some(Box::new(hook));
+    }
+    // calls `hook` with the screen once per 60Hz frame (`tick_60hz`), whether it changed or not,
+    // e.g. for recording videos at a steady frame rate.
+    pub fn set_frame_hook(&mut self, hook: impl FnMut(&FrameBuffer) + 'static) {
+        self.frame_hook = Some(Box::new(hook));
+    }
+    pub fn clear_hooks(&mut self) {
+        self.pre_hook = None;
+        self.post_hook = None;
+        self.frame_hook = None;
+    }
+    // a hook returned `Pause` during the last `step` or `cycle`.
+    pub fn hook_paused(&self) -> bool {
+        self.hook_paused
+    }
+    // returns the handle controlling the emulation speed of `run`.
+    #[cfg(feature = "std")]
+    pub fn speed(&self) -> Speed {
+        self.speed.clone()
+    }
+    #[cfg(feature = "std")]
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+    }
+    // returns the handle pausing `run` and advancing it frame by frame.
+    #[cfg(feature = "std")]
+    pub fn frame_step(&self) -> FrameStep {
+        self.frame_step.clone()
+    }
+    #[cfg(feature = "std")]
+    pub fn set_frame_step(&mut self, frame_step: FrameStep) {
+        self.frame_step = frame_step;
+    }
+    // returns the handle ending `run` from another thread or a signal handler.
+    #[cfg(feature = "std")]
+    pub fn stopper(&self) -> Stopper {
+        self.stopper.clone()
+    }
+    #[cfg(feature = "std")]
+    pub fn set_stopper(&mut self, stopper: Stopper) {
+        self.stopper = stopper;
+    }
+    pub fn cycle(&mut self) -> Result<(), Trap> {
+        self.step().map(|_| ())
+    }
+    // executes one instruction like `cycle` and tells what it did.
+    pub fn step(&mut self) -> Result<Step, Trap> {
+        if let Some(w) = self.ram.writes.as_mut() {
+            w.clear();
+        }
+        self.watch_hit = None;
+        self.hook_paused = false;
+        if self.cpu.vblank_wait {
+            return Ok(Step::wait(self.cpu.pc));
+        }
+        let pc = self.cpu.pc as usize;
+        let word = match self.ram.buf.get(pc..pc + 2) {
+            Some(w) => u16::from_be_bytes([w[0], w[1]]),
+            None => 0,
+        };
+        if let (Some(hook), Some(op)) = (self.pre_hook.as_mut(), opcode::decode(word)) {
+            match hook(&self.cpu.view(), &self.ram, &op) {
+                HookAction::Continue => {}
+                HookAction::Pause => {
+                    self.hook_paused = true;
+                    return Ok(Step {
+                        addr: self.cpu.pc,
+                        opcode: Some(op),
+                        control: Control::Jump(self.cpu.pc),
+                        changed_v: 0,
+                        changed_i: false,
+                        display_changed: false,
+                    });
+                }
+                HookAction::Replace(view) => self.cpu.apply(view),
+            }
+        }
+        let dt = self.cpu.dt.get();
+        #[cfg(feature = "std")]
+        let remote =
+            (self.remote.as_ref()).map(|r| RemoteKeyboard(self.keyboard.as_ref(), &r.keys));
+        #[cfg(feature = "std")]
+        let keyboard: &dyn Keyboard = match &remote {
+            Some(k) => k,
+            None => self.keyboard.as_ref(),
+        };
+        #[cfg(not(feature = "std"))]
+        let keyboard = self.keyboard.as_ref();
+        let step = self
+            .cpu
+            .cycle(&mut self.ram, &mut self.framebuffer, keyboard)?;
+        if let Some(p) = self
+            .profile
+            .as_mut()
+            .filter(|_| step.control != Control::Wait)
+        {
+            p.record(step.addr, word);
+        }
+        if let (Some(hook), Some(op)) = (self.post_hook.as_mut(), step.opcode) {
+            match hook(&self.cpu.view(), &self.ram, &op) {
+                HookAction::Continue => {}
+                HookAction::Pause => self.hook_paused = true,
+                HookAction::Replace(view) => self.cpu.apply(view),
+            }
+        }
+        self.update_audio();
+        let writes = self.ram.writes.as_deref().unwrap_or_default();
+        self.watch_hit = self.watches.iter().copied().find(|w| match *w {
+            Watch::Ram { start, end } => writes.iter().any(|a| (start..end).contains(a)),
+            Watch::V(x) => step.changed_v & 1 << x != 0,
+            Watch::I => step.changed_i,
+            Watch::Dt => self.cpu.dt.get() != dt,
+        });
+        Ok(step)
+    }
+    // counts the delay and sound timers down and presents the screen when it changed; call it
+    // once per 60Hz frame when driving `cycle` directly (`run` does it every hz/60 cycles).
+    pub fn tick_60hz(&mut self) {
+        if self.framebuffer.is_dirty() {
+            self.present();
+        }
+        self.cpu.vblank_wait = false;
+        self.cpu.dt.tick();
+        self.cpu.st.tick();
+        self.update_audio();
+        if let Some(hook) = self.frame_hook.as_mut() {
+            hook(&self.framebuffer);
+        }
+        if self.rewind.as_mut().is_some_and(|r| r.due()) {
+            let state = self.save_state();
+            if let Some(r) = self.rewind.as_mut() {
+                r.push(state);
+            }
+        }
+    }
+    // keeps up to `depth` snapshots for `rewind`, one every `interval` frames (taken by
+    // `tick_60hz`); a depth of 0 turns rewinding off.
+    pub fn set_rewind(&mut self, depth: usize, interval: u32) {
+        self.rewind = (depth > 0).then(|| Rewind {
+            depth,
+            interval: interval.max(1),
+            since: 0,
+            snapshots: VecDeque::with_capacity(depth),
+        });
+    }
+    // goes back to the newest snapshot at least `frames` frames old (or the oldest one kept)
+    // and returns how many frames were rewound; snapshots newer than it are dropped.
+    pub fn rewind(&mut self, frames: u32) -> Option<u32> {
+        let r = self.rewind.as_mut()?;
+        let back = frames.saturating_sub(r.since).div_ceil(r.interval) as usize;
+        let back = back.min(r.snapshots.len().checked_sub(1)?);
+        let age = r.since + back as u32 * r.interval;
+        r.snapshots.truncate(r.snapshots.len() - back);
+        r.since = 0;
+        let state = r.snapshots.back()?.clone();
+        self.load_state(state);
+        Some(age)
+    }
+    // counts the instructions executed by `step` per opcode type and per address; turning it
+    // on again starts the counts over.
+    pub fn set_profiling(&mut self, on: bool) {
+        self.profile = on.then(profile::Profile::default);
+    }
+    // the counts since `set_profiling(true)`; print it for a report.
+    pub fn profile(&self) -> Option<&profile::Profile> {
+        self.profile.as_ref()
+    }
+    pub fn set_audio(&mut self, audio: Box<dyn Audio>) {
+        self.audio = audio;
+    }
+    // starts or stops the beep when the sound timer has become non-zero or zero.
+    fn update_audio(&mut self) {
+        let beeping = self.cpu.st.get() > 0;
+        if beeping != self.beeping {
+            self.beeping = beeping;
+            if beeping {
+                self.audio.start_beep();
+            } else {
+                self.audio.stop_beep();
+            }
+        }
+    }
+    // logs a warning the first time each kind of quirk-sensitive opcode is executed.
+    pub fn warn_quirks(&mut self, enabled: bool) {
+        self.cpu.quirk_warnings = enabled;
+    }
+    // returns the quirk first hit by the last cycle, if any (only while warn_quirks is enabled).
+    pub fn quirk_hit(&self) -> Option<Quirk> {
+        self.cpu.quirk_hit
+    }
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+    // whether the program executed EXIT (00FD); `step` then executes nothing until `reset`.
+    pub fn halted(&self) -> bool {
+        self.cpu.halted
+    }
+    // the registers, for debuggers; `set_registers` writes them back.
+    pub fn registers(&self) -> CpuView {
+        self.cpu.view()
+    }
+    pub fn set_registers(&mut self, view: CpuView) {
+        self.cpu.apply(view);
+        self.update_audio();
+    }
+    // one register, e.g. `chip.register("v3".parse()?)`.
+    pub fn register(&self, r: Register) -> u16 {
+        self.registers().get(r)
+    }
+    pub fn set_register(&mut self, r: Register, value: u16) {
+        let mut view = self.registers();
+        view.set(r, value);
+        self.set_registers(view);
+    }
+    pub fn i(&self) -> u16 {
+        self.cpu.i
+    }
+    // FNV-1a over the registers, timers, stack and RAM; two runs of a program with the same
+    // input agree on this hash cycle by cycle.
+    pub fn state_hash(&self) -> u64 {
+        let cpu = &self.cpu;
+        let mut bytes = cpu.v.to_vec();
+        bytes.extend_from_slice(&cpu.i.to_be_bytes());
+        bytes.extend_from_slice(&cpu.pc.to_be_bytes());
+        bytes.push(cpu.sp);
+        bytes.extend_from_slice(&[cpu.dt.get(), cpu.st.get()]);
+        cpu.stack
+            .iter()
+            .for_each(|a| bytes.extend_from_slice(&a.to_be_bytes()));
+        bytes.extend_from_slice(&cpu.rpl);
+        bytes.push(self.framebuffer.hires() as u8);
+        bytes.push(cpu.planes);
+        bytes.extend_from_slice(&self.ram.buf);
+        bytes.iter().fold(0xcbf29ce484222325, |h, b| {
+            (h ^ *b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.quirks = quirks;
+    }
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.set_quirks(quirks);
+        self
+    }
+    pub fn quirks(&self) -> Quirks {
+        self.cpu.quirks
+    }
+    // the SUPER-CHIP RPL user flags of FX75/FX85, which games use for high scores; frontends
+    // keep them between runs like the HP48's memory did.
+    pub fn rpl_flags(&self) -> [u8; 8] {
+        self.cpu.rpl
+    }
+    pub fn set_rpl_flags(&mut self, flags: [u8; 8]) {
+        self.cpu.rpl = flags;
+    }
+    pub fn save_state(&self) -> ChipState {
+        let cpu = &self.cpu;
+        ChipState {
+            v: cpu.v,
+            i: cpu.i,
+            pc: cpu.pc,
+            sp: cpu.sp,
+            stack: cpu.stack,
+            dt: cpu.dt.get(),
+            st: cpu.st.get(),
+            hires: self.framebuffer.hires(),
+            halted: cpu.halted,
+            rpl: cpu.rpl,
+            planes: cpu.planes,
+            vblank_wait: cpu.vblank_wait,
+            key_wait: cpu.key_wait,
+            key_ignored: cpu.key_ignored,
+            key_down: cpu.key_down,
+            ram: self.ram.buf.to_vec(),
+            framebuffer: self.framebuffer.pixels().to_vec(),
+        }
+    }
+    // restores a snapshot of `save_state`; a shorter RAM image leaves the rest of RAM as it is.
+    pub fn load_state(&mut self, state: ChipState) {
+        let cpu = &mut self.cpu;
+        cpu.v = state.v;
+        cpu.i = state.i;
+        cpu.pc = state.pc;
+        cpu.sp = state.sp;
+        cpu.stack = state.stack;
+        cpu.dt.set(state.dt);
+        cpu.st.set(state.st);
+        cpu.halted = state.halted;
+        cpu.rpl = state.rpl;
+        cpu.planes = state.planes;
+        cpu.vblank_wait = state.vblank_wait;
+        cpu.key_wait = state.key_wait;
+        cpu.key_ignored = state.key_ignored;
+        cpu.key_down = state.key_down;
+        let n = state.ram.len().min(RAM_SIZE);
+        self.ram.buf[..n].copy_from_slice(&state.ram[..n]);
+        if self.framebuffer.hires() != state.hires {
+            self.framebuffer.set_hires(state.hires);
+        }
+        if !state.framebuffer.is_empty() {
+            self.framebuffer.restore(&state.framebuffer);
+        }
+        self.present();
+        self.update_audio();
+    }
+    // makes CXNN reproducible: the same seed yields the same numbers on every run.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.cpu.rng = Random::seeded(seed);
+    }
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+    // emulates `platform`: its font, program start, screen size and quirks; call it before `load`.
+    pub fn set_platform(&mut self, platform: &Platform) {
+        self.ram
+            .load_slice(HEAD_OF_SPRITE as u16, platform.font.as_flattened());
+        self.cpu.start = platform.start;
+        self.cpu.pc = platform.start;
+        self.cpu.quirks = platform.quirks;
+        self.framebuffer.set_size(platform.width, platform.height);
+        self.set_cycles_per_frame(platform.cpu_hz / 60);
+    }
+    pub fn with_platform(mut self, platform: &Platform) -> Self {
+        self.set_platform(platform);
+        self
     }
-    pub fn cycle(&mut self) {
+    // draws the CXNN numbers from `rng`.
+    #[cfg(feature = "rand")]
+    pub fn set_rng<R: RngCore + 'static>(&mut self, rng: R) {
+        self.cpu.rng = Random::Rng(Box::new(rng));
+    }
+    // passes the colors for each plane combination to the display.
+    pub fn set_palette(&mut self, palette: &Palette) {
+        self.display.set_palette(palette);
+        self.present();
+    }
+    // hands the framebuffer to the display and starts tracking changes anew.
+    fn present(&mut self) {
+        self.display.present(&self.framebuffer);
+        self.framebuffer.mark_presented();
+    }
+    // the screen as drawn by the program.
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
+    }
+    // reads the whole ROM from `r` and loads it like `load_slice`.
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, r: &mut dyn Read) -> Result<usize, LoadError> {
+        let mut rom = Vec::new();
+        r.read_to_end(&mut rom)?;
+        self.load_slice(&rom)
+    }
+    // copies a ROM image to the program area, refusing empty ROMs and ROMs which do not fit in
+    // RAM; returns the number of bytes loaded.
+    pub fn load_slice(&mut self, rom: &[u8]) -> Result<usize, LoadError> {
+        self.check_rom(rom)?;
+        self.ram.load_slice(self.cpu.start, rom);
+        self.power_on = Some(self.ram.buf.to_vec());
+        Ok(rom.len())
+    }
+    fn check_rom(&self, rom: &[u8]) -> Result<(), LoadError> {
+        let max = RAM_SIZE - self.cpu.start as usize;
+        if rom.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        if rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+    // replaces the program with a new build of it and starts over: the program area is cleared
+    // and loaded, then the machine is `reset`. A ROM which cannot be loaded leaves it as it was.
+    #[cfg(feature = "std")]
+    pub fn reload(&mut self, r: &mut dyn Read) -> Result<usize, LoadError> {
+        let mut rom = Vec::new();
+        r.read_to_end(&mut rom)?;
+        self.reload_slice(&rom)
+    }
+    pub fn reload_slice(&mut self, rom: &[u8]) -> Result<usize, LoadError> {
+        self.check_rom(rom)?;
+        self.ram.buf[self.cpu.start as usize..].fill(0);
+        let n = self.load_slice(rom)?;
+        self.reset();
+        Ok(n)
+    }
+    // returns to the power-on state with the ROM still loaded: RAM as after the last load,
+    // cleared registers, timers and screen, PC at the program start. The platform, quirks,
+    // random number generator and RPL flags are kept.
+    pub fn reset(&mut self) {
+        let cpu = &mut self.cpu;
+        cpu.v = [0; 16];
+        cpu.i = 0;
+        cpu.pc = cpu.start;
+        cpu.sp = 0;
+        cpu.stack = [0; 16];
+        cpu.dt.set(0);
+        cpu.st.set(0);
+        cpu.halted = false;
+        cpu.planes = 1;
+        cpu.vblank_wait = false;
+        if cpu.key_wait {
+            cpu.key_wait = false;
+            self.keyboard.waiting(false);
+        }
+        match &self.power_on {
+            Some(ram) => self.ram.buf.copy_from_slice(ram),
+            None => self.ram.buf[self.cpu.start as usize..].fill(0),
+        }
+        self.framebuffer.set_hires(false);
+        self.framebuffer.clear();
+        self.present();
+        self.update_audio();
+    }
+    // pauses `run` at the next frame boundary (see `FrameStep`).
+    #[cfg(feature = "std")]
+    pub fn pause(&self) {
+        self.frame_step.pause();
+    }
+    #[cfg(feature = "std")]
+    pub fn resume(&self) {
+        self.frame_step.resume();
+    }
+    #[cfg(feature = "std")]
+    pub fn is_paused(&self) -> bool {
+        self.frame_step.is_paused()
+    }
+    // returns a handle controlling `run` from other threads; it shares the current `FrameStep`
+    // and `Stopper`, so set those first.
+    #[cfg(feature = "std")]
+    pub fn controller(&mut self) -> ChipController {
+        let remote = self.remote.get_or_insert_with(|| {
+            let (sender, requests) = mpsc::channel();
+            Remote {
+                sender,
+                requests,
+                keys: Arc::default(),
+            }
+        });
+        ChipController {
+            frame_step: self.frame_step.clone(),
+            stopper: self.stopper.clone(),
+            requests: remote.sender.clone(),
+            keys: remote.keys.clone(),
+        }
+    }
+    // answers the requests of the controllers; `run` does it at every frame.
+    #[cfg(feature = "std")]
+    fn serve_requests(&mut self) {
+        let Some(remote) = self.remote.as_ref() else {
+            return;
+        };
+        let requests: Vec<Request> = remote.requests.try_iter().collect();
+        for request in requests {
+            match request {
+                Request::Snapshot(reply) => {
+                    let _ = reply.send(self.save_state());
+                }
+                Request::Reload(rom, reply) => {
+                    let _ = reply.send(self.reload_slice(&rom));
+                }
+            }
+        }
+    }
+}
+
+// the bare interpreter for targets without an allocator, e.g. a microcontroller driving a small
+// OLED: no hooks, breakpoints, rewind or pacing, and the display and keyboard are type
+// parameters instead of boxed trait objects. Call `step` at the CPU clock and `tick_60hz` at
+// 60Hz from the board's timer.
+pub struct Core<D: Display, K: Keyboard> {
+    cpu: CPU,
+    pub ram: Ram,
+    framebuffer: FrameBuffer,
+    pub display: D,
+    pub keyboard: K,
+}
+
+impl<D: Display, K: Keyboard> Core<D, K> {
+    pub fn new(display: D, keyboard: K) -> Self {
+        let mut core = Core {
+            cpu: CPU::new(),
+            ram: Ram::new(),
+            framebuffer: FrameBuffer::new(WIDTH, HEIGHT),
+            display,
+            keyboard,
+        };
+        core.ram
+            .load_slice(HEAD_OF_SPRITE as u16, SPRITES.as_flattened());
+        core.ram
+            .load_slice(HEAD_OF_BIG_SPRITE as u16, BIG_SPRITES.as_flattened());
+        core
+    }
+    // see `Chip::set_platform`.
+    pub fn set_platform(&mut self, platform: &Platform) {
+        self.ram
+            .load_slice(HEAD_OF_SPRITE as u16, platform.font.as_flattened());
+        self.cpu.start = platform.start;
+        self.cpu.pc = platform.start;
+        self.cpu.quirks = platform.quirks;
+        self.framebuffer.set_size(platform.width, platform.height);
+    }
+    pub fn with_platform(mut self, platform: &Platform) -> Self {
+        self.set_platform(platform);
+        self
+    }
+    pub fn set_seed(&mut self, seed: u64) {
+        self.cpu.rng = Random::seeded(seed);
+    }
+    // copies a ROM image to the program area like `Chip::load_slice`.
+    pub fn load_slice(&mut self, rom: &[u8]) -> Result<usize, LoadError> {
+        let max = RAM_SIZE - self.cpu.start as usize;
+        if rom.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        if rom.len() > max {
+            return Err(LoadError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.ram.load_slice(self.cpu.start, rom);
+        Ok(rom.len())
+    }
+    // executes one instruction; waits (returns `Control::Wait`) under the display-wait quirk
+    // until the next `tick_60hz`.
+    pub fn step(&mut self) -> Result<Step, Trap> {
+        if self.cpu.vblank_wait {
+            return Ok(Step::wait(self.cpu.pc));
+        }
         self.cpu
-            .cycle(&mut self.ram, &mut self.display, &self.keyboard)
+            .cycle(&mut self.ram, &mut self.framebuffer, &self.keyboard)
+    }
+    // counts the timers down and presents the screen when it changed.
+    pub fn tick_60hz(&mut self) {
+        if self.framebuffer.is_dirty() {
+            self.display.present(&self.framebuffer);
+            self.framebuffer.mark_presented();
+        }
+        self.cpu.vblank_wait = false;
+        self.cpu.dt.tick();
+        self.cpu.st.tick();
+    }
+    // whether the program executed EXIT (00FD); `step` then executes nothing.
+    pub fn halted(&self) -> bool {
+        self.cpu.halted
+    }
+    // whether the sound timer is running, e.g. to drive a buzzer pin.
+    pub fn beeping(&self) -> bool {
+        self.cpu.st.get() > 0
+    }
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.framebuffer
     }
-    pub fn load(&mut self, r: &mut dyn Read) -> Result<usize, std::io::Error> {
-        self.ram.load(HEAD_OF_PROGRAM, r)
+    pub fn registers(&self) -> CpuView {
+        self.cpu.view()
+    }
+    pub fn set_registers(&mut self, view: CpuView) {
+        self.cpu.apply(view);
+    }
+    // see `Chip::rpl_flags`, e.g. to keep high scores in flash.
+    pub fn rpl_flags(&self) -> [u8; 8] {
+        self.cpu.rpl
+    }
+    pub fn set_rpl_flags(&mut self, flags: [u8; 8]) {
+        self.cpu.rpl = flags;
     }
 }
 
@@ -48,6 +998,26 @@ pub struct CPU {
     stack: [u16; 16],
     dt: Timer,
     st: Timer,
+    quirk_warnings: bool,
+    quirks_seen: u8,
+    quirk_hit: Option<Quirk>,
+    rng: Random,
+    quirks: Quirks,
+    // SUPER-CHIP state: EXIT executed and the RPL user flags; the 128x64 mode is the
+    // framebuffer's.
+    halted: bool,
+    rpl: [u8; 8],
+    // XO-CHIP: the bits of the planes which DXYN, 00E0 and the scrolls act on (FN01)
+    planes: u8,
+    // DXYN ran under the display-wait quirk; nothing executes until the next 60Hz tick
+    vblank_wait: bool,
+    // FX0A waits and executes again until a key is pressed and released: the keys held when
+    // it started (ignored until released) and the key pressed since
+    key_wait: bool,
+    key_ignored: u16,
+    key_down: Option<u8>,
+    // where programs are loaded (see `Platform`)
+    start: u16,
 }
 
 impl CPU {
@@ -60,46 +1030,145 @@ impl CPU {
             pc: HEAD_OF_PROGRAM,
             dt: Timer::new(),
             st: Timer::new(),
+            quirk_warnings: false,
+            quirks_seen: 0,
+            quirk_hit: None,
+            rng: Random::new(),
+            quirks: Quirks::default(),
+            halted: false,
+            rpl: [0; 8],
+            planes: 1,
+            vblank_wait: false,
+            key_wait: false,
+            key_ignored: 0,
+            key_down: None,
+            start: HEAD_OF_PROGRAM,
         }
     }
 }
 
 impl CPU {
+    // executes one instruction; a trapped instruction leaves the registers untouched.
     fn cycle(
         &mut self,
         ram: &mut Ram,
-        display: &mut Box<dyn Display>,
-        keyboard: &Box<dyn Keyboard>,
-    ) {
+        display: &mut FrameBuffer,
+        keyboard: &dyn Keyboard,
+    ) -> Result<Step, Trap> {
+        if self.halted {
+            return Ok(Step::wait(self.pc));
+        }
+        // below the program area lies the interpreter (and the fonts here); a whole instruction
+        // has to fit before the end of RAM.
+        if self.pc < HEAD_OF_PROGRAM || usize::from(self.pc) + 1 >= RAM_SIZE {
+            return Err(Trap::PcOutOfRange { pc: self.pc });
+        }
         let op = Inst::from(ram.fetch(self.pc));
-        self.execute(op, ram, display, keyboard);
+        let (addr, v, i) = (self.pc, self.v, self.i);
+        if self.quirk_warnings {
+            self.watch_quirk(&op, display);
+        }
+        let control = self.execute(op, ram, display, keyboard)?;
+        match control {
+            Next => self.pc += 2,
+            Skip => self.pc += 2 + ram.inst_len(self.pc + 2),
+            Jump(r) => {
+                if r % 2 != 0 {
+                    warn!("jump to the odd address {:03X} at {:03X}", r, addr);
+                }
+                self.pc = r;
+            }
+            Control::Wait => {}
+        }
         self.dump();
+        let opcode = opcode::decode(op.into());
+        use opcode::Opcode::*;
+        Ok(Step {
+            addr,
+            opcode,
+            control,
+            changed_v: (0..16)
+                .filter(|&n| v[n] != self.v[n])
+                .fold(0, |m, n| m | 1 << n),
+            changed_i: i != self.i,
+            display_changed: matches!(
+                opcode,
+                Some(
+                    Cls | ScrollDown(_)
+                        | ScrollUp(_)
+                        | ScrollRight
+                        | ScrollLeft
+                        | Lores
+                        | Hires
+                        | Drw { .. }
+                )
+            ),
+        })
     }
     fn execute(
         &mut self,
         op: Inst,
         ram: &mut Ram,
-        display: &mut Box<dyn Display>,
-        keyboard: &Box<dyn Keyboard>,
-    ) {
-        debug!("op:{:?}", op);
+        display: &mut FrameBuffer,
+        keyboard: &dyn Keyboard,
+    ) -> Result<Control, Trap> {
+        #[cfg(feature = "alloc")]
+        debug!("{:03x}: {}", self.pc, disasm::mnemonic(op.into()));
         let ctl = match op {
             Inst(0, 0, 0xe, 0) => {
                 debug!("CLS");
-                display.clear();
+                display.clear_planes(self.planes);
                 Next
             }
             Inst(0, 0, 0xe, 0xe) => {
                 debug!("RET");
-                self.sp -= 1;
-                Jump(self.stack[(self.sp) as usize] + 2)
+                let pc = self.pc;
+                let sp = self.sp.checked_sub(1).ok_or(Trap::StackUnderflow { pc })?;
+                let ret = *self
+                    .stack
+                    .get(sp as usize)
+                    .ok_or(Trap::StackOverflow { pc })?;
+                self.sp = sp;
+                Jump(ret.wrapping_add(2))
+            }
+            Inst(0, 0, 0xC, n) => {
+                debug!("SCD {}", n);
+                let rows = n as usize / self.scroll_divisor(display);
+                display.scroll(0, rows as isize, self.planes);
+                Next
+            }
+            Inst(0, 0, 0xF, 0xB) => {
+                debug!("SCR");
+                let columns = 4 / self.scroll_divisor(display);
+                display.scroll(columns as isize, 0, self.planes);
+                Next
+            }
+            Inst(0, 0, 0xF, 0xC) => {
+                debug!("SCL");
+                let columns = 4 / self.scroll_divisor(display);
+                display.scroll(-(columns as isize), 0, self.planes);
+                Next
+            }
+            Inst(0, 0, 0xF, 0xD) => {
+                debug!("EXIT");
+                self.halted = true;
+                Jump(self.pc)
+            }
+            Inst(0, 0, 0xF, m @ (0xE | 0xF)) => {
+                debug!("{}", if m == 0xF { "HIGH" } else { "LOW" });
+                display.set_hires(m == 0xF);
+                Next
             }
             Inst(0, n1, n2, n3) => Jump(addr(n1, n2, n3)),
             Inst(1, n1, n2, n3) => Jump(addr(n1, n2, n3)),
             Inst(2, n1, n2, n3) => {
                 let f = addr(n1, n2, n3);
                 debug!("CALL 0x{:x}", f);
-                self.stack[self.sp as usize] = self.pc;
+                let pc = self.pc;
+                *self
+                    .stack
+                    .get_mut(self.sp as usize)
+                    .ok_or(Trap::StackOverflow { pc })? = pc;
                 self.sp += 1;
                 Jump(f)
             }
@@ -149,16 +1218,25 @@ impl CPU {
             Inst(8, x, y, 1) => {
                 debug!("OR V{}, V{}", x, y);
                 self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
                 Next
             }
             Inst(8, x, y, 2) => {
                 debug!("AND V{}, V{}", x, y);
                 self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
                 Next
             }
             Inst(8, x, y, 3) => {
                 debug!("XOR V{}, V{}", x, y);
                 self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
                 Next
             }
             Inst(8, x, y, 4) => {
@@ -175,10 +1253,11 @@ impl CPU {
                 self.v[0xF] = if !overflowed { 1 } else { 0 };
                 Next
             }
-            Inst(8, x, _, 6) => {
+            Inst(8, x, y, 6) => {
                 debug!("SHR V{}", x);
-                self.v[0xF] = self.v[x as usize] & 1;
-                self.v[x as usize] >>= 1;
+                let src = self.v[if self.quirks.shift_vy { y } else { x } as usize];
+                self.v[x as usize] = src >> 1;
+                self.v[0xF] = src & 1;
                 Next
             }
             Inst(8, x, y, 7) => {
@@ -188,10 +1267,11 @@ impl CPU {
                 self.v[0xF] = if !overflowed { 1 } else { 0 };
                 Next
             }
-            Inst(8, x, _, 0xE) => {
+            Inst(8, x, y, 0xE) => {
                 debug!("SHL V{}", x);
-                self.v[0xF] = self.v[x as usize] >> 7 & 1;
-                self.v[x as usize] = self.v[x as usize] << 1;
+                let src = self.v[if self.quirks.shift_vy { y } else { x } as usize];
+                self.v[x as usize] = src << 1;
+                self.v[0xF] = src >> 7 & 1;
                 Next
             }
             Inst(9, x, y, 0) => {
@@ -210,40 +1290,57 @@ impl CPU {
             }
             Inst(0xB, n1, n2, n3) => {
                 let off = addr(n1, n2, n3);
-                let pos = self.v[0] as u16 + off;
-                debug!("JP V0, addr(pos: {}, off: {})", pos, off);
+                let base = if self.quirks.jump_vx { n1 } else { 0 };
+                let pos = self.v[base as usize] as u16 + off;
+                debug!("JP V{}, addr(pos: {}, off: {})", base, pos, off);
                 Jump(pos)
             }
             Inst(0xC, x, k1, k2) => {
-                let rnd: u8 = rand::random();
+                let rnd = self.rng.next_u8();
                 self.v[x as usize] = rnd & val(k1, k2);
                 Next
             }
+            Inst(0xD, x, y, 0) if display.hires() => {
+                debug!("DRW V{}, V{}, 0", x, y);
+                self.draw(ram, display, x, y, 16, 2)?;
+                Next
+            }
             Inst(0xD, x, y, n) => {
                 debug!("DRW V{}, V{}, nibble({})", x, y, n);
-                let (start, end) = (self.i as usize, (self.i + n as u16) as usize);
-                self.v[0xF] =
-                    if display.draw(self.v[x as usize], self.v[y as usize], &ram.buf[start..end]) {
-                        1
-                    } else {
-                        0
-                    };
+                self.draw(ram, display, x, y, n as usize, 1)?;
                 Next
             }
             Inst(0xE, x, 9, 0xE) => {
-                if keyboard.is_pressed(self.v[x as usize]) {
+                let key = self.v[x as usize];
+                let down = keyboard.is_pressed(key);
+                keyboard.consumed(1 << (key & 0xF));
+                keyboard.observed(KeyCheck::Pressed { key, down });
+                if down {
                     Skip
                 } else {
                     Next
                 }
             }
             Inst(0xE, x, 0xA, 1) => {
-                if !keyboard.is_pressed(self.v[x as usize]) {
+                let key = self.v[x as usize];
+                let down = keyboard.is_pressed(key);
+                keyboard.consumed(1 << (key & 0xF));
+                keyboard.observed(KeyCheck::Pressed { key, down });
+                if !down {
                     Skip
                 } else {
                     Next
                 }
             }
+            Inst(0xF, 0, 0, 0) => {
+                // XO-CHIP's `F000 NNNN`: the address is the word after the opcode.
+                let word = ram
+                    .read_range(self.pc as usize + 2, 2)
+                    .ok_or(Trap::PcOutOfRange { pc: self.pc })?;
+                self.i = u16::from_be_bytes([word[0], word[1]]);
+                debug!("LD I, {:04X}", self.i);
+                Jump(self.pc + 4)
+            }
             Inst(0xF, x, 0, 7) => {
                 debug!("LD V{}, DT", x);
                 self.v[x as usize] = self.dt.get();
@@ -251,8 +1348,33 @@ impl CPU {
             }
             Inst(0xF, x, 0, 0xA) => {
                 debug!("LD V{}, K", x);
-                self.v[x as usize] = keyboard.wait();
-                Next
+                let held = keyboard.state();
+                keyboard.consumed(held);
+                if !self.key_wait {
+                    self.key_wait = true;
+                    self.key_ignored = held;
+                    self.key_down = None;
+                    keyboard.waiting(true);
+                }
+                // a key held before FX0A started counts once it was released.
+                self.key_ignored &= held;
+                if self.key_down.is_none() {
+                    let fresh = held & !self.key_ignored;
+                    self.key_down = (0..0x10).find(|k| fresh & 1 << k != 0);
+                }
+                match self.key_down {
+                    // completes on the release, like the COSMAC VIP.
+                    Some(key) if held & 1 << key == 0 => {
+                        self.key_wait = false;
+                        self.key_down = None;
+                        keyboard.waiting(false);
+                        keyboard.observed(KeyCheck::Waited { key });
+                        self.v[x as usize] = key;
+                        Next
+                    }
+                    // asks again on the next cycle, the timers keep running meanwhile.
+                    _ => Jump(self.pc),
+                }
             }
             Inst(0xF, x, 1, 5) => {
                 debug!("LD DT, V{}", x);
@@ -266,66 +1388,234 @@ impl CPU {
             }
             Inst(0xF, x, 1, 0xE) => {
                 debug!("ADD I, V{}", x);
-                self.i += self.v[x as usize] as u16;
+                let i = self.i.wrapping_add(self.v[x as usize] as u16);
+                if self.quirks.index_overflow {
+                    self.v[0xF] = (i > 0xFFF || i < self.i) as u8;
+                }
+                self.i = i;
                 Next
             }
             Inst(0xF, x, 2, 9) => {
                 debug!("LD F, V{}", x);
-                self.i = HEAD_OF_SPRITE as u16 + (self.v[x as usize] * 5) as u16;
+                self.i = HEAD_OF_SPRITE as u16 + self.v[x as usize] as u16 * 5;
+                Next
+            }
+            Inst(0xF, x, 3, 0) => {
+                debug!("LD HF, V{}", x);
+                self.i = HEAD_OF_BIG_SPRITE as u16 + (self.v[x as usize] & 0xF) as u16 * 10;
+                Next
+            }
+            Inst(0xF, n, 0, 1) => {
+                debug!("PLANE {}", n);
+                self.planes = n & ALL_PLANES;
+                Next
+            }
+            Inst(0xF, x, 7, 5) => {
+                debug!("LD R, V{}", x);
+                let n = (x as usize).min(7);
+                self.rpl[..=n].copy_from_slice(&self.v[..=n]);
+                Next
+            }
+            Inst(0xF, x, 8, 5) => {
+                debug!("LD V{}, R", x);
+                let n = (x as usize).min(7);
+                self.v[..=n].copy_from_slice(&self.rpl[..=n]);
                 Next
             }
             Inst(0xF, x, 3, 3) => {
                 debug!("LD B, V{}", x);
-                let mut v = self.v[x as usize];
-                ram.buf[(self.i + 2) as usize] = v % 10;
-                v /= 10;
-                ram.buf[(self.i + 1) as usize] = v % 10;
-                v /= 10;
-                ram.buf[(self.i) as usize] = v % 10;
+                let v = self.v[x as usize];
+                self.store(ram, &[v / 100, v / 10 % 10, v % 10])?;
                 Next
             }
             Inst(0xF, x, 5, 5) => {
                 debug!("LD [I], V{}", x);
-                for i in 0..x + 1 {
-                    ram.buf[self.i as usize + i as usize] = self.v[i as usize];
+                self.store(ram, &self.v[..=x as usize])?;
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
                 }
                 Next
             }
             Inst(0xF, x, 6, 5) => {
                 debug!("LD V{}, [I]", x);
-                for i in 0..x + 1 {
-                    self.v[i as usize] = ram.buf[self.i as usize + i as usize];
+                let n = x as usize + 1;
+                let bytes = ram
+                    .read_range(self.i as usize, n)
+                    .ok_or(self.out_of_ram(n))?;
+                self.v[..n].copy_from_slice(bytes);
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
                 }
                 Next
             }
             _ => {
-                todo!("{:?}", op);
+                return Err(Trap::UnknownOpcode {
+                    pc: self.pc,
+                    opcode: op.into(),
+                });
             }
         };
-        match ctl {
-            Next => self.pc += 2,
-            Skip => self.pc += 4,
-            Jump(r) => self.pc = r,
-        }
+        Ok(ctl)
     }
-    fn run(
+    // draws the sprite at I on the selected planes and sets VF: 1 for a collision, or with the
+    // row-collisions quirk in the high-resolution mode the number of rows which collided or were
+    // clipped at the bottom. With both planes selected (XO-CHIP) the sprite of the second plane
+    // follows that of the first.
+    fn draw(
         &mut self,
-        hz: u32,
-        ram: &mut Ram,
-        display: &mut Box<dyn Display>,
-        keyboard: &Box<dyn Keyboard>,
-    ) {
-        let d = Duration::new(1, 0) / hz;
-        loop {
-            let now = Instant::now();
-            // inst's length is 2 bytes.
-            if usize::from(self.pc + 1) >= RAM_SIZE {
-                break;
+        ram: &Ram,
+        display: &mut FrameBuffer,
+        x: u8,
+        y: u8,
+        rows: usize,
+        row_bytes: usize,
+    ) -> Result<(), Trap> {
+        let size = rows * row_bytes;
+        let total = size * self.planes.count_ones() as usize;
+        ram.read_range(self.i as usize, total)
+            .ok_or(self.out_of_ram(total))?;
+        let (mut collided, mut clipped, mut offset) = (0, 0, 0);
+        for plane in [1, 2].into_iter().filter(|p| self.planes & p != 0) {
+            let (vx, vy, sprite) = self.sprite(ram, display, x, y, rows, row_bytes, offset)?;
+            collided = collided.max(display.draw_rows(vx, vy, &sprite, row_bytes, plane));
+            clipped = rows - sprite.len() / row_bytes;
+            offset += size;
+        }
+        self.v[0xF] = if self.quirks.row_collisions && display.hires() {
+            (collided + clipped) as u8
+        } else {
+            (collided > 0) as u8
+        };
+        self.vblank_wait = self.quirks.display_wait;
+        Ok(())
+    }
+    // writes `bytes` at I, or traps without writing anything when they do not fit in RAM.
+    fn store(&self, ram: &mut Ram, bytes: &[u8]) -> Result<(), Trap> {
+        if ram.write_range(self.i as usize, bytes) {
+            Ok(())
+        } else {
+            Err(self.out_of_ram(bytes.len()))
+        }
+    }
+    fn out_of_ram(&self, len: usize) -> Trap {
+        Trap::MemoryOutOfRange {
+            pc: self.pc,
+            i: self.i,
+            len,
+        }
+    }
+    // SUPER-CHIP 1.1 scrolls by high-resolution pixels, half as many low-resolution ones.
+    fn scroll_divisor(&self, screen: &FrameBuffer) -> usize {
+        if self.quirks.half_scroll && !screen.hires() {
+            2
+        } else {
+            1
+        }
+    }
+    // reads a sprite of `rows` rows of `row_bytes` bytes at I + `offset` for DRW VX, VY and
+    // returns it with its wrapped origin, clipped at the screen edges when the clipping quirk is
+    // set; traps when the sprite does not fit in RAM.
+    #[allow(clippy::too_many_arguments)]
+    fn sprite(
+        &self,
+        ram: &Ram,
+        screen: &FrameBuffer,
+        x: u8,
+        y: u8,
+        rows: usize,
+        row_bytes: usize,
+        offset: usize,
+    ) -> Result<(u8, u8, Sprite), Trap> {
+        let (w, h) = (screen.width(), screen.height());
+        let (vx, vy) = (
+            self.v[x as usize] as usize % w,
+            self.v[y as usize] as usize % h,
+        );
+        let mut sprite = Sprite {
+            bytes: [0; 32],
+            len: rows * row_bytes,
+        };
+        let bytes = ram
+            .read_range(self.i as usize + offset, sprite.len)
+            .ok_or(self.out_of_ram(offset + sprite.len))?;
+        sprite.bytes[..sprite.len].copy_from_slice(bytes);
+        if self.quirks.clipping {
+            sprite.len = sprite.len.min((h - vy).min(rows) * row_bytes);
+            if vx + row_bytes * 8 > w {
+                for row in sprite.bytes[..sprite.len].chunks_mut(row_bytes) {
+                    // keeps the leftmost `visible` pixels of the row.
+                    let mut visible = w - vx;
+                    for b in row.iter_mut() {
+                        if visible < 8 {
+                            *b &= !(0xFF >> visible);
+                        }
+                        visible -= visible.min(8);
+                    }
+                }
+            }
+        }
+        Ok((vx as u8, vy as u8, sprite))
+    }
+    fn watch_quirk(&mut self, op: &Inst, screen: &FrameBuffer) {
+        self.quirk_hit = None;
+        let q = match op {
+            Inst(8, _, _, 1..=3) => Quirk::VfReset,
+            Inst(8, _, _, 6) | Inst(8, _, _, 0xE) => Quirk::Shift,
+            Inst(0xF, _, 5 | 6, 5) => Quirk::LoadStore,
+            Inst(0xF, _, 1, 0xE) => Quirk::IndexOverflow,
+            Inst(0xB, ..) => Quirk::Jump,
+            Inst(0, 0, 0xC, _) | Inst(0, 0, 0xF, 0xB | 0xC) if !screen.hires() => Quirk::HalfScroll,
+            Inst(0xD, x, y, n) => {
+                let (w, h) = (screen.width(), screen.height());
+                let (x, y) = (
+                    self.v[*x as usize] as usize % w,
+                    self.v[*y as usize] as usize % h,
+                );
+                // DXY0 draws 16x16 sprites in high resolution
+                let (sw, sh) = match n {
+                    0 if screen.hires() => (16, 16),
+                    _ => (8, *n as usize),
+                };
+                if x + sw > w || y + sh > h {
+                    Quirk::Clipping
+                } else {
+                    return;
+                }
             }
-            self.cycle(ram, display, keyboard);
-            thread::sleep(d - (Instant::now() - now));
+            _ => return,
+        };
+        if self.quirks_seen & q as u8 != 0 {
+            return;
+        }
+        self.quirks_seen |= q as u8;
+        self.quirk_hit = Some(q);
+        warn!(
+            "pc:0x{:x} {:?} depends on a quirk: {}",
+            self.pc,
+            op,
+            q.description()
+        );
+    }
+    fn view(&self) -> CpuView {
+        CpuView {
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            dt: self.dt.get(),
+            st: self.st.get(),
         }
     }
+    fn apply(&mut self, view: CpuView) {
+        self.v = view.v;
+        self.i = view.i;
+        self.pc = view.pc;
+        self.sp = view.sp;
+        self.stack = view.stack;
+        self.dt.set(view.dt);
+        self.st.set(view.st);
+    }
     pub fn dump(&self) {
         debug!(
             "pc:0x{:x}({}), v:{:?}, sp:{}, stack:{:?}, i:0x{:x}, dt:{}",
@@ -340,30 +1630,808 @@ impl CPU {
     }
 }
 
-enum Control {
-    Next,
-    Skip,
-    Jump(u16),
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-fn addr(n1: u8, n2: u8, n3: u8) -> u16 {
-    ((n1 as u16) << 8) + ((n2 as u16) << 4) + n3 as u16
+// switches for opcode behavior which differs between interpreters.
+// The default is the behavior of this emulator so far (roughly SUPER-CHIP).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY1/8XY2/8XY3 reset VF to 0 (COSMAC VIP).
+    pub vf_reset: bool,
+    // FX55/FX65 leave I at I + X + 1 (COSMAC VIP).
+    pub load_store_increments_i: bool,
+    // 8XY6/8XYE shift VY into VX instead of shifting VX in place (COSMAC VIP).
+    pub shift_vy: bool,
+    // BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0 (SUPER-CHIP).
+    pub jump_vx: bool,
+    // sprites are clipped at the screen edges instead of wrapping around.
+    pub clipping: bool,
+    // DXYN waits for the next 60Hz frame (COSMAC VIP vblank).
+    pub display_wait: bool,
+    // 00CN/00FB/00FC scroll by half as many pixels in the low-resolution mode (SUPER-CHIP 1.1).
+    pub half_scroll: bool,
+    // FX1E sets VF to 1 when I + VX leaves the 12-bit address space, 0 otherwise (Amiga).
+    pub index_overflow: bool,
+    // DXYN in the high-resolution mode sets VF to the number of sprite rows which collided or
+    // were clipped at the bottom edge (SUPER-CHIP 1.1).
+    pub row_collisions: bool,
 }
 
-fn val(k1: u8, k2: u8) -> u8 {
-    (k1 << 4) + k2
+impl Quirks {
+    pub const NAMES: [&'static str; 9] = [
+        "vf-reset",
+        "load-store",
+        "shift",
+        "jump",
+        "clipping",
+        "display-wait",
+        "half-scroll",
+        "index-overflow",
+        "row-collisions",
+    ];
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        Some(match name {
+            "vf-reset" => &mut self.vf_reset,
+            "load-store" => &mut self.load_store_increments_i,
+            "shift" => &mut self.shift_vy,
+            "jump" => &mut self.jump_vx,
+            "clipping" => &mut self.clipping,
+            "display-wait" => &mut self.display_wait,
+            "half-scroll" => &mut self.half_scroll,
+            "index-overflow" => &mut self.index_overflow,
+            "row-collisions" => &mut self.row_collisions,
+            _ => return None,
+        })
+    }
 }
 
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for Quirks {
+    type Err = String;
+    // parses comma separated quirk names (Quirks::NAMES) to enable.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut q = Quirks::default();
+        for name in s.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            match q.flag(name) {
+                Some(f) => *f = true,
+                None => {
+                    return Err(format!(
+                        "unknown quirk `{}` (one of {})",
+                        name,
+                        Quirks::NAMES.join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(q)
+    }
+}
+
+// formats the enabled quirks as parsed by `from_str`.
+impl core::fmt::Display for Quirks {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut q = *self;
+        let mut sep = "";
+        for name in Quirks::NAMES {
+            if q.flag(name).is_some_and(|on| *on) {
+                write!(f, "{}{}", sep, name)?;
+                sep = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+// the rows of a sprite read for DXYN, at most 16 rows of 2 bytes.
+#[derive(Debug, PartialEq, Eq)]
+struct Sprite {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl core::ops::Deref for Sprite {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+// opcode behavior which differs between interpreters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    VfReset = 1,
+    Shift = 1 << 1,
+    LoadStore = 1 << 2,
+    IndexOverflow = 1 << 3,
+    Jump = 1 << 4,
+    Clipping = 1 << 5,
+    HalfScroll = 1 << 6,
+}
+
+impl Quirk {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Quirk::VfReset => "8XY1/8XY2/8XY3 may reset VF",
+            Quirk::Shift => "8XY6/8XYE may shift VY instead of VX",
+            Quirk::LoadStore => "FX55/FX65 may increment I",
+            Quirk::IndexOverflow => "FX1E may set VF when I overflows",
+            Quirk::Jump => "BNNN may jump to XNN + VX",
+            Quirk::Clipping => "DXYN may clip sprites at the screen edge instead of wrapping",
+            Quirk::HalfScroll => "00CN/00FB/00FC may scroll half as far in low resolution",
+        }
+    }
+}
+
+// pauses `run` at frame boundaries and lets it advance one frame at a time, shared with frontends.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct FrameStep(Arc<(Mutex<StepState>, Condvar)>);
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct StepState {
+    paused: bool,
+    pending: u32,
+}
+
+#[cfg(feature = "std")]
+impl FrameStep {
+    pub fn new() -> Self {
+        FrameStep(Arc::new((Mutex::new(StepState::default()), Condvar::new())))
+    }
+    pub fn is_paused(&self) -> bool {
+        self.0 .0.lock().unwrap().paused
+    }
+    pub fn pause(&self) {
+        self.0 .0.lock().unwrap().paused = true;
+    }
+    pub fn resume(&self) {
+        let mut st = self.0 .0.lock().unwrap();
+        st.paused = false;
+        st.pending = 0;
+        self.0 .1.notify_all();
+    }
+    // pauses a running machine, or lets a paused one run one more frame.
+    pub fn step(&self) {
+        let mut st = self.0 .0.lock().unwrap();
+        if st.paused {
+            st.pending += 1;
+            self.0 .1.notify_all();
+        } else {
+            st.paused = true;
+        }
+    }
+    // blocks while paused until a frame is granted; returns true for a stepped frame.
+    fn wait_frame(&self) -> bool {
+        let mut st = self.0 .0.lock().unwrap();
+        while st.paused && st.pending == 0 {
+            st = self.0 .1.wait(st).unwrap();
+        }
+        if st.paused {
+            st.pending -= 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for FrameStep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// asks `run` to return `Stopped::Requested` before the next instruction, or to `Chip::reset`
+// the machine there, shared with frontends. A paused `FrameStep` must be resumed (or stepped)
+// for a request to be noticed.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct Stopper {
+    stopped: Arc<AtomicBool>,
+    // counts the requested resets, so that every machine sharing the Stopper sees them
+    resets: Arc<AtomicU32>,
+}
+
+#[cfg(feature = "std")]
+impl Stopper {
+    pub fn new() -> Self {
+        Stopper::default()
+    }
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+    pub fn reset(&self) {
+        self.resets.fetch_add(1, Ordering::Relaxed);
+    }
+    fn resets(&self) -> u32 {
+        self.resets.load(Ordering::Relaxed)
+    }
+}
+
+// emulation speed of `run`, shared with frontends: a percentage of the clock and the clock
+// itself, which both change the instructions per frame only (DT, ST and the display stay at
+// 60 Hz); and turbo, which runs as fast as the host can.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Speed {
+    percent: Arc<AtomicU32>,
+    // 0: the `hz` given to `run`
+    cpu_hz: Arc<AtomicU32>,
+    turbo: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "std")]
+impl Speed {
+    pub const MIN: u32 = 25;
+    pub const MAX: u32 = 400;
+    // the clock is at least one instruction per frame
+    pub const MIN_HZ: u32 = 60;
+    pub const MAX_HZ: u32 = 1_000_000;
+    pub fn new() -> Self {
+        Speed {
+            percent: Arc::new(AtomicU32::new(100)),
+            cpu_hz: Arc::new(AtomicU32::new(0)),
+            turbo: Arc::new(AtomicBool::new(false)),
+        }
+    }
+    pub fn percent(&self) -> u32 {
+        self.percent.load(Ordering::Relaxed)
+    }
+    // sets the speed clamped to MIN..=MAX and returns the applied value.
+    pub fn set_percent(&self, percent: u32) -> u32 {
+        let p = percent.clamp(Self::MIN, Self::MAX);
+        self.percent.store(p, Ordering::Relaxed);
+        p
+    }
+    // the clock set by `set_cpu_hz`; None runs at the `hz` given to `run`.
+    pub fn cpu_hz(&self) -> Option<u32> {
+        Some(self.cpu_hz.load(Ordering::Relaxed)).filter(|hz| *hz > 0)
+    }
+    // sets the clock clamped to MIN_HZ..=MAX_HZ (None for the `hz` given to `run`) and returns
+    // the applied value.
+    pub fn set_cpu_hz(&self, hz: Option<u32>) -> Option<u32> {
+        let hz = hz.map(|hz| hz.clamp(Self::MIN_HZ, Self::MAX_HZ));
+        self.cpu_hz.store(hz.unwrap_or(0), Ordering::Relaxed);
+        hz
+    }
+    pub fn turbo(&self) -> bool {
+        self.turbo.load(Ordering::Relaxed)
+    }
+    pub fn set_turbo(&self, turbo: bool) {
+        self.turbo.store(turbo, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a handle controlling `Chip::run` from other threads (see `Chip::controller`): pausing,
+// resuming, resetting and stopping it, pressing keys and taking snapshots. Clones share the machine.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct ChipController {
+    frame_step: FrameStep,
+    stopper: Stopper,
+    requests: mpsc::Sender<Request>,
+    keys: Arc<RemoteKeys>,
+}
+
+#[cfg(feature = "std")]
+impl ChipController {
+    pub fn pause(&self) {
+        self.frame_step.pause();
+    }
+    pub fn resume(&self) {
+        self.frame_step.resume();
+    }
+    pub fn is_paused(&self) -> bool {
+        self.frame_step.is_paused()
+    }
+    // advances a paused machine by one frame.
+    pub fn step_frame(&self) {
+        self.frame_step.step();
+    }
+    pub fn reset(&self) {
+        self.stopper.reset();
+    }
+    pub fn stop(&self) {
+        self.stopper.stop();
+    }
+    // holds the key down alongside the machine's keyboard; a key pressed and released before the
+    // machine looked is reported pressed once, like `Keypad`.
+    pub fn press(&self, key: u8) {
+        let bit = 1 << (key & 0xF);
+        self.keys.held.fetch_or(bit, Ordering::Relaxed);
+        self.keys.tapped.fetch_or(bit, Ordering::Relaxed);
+    }
+    pub fn release(&self, key: u8) {
+        (self.keys.held).fetch_and(!(1 << (key & 0xF)), Ordering::Relaxed);
+    }
+    // asks for the machine's state, sent at the next frame of `run` (a paused machine answers
+    // once it is resumed or stepped); the receiver fails when the machine is gone.
+    pub fn request_snapshot(&self) -> mpsc::Receiver<ChipState> {
+        let (reply, snapshot) = mpsc::channel();
+        let _ = self.requests.send(Request::Snapshot(reply));
+        snapshot
+    }
+    // asks for `Chip::reload_slice` at the next frame; the receiver gets its result.
+    pub fn reload(&self, rom: Vec<u8>) -> mpsc::Receiver<Result<usize, LoadError>> {
+        let (reply, result) = mpsc::channel();
+        let _ = self.requests.send(Request::Reload(rom, reply));
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+enum Request {
+    Snapshot(mpsc::Sender<ChipState>),
+    Reload(Vec<u8>, mpsc::Sender<Result<usize, LoadError>>),
+}
+
+// the machine's end of its controllers.
+#[cfg(feature = "std")]
+struct Remote {
+    sender: mpsc::Sender<Request>,
+    requests: mpsc::Receiver<Request>,
+    keys: Arc<RemoteKeys>,
+}
+
+// the keys of the controllers, as in `KeypadState`.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct RemoteKeys {
+    held: AtomicU16,
+    tapped: AtomicU16,
+}
+
+// the machine's keyboard with the keys of its controllers.
+#[cfg(feature = "std")]
+struct RemoteKeyboard<'a>(&'a dyn Keyboard, &'a RemoteKeys);
+
+#[cfg(feature = "std")]
+impl Keyboard for RemoteKeyboard<'_> {
+    fn is_pressed(&self, key: u8) -> bool {
+        let keys = self.1.held.load(Ordering::Relaxed) | self.1.tapped.load(Ordering::Relaxed);
+        keys & 1 << (key & 0xF) != 0 || self.0.is_pressed(key)
+    }
+    fn consumed(&self, keys: u16) {
+        self.1.tapped.fetch_and(!keys, Ordering::Relaxed);
+        self.0.consumed(keys);
+    }
+    fn waiting(&self, waiting: bool) {
+        self.0.waiting(waiting);
+    }
+    fn observed(&self, check: KeyCheck) {
+        self.0.observed(check);
+    }
+}
+
+// why `Chip::run` returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    // the program counter left RAM
+    LeftRam,
+    // EXIT (00FD) was executed
+    Halted,
+    // the next instruction, at the address, has a breakpoint
+    Breakpoint(u16),
+    // the instruction at `pc` hit the watchpoint
+    Watchpoint { watch: Watch, pc: u16 },
+    // a hook returned `HookAction::Pause`; the address of the next instruction
+    Paused(u16),
+    // `Stopper::stop` was called
+    Requested,
+    // `run_headless` executed its maximum number of cycles (`StopCondition::Cycles`)
+    CycleLimit,
+    // the next instruction is at the address of `StopCondition::Pc`
+    Reached(u16),
+    // the next instruction, at the address, jumps to itself (see `Chip::set_stop_on_self_jump`)
+    SelfJump(u16),
+    // nothing was drawn for the cycles of `StopCondition::DisplaySettled`
+    Settled,
+    // the instant of `StopCondition::Deadline` passed
+    Deadline,
+}
+
+// what ends `Chip::run_until`, besides the reasons `Chip::run` stops for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    // after this many cycles
+    Cycles(u64),
+    // once the wall clock passes the instant, checked at every frame
+    #[cfg(feature = "std")]
+    Deadline(Instant),
+    // only at a breakpoint (or for the other reasons)
+    Breakpoint,
+    // before executing the instruction at the address; like a breakpoint, not before the
+    // first instruction
+    Pc(u16),
+    // after this many cycles without drawing, clearing or scrolling the screen
+    DisplaySettled(u64),
+}
+
+// the registers as seen by a hook or `Chip::registers`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuView {
+    pub v: [u8; 0x10],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl CpuView {
+    pub fn get(&self, r: Register) -> u16 {
+        match r {
+            Register::V(x) => self.v[x as usize & 0xF].into(),
+            Register::I => self.i,
+            Register::Pc => self.pc,
+            Register::Sp => self.sp.into(),
+            Register::Dt => self.dt.into(),
+            Register::St => self.st.into(),
+        }
+    }
+    // sets a register, truncating the value to its width; SP stays within the stack.
+    pub fn set(&mut self, r: Register, value: u16) {
+        match r {
+            Register::V(x) => self.v[x as usize & 0xF] = value as u8,
+            Register::I => self.i = value,
+            Register::Pc => self.pc = value,
+            Register::Sp => self.sp = value.min(self.stack.len() as u16) as u8,
+            Register::Dt => self.dt = value as u8,
+            Register::St => self.st = value as u8,
+        }
+    }
+}
+
+// a register of `CpuView`, named `v0`-`vf`, `i`, `pc`, `sp`, `dt` or `st`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+impl Register {
+    // V0-VF, I, PC, SP, DT and ST in this order (also the GDB register numbers of `dbg`).
+    pub const ALL: [Register; 21] = [
+        Register::V(0),
+        Register::V(1),
+        Register::V(2),
+        Register::V(3),
+        Register::V(4),
+        Register::V(5),
+        Register::V(6),
+        Register::V(7),
+        Register::V(8),
+        Register::V(9),
+        Register::V(0xA),
+        Register::V(0xB),
+        Register::V(0xC),
+        Register::V(0xD),
+        Register::V(0xE),
+        Register::V(0xF),
+        Register::I,
+        Register::Pc,
+        Register::Sp,
+        Register::Dt,
+        Register::St,
+    ];
+}
+
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for Register {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let r = match lower.as_str() {
+            "i" => Some(Register::I),
+            "pc" => Some(Register::Pc),
+            "sp" => Some(Register::Sp),
+            "dt" => Some(Register::Dt),
+            "st" => Some(Register::St),
+            v if v.len() == 2 && v.starts_with('v') => {
+                u8::from_str_radix(&v[1..], 16).ok().map(Register::V)
+            }
+            _ => None,
+        };
+        r.ok_or_else(|| format!("unknown register `{}` (v0-vf, i, pc, sp, dt or st)", s))
+    }
+}
+
+impl core::fmt::Display for Register {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Sp => write!(f, "SP"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+        }
+    }
+}
+
+// what the machine does after a hook returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    Continue,
+    Pause,
+    // sets the registers (e.g. a cheat) and continues; before an instruction, the one at the
+    // new `pc` is executed
+    Replace(CpuView),
+}
+
+// what a watchpoint looks at (`Chip::add_watchpoint`). RAM watchpoints fire on every write to
+// `start..end`, register watchpoints when an instruction changes the value (the timer counting
+// down does not count).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Ram { start: usize, end: usize },
+    V(u8),
+    I,
+    Dt,
+}
+
+// parses `v3`, `i`, `dt`, or a hex RAM address `300` or range `300..310` (end excluded).
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for Watch {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let hex = |h: &str| usize::from_str_radix(h.trim_start_matches("0x"), 16).ok();
+        let w = match lower.as_str() {
+            "i" => Some(Watch::I),
+            "dt" => Some(Watch::Dt),
+            v if v.len() == 2 && v.starts_with('v') => {
+                u8::from_str_radix(&v[1..], 16).ok().map(Watch::V)
+            }
+            range => match range.split_once("..") {
+                Some((start, end)) => hex(start)
+                    .zip(hex(end))
+                    .filter(|(start, end)| start < end)
+                    .map(|(start, end)| Watch::Ram { start, end }),
+                None => hex(range).map(|start| Watch::Ram {
+                    start,
+                    end: start + 1,
+                }),
+            },
+        };
+        w.ok_or_else(|| {
+            format!(
+                "invalid watchpoint `{}` (v0-vf, i, dt, ADDR or ADDR..END)",
+                s
+            )
+        })
+    }
+}
+
+impl core::fmt::Display for Watch {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Watch::Ram { start, end } if *end == start + 1 => write!(f, "{:03x}", start),
+            Watch::Ram { start, end } => write!(f, "{:03x}..{:03x}", start, end),
+            Watch::V(x) => write!(f, "V{:X}", x),
+            Watch::I => write!(f, "I"),
+            Watch::Dt => write!(f, "DT"),
+        }
+    }
+}
+
+// where the program counter went after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    // to the following instruction
+    Next,
+    // over the following instruction (a skip taken)
+    Skip,
+    // to the address (jumps, calls, returns; EXIT stays in place)
+    Jump(u16),
+    // nowhere: nothing was executed while waiting for the next 60Hz frame (display-wait quirk)
+    Wait,
+}
+
+// what one instruction did, returned by `Chip::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub addr: u16,
+    // None for words which are not instructions but still execute (e.g. `0000`)
+    pub opcode: Option<opcode::Opcode>,
+    pub control: Control,
+    // bit n is set when VN changed
+    pub changed_v: u16,
+    pub changed_i: bool,
+    // the instruction drew, cleared or scrolled the screen or switched its resolution
+    pub display_changed: bool,
+}
+
+impl Step {
+    // nothing executed at `addr`: the machine waits for the next frame or is halted.
+    fn wait(addr: u16) -> Step {
+        Step {
+            addr,
+            opcode: None,
+            control: Control::Wait,
+            changed_v: 0,
+            changed_i: false,
+            display_changed: false,
+        }
+    }
+}
+
+// a snapshot of the machine taken by `Chip::save_state`; the configuration (quirks, speed,
+// peripherals) is not part of it.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChipState {
+    pub v: [u8; 0x10],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u8,
+    pub stack: [u16; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub hires: bool,
+    pub halted: bool,
+    pub rpl: [u8; 8],
+    // the XO-CHIP planes selected by FN01
+    pub planes: u8,
+    // an instruction waits for the next frame (display-wait quirk)
+    pub vblank_wait: bool,
+    // FX0A waits for a key: the keys held when it started (bit n for key n, ignored until
+    // released) and the key pressed since, which completes it once released
+    pub key_wait: bool,
+    pub key_ignored: u16,
+    pub key_down: Option<u8>,
+    pub ram: Vec<u8>,
+    // the pixels of `Chip::framebuffer` (`FrameBuffer::pixels`); empty to keep the screen
+    pub framebuffer: Vec<u8>,
+}
+
+// snapshots of the last frames for `Chip::rewind`, newest last.
+#[cfg(feature = "alloc")]
+struct Rewind {
+    depth: usize,
+    interval: u32,
+    // frames since the newest snapshot
+    since: u32,
+    snapshots: VecDeque<ChipState>,
+}
+
+#[cfg(feature = "alloc")]
+impl Rewind {
+    // counts a frame and returns whether it is time for a snapshot.
+    fn due(&mut self) -> bool {
+        self.since += 1;
+        self.snapshots.is_empty() || self.since >= self.interval
+    }
+    fn push(&mut self, state: ChipState) {
+        if self.snapshots.len() == self.depth {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(state);
+        self.since = 0;
+    }
+}
+
+// an error stopping the execution of a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trap {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    // a CALL with all 16 stack entries in use
+    StackOverflow { pc: u16 },
+    // a RET with an empty stack
+    StackUnderflow { pc: u16 },
+    // the PC left the program area: below 0x200 or past the end of RAM
+    PcOutOfRange { pc: u16 },
+    // FX33, FX55, FX65 or DXYN accessed `len` bytes at I past the end of RAM
+    MemoryOutOfRange { pc: u16, i: u16, len: usize },
+}
+
+impl core::fmt::Display for Trap {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Trap::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", opcode, pc)
+            }
+            Trap::StackOverflow { pc } => write!(f, "stack overflow at {:03X}", pc),
+            Trap::StackUnderflow { pc } => write!(f, "return with an empty stack at {:03X}", pc),
+            Trap::PcOutOfRange { pc } => write!(f, "program counter out of range: {:03X}", pc),
+            Trap::MemoryOutOfRange { pc, i, len } => {
+                write!(f, "{} bytes at I={:03X} exceed RAM at {:03X}", len, i, pc)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Trap {}
+
+// why a ROM was not loaded.
 #[derive(Debug)]
+pub enum LoadError {
+    // the ROM does not fit between the program start and the end of RAM
+    TooLarge {
+        size: usize,
+        max: usize,
+    },
+    Empty,
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl core::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            LoadError::TooLarge { size, max } => {
+                write!(f, "the ROM has {} bytes, at most {} fit in RAM", size, max)
+            }
+            LoadError::Empty => write!(f, "the ROM is empty"),
+            #[cfg(feature = "std")]
+            LoadError::Io(e) => write!(f, "cannot read the ROM: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+fn addr(n1: u8, n2: u8, n3: u8) -> u16 {
+    ((n1 as u16) << 8) + ((n2 as u16) << 4) + n3 as u16
+}
+
+fn val(k1: u8, k2: u8) -> u8 {
+    (k1 << 4) + k2
+}
+
+#[derive(Debug, Clone, Copy)]
 struct Inst(u8, u8, u8, u8);
+impl From<Inst> for u16 {
+    fn from(op: Inst) -> Self {
+        (op.0 as u16) << 12 | (op.1 as u16) << 8 | (op.2 as u16) << 4 | op.3 as u16
+    }
+}
 impl From<&[u8; 2]> for Inst {
     fn from(bytes: &[u8; 2]) -> Self {
-        return Inst(
+        Inst(
             bytes[0] >> 4,
             bytes[0] & 0x0f,
             bytes[1] >> 4,
             bytes[1] & 0x0f,
-        );
+        )
     }
 }
 
@@ -371,52 +2439,172 @@ const RAM_SIZE: usize = 0x1000;
 
 pub struct Ram {
     pub buf: [u8; RAM_SIZE],
+    // `Access` bits per byte; the upper nibble holds the accesses since `clear_recent`.
+    access: [Cell<u8>; RAM_SIZE],
+    // addresses written by the current instruction, while RAM watchpoints are set
+    #[cfg(feature = "alloc")]
+    writes: Option<Vec<usize>>,
+}
+
+// kinds of accesses recorded for each byte of RAM, e.g. for a memory map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Executed = 1,
+    Read = 2,
+    Written = 4,
 }
 
 impl Ram {
     pub fn new() -> Self {
-        Ram { buf: [0; RAM_SIZE] }
+        Ram {
+            buf: [0; RAM_SIZE],
+            access: [const { Cell::new(0) }; RAM_SIZE],
+            #[cfg(feature = "alloc")]
+            writes: None,
+        }
     }
     fn fetch(&self, pc: u16) -> &[u8; 2] {
-        return self.buf[(pc as usize)..(pc as usize) + 2]
+        self.mark(pc as usize, Access::Executed);
+        self.mark(pc as usize + 1, Access::Executed);
+        self.buf[(pc as usize)..(pc as usize) + 2]
             .try_into()
-            .expect("fail to fetch");
+            .expect("fail to fetch")
+    }
+    fn mark(&self, addr: usize, a: Access) {
+        if let Some(c) = self.access.get(addr) {
+            c.set(c.get() | (a as u8) | (a as u8) << 4);
+        }
+    }
+    // whether the program has ever accessed `addr` that way.
+    pub fn accessed(&self, addr: usize, a: Access) -> bool {
+        self.access
+            .get(addr)
+            .is_some_and(|c| c.get() & a as u8 != 0)
+    }
+    // whether the program has accessed `addr` that way since the last `clear_recent`.
+    pub fn recently(&self, addr: usize, a: Access) -> bool {
+        self.access
+            .get(addr)
+            .is_some_and(|c| c.get() & (a as u8) << 4 != 0)
+    }
+    pub fn clear_recent(&self) {
+        self.access.iter().for_each(|c| c.set(c.get() & 0x0f));
+    }
+    // returns the length in bytes of the instruction at `pc`;
+    // XO-CHIP's `F000 NNNN` is the only 4-byte instruction.
+    fn inst_len(&self, pc: u16) -> u16 {
+        let pc = pc as usize;
+        match (self.buf.get(pc).copied(), self.buf.get(pc + 1).copied()) {
+            (Some(0xF0), Some(0x00)) => 4,
+            _ => 2,
+        }
+    }
+    // returns the byte at `addr`, or None beyond the end of RAM.
+    pub fn read(&self, addr: usize) -> Option<u8> {
+        self.mark(addr, Access::Read);
+        self.buf.get(addr).copied()
+    }
+    // returns the `len` bytes at `addr`, or None when they do not all fit in RAM.
+    pub fn read_range(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        let bytes = self.buf.get(addr..addr.checked_add(len)?)?;
+        (addr..addr + len).for_each(|a| self.mark(a, Access::Read));
+        Some(bytes)
+    }
+    // stores `bytes` at `addr`; returns false (and writes nothing) when they do not all fit.
+    pub fn write_range(&mut self, addr: usize, bytes: &[u8]) -> bool {
+        if addr
+            .checked_add(bytes.len())
+            .is_none_or(|end| end > RAM_SIZE)
+        {
+            return false;
+        }
+        for (n, b) in bytes.iter().enumerate() {
+            self.write(addr + n, *b);
+        }
+        true
+    }
+    // stores `v` at `addr`; returns false (and writes nothing) beyond the end of RAM.
+    pub fn write(&mut self, addr: usize, v: u8) -> bool {
+        self.mark(addr, Access::Written);
+        #[cfg(feature = "alloc")]
+        if let Some(w) = self.writes.as_mut() {
+            w.push(addr);
+        }
+        match self.buf.get_mut(addr) {
+            Some(b) => {
+                *b = v;
+                true
+            }
+            None => false,
+        }
     }
     pub fn load_slice(&mut self, start: u16, r: &[u8]) {
         for (i, b) in r.iter().enumerate() {
             self.buf[start as usize + i] = *b
         }
     }
-    pub fn load(&mut self, start: u16, r: &mut dyn Read) -> Result<usize, std::io::Error> {
-        r.read(&mut self.buf[(start as usize)..])
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-struct Timer {
-    val: Arc<Mutex<u8>>,
+// source of the CXNN random numbers: an entropy-seeded SmallRng (or any RngCore) with the
+// `rand` feature, otherwise a xorshift generator. Seeding always selects the xorshift generator,
+// whose sequence is the same on every platform and version.
+enum Random {
+    Xorshift(u32),
+    #[cfg(feature = "rand")]
+    Rng(Box<dyn RngCore>),
+}
+
+impl Random {
+    #[cfg(feature = "rand")]
+    fn new() -> Self {
+        Random::Rng(Box::new(SmallRng::from_entropy()))
+    }
+    #[cfg(not(feature = "rand"))]
+    fn new() -> Self {
+        Random::seeded(0)
+    }
+    fn seeded(seed: u64) -> Self {
+        // xorshift must not start from 0.
+        match (seed ^ seed >> 32) as u32 {
+            0 => Random::Xorshift(0x2545_f491),
+            s => Random::Xorshift(s),
+        }
+    }
+    fn next_u8(&mut self) -> u8 {
+        match self {
+            Random::Xorshift(x) => {
+                *x ^= *x << 13;
+                *x ^= *x >> 17;
+                *x ^= *x << 5;
+                (*x >> 24) as u8
+            }
+            #[cfg(feature = "rand")]
+            Random::Rng(rng) => rng.gen(),
+        }
+    }
 }
 
+// a 60Hz countdown (DT/ST), decremented by `Chip::tick_60hz`.
+struct Timer(u8);
+
 impl Timer {
     fn new() -> Self {
-        let v = Timer {
-            val: Arc::new(Mutex::new(0)),
-        };
-        let dul: Duration = Duration::from_nanos(Duration::new(1, 0).as_nanos() as u64 / 60);
-        let val = v.val.clone();
-        thread::spawn(move || loop {
-            let n = Instant::now();
-            if *val.lock().unwrap() > 0 {
-                *val.lock().unwrap() -= 1;
-            }
-            thread::sleep(dul - (Instant::now() - n));
-        });
-        v
+        Timer(0)
     }
     fn get(&self) -> u8 {
-        *self.val.lock().unwrap()
+        self.0
     }
     fn set(&mut self, v: u8) {
-        *self.val.lock().unwrap() = v;
+        self.0 = v;
+    }
+    fn tick(&mut self) {
+        self.0 = self.0.saturating_sub(1);
     }
 }
 
@@ -439,23 +2627,2059 @@ const SPRITES: [[u8; 5]; 0x10] = [
     [0b11110000, 0b10000000, 0b11110000, 0b10000000, 0b10000000],
 ];
 
+// the 3x5 digits of the ETI-660 monitor, and of CHIPOS on the DREAM 6800.
+const ETI660_SPRITES: [[u8; 5]; 0x10] = [
+    [0b11100000, 0b10100000, 0b10100000, 0b10100000, 0b11100000],
+    [0b00100000, 0b00100000, 0b00100000, 0b00100000, 0b00100000],
+    [0b11100000, 0b00100000, 0b11100000, 0b10000000, 0b11100000],
+    [0b11100000, 0b00100000, 0b11100000, 0b00100000, 0b11100000],
+    [0b10100000, 0b10100000, 0b11100000, 0b00100000, 0b00100000],
+    [0b11100000, 0b10000000, 0b11100000, 0b00100000, 0b11100000],
+    [0b11100000, 0b10000000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b00100000, 0b00100000, 0b00100000, 0b00100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b00100000, 0b11100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b10100000, 0b10100000],
+    [0b10000000, 0b10000000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b10000000, 0b10000000, 0b10000000, 0b11100000],
+    [0b00100000, 0b00100000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b10000000, 0b11100000, 0b10000000, 0b11100000],
+    [0b11100000, 0b10000000, 0b11000000, 0b10000000, 0b10000000],
+];
+
+const DREAM6800_SPRITES: [[u8; 5]; 0x10] = [
+    [0b11100000, 0b10100000, 0b10100000, 0b10100000, 0b11100000],
+    [0b01000000, 0b01000000, 0b01000000, 0b01000000, 0b01000000],
+    [0b11100000, 0b00100000, 0b11100000, 0b10000000, 0b11100000],
+    [0b11100000, 0b00100000, 0b11100000, 0b00100000, 0b11100000],
+    [0b10000000, 0b10100000, 0b10100000, 0b11100000, 0b00100000],
+    [0b11100000, 0b10000000, 0b11100000, 0b00100000, 0b11100000],
+    [0b11100000, 0b10000000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b00100000, 0b00100000, 0b00100000, 0b00100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b10100000, 0b11100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b00100000, 0b11100000],
+    [0b11100000, 0b10100000, 0b11100000, 0b10100000, 0b10100000],
+    [0b11000000, 0b10100000, 0b11100000, 0b10100000, 0b11000000],
+    [0b11100000, 0b10000000, 0b10000000, 0b10000000, 0b11100000],
+    [0b11000000, 0b10100000, 0b10100000, 0b10100000, 0b11000000],
+    [0b11100000, 0b10000000, 0b11100000, 0b10000000, 0b11100000],
+    [0b11100000, 0b10000000, 0b11000000, 0b10000000, 0b10000000],
+];
+
+// big hexadecimal digits, 8x10 pixels each: 0-9 of SUPER-CHIP and A-F as drawn by XO-CHIP.
+const BIG_SPRITES: [[u8; 10]; 0x10] = [
+    [0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C],
+    [0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C],
+    [0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF],
+    [0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C],
+    [0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06],
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C],
+    [0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C],
+    [0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60],
+    [0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C],
+    [0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C],
+    [0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3],
+    [0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC],
+    [0x3C, 0x7E, 0xE7, 0xC0, 0xC0, 0xC0, 0xC0, 0xE7, 0x7E, 0x3C],
+    [0xFC, 0xFE, 0xC7, 0xC3, 0xC3, 0xC3, 0xC3, 0xC7, 0xFE, 0xFC],
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0],
+];
+
 pub const HEIGHT: usize = 32;
 pub const WIDTH: usize = 64;
+// SUPER-CHIP high resolution mode
+pub const HIRES_HEIGHT: usize = 64;
+pub const HIRES_WIDTH: usize = 128;
 
-pub trait Display {
-    fn clear(&mut self);
-    fn draw(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool;
+// a CHIP-8 interpreter to emulate beyond the quirks: where programs are loaded, the
+// low-resolution screen size, the hex digit font and a typical speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    pub name: &'static str,
+    pub start: u16,
+    pub width: usize,
+    pub height: usize,
+    pub font: &'static [[u8; 5]; 0x10],
+    pub cpu_hz: u32,
+    pub quirks: Quirks,
 }
-pub trait Keyboard {
-    fn is_pressed(&self, key: u8) -> bool;
-    fn wait(&self) -> u8;
+
+// the COSMAC VIP behavior shared by the early interpreters.
+const VIP_QUIRKS: Quirks = Quirks {
+    vf_reset: true,
+    load_store_increments_i: true,
+    shift_vy: true,
+    jump_vx: false,
+    clipping: true,
+    display_wait: true,
+    half_scroll: false,
+    index_overflow: false,
+    row_collisions: false,
+};
+
+impl Platform {
+    // this emulator's default (roughly SUPER-CHIP).
+    pub const CHIP8: Platform = Platform {
+        name: "chip8",
+        start: HEAD_OF_PROGRAM,
+        width: WIDTH,
+        height: HEIGHT,
+        font: &SPRITES,
+        cpu_hz: 600,
+        quirks: Quirks {
+            vf_reset: false,
+            load_store_increments_i: false,
+            shift_vy: false,
+            jump_vx: false,
+            clipping: false,
+            display_wait: false,
+            half_scroll: false,
+            index_overflow: false,
+            row_collisions: false,
+        },
+    };
+    // ETI-660 (1981): programs start at 0x600 and the screen has 48 rows.
+    pub const ETI660: Platform = Platform {
+        name: "eti-660",
+        start: 0x600,
+        width: WIDTH,
+        height: 48,
+        font: &ETI660_SPRITES,
+        cpu_hz: 500,
+        quirks: VIP_QUIRKS,
+    };
+    // DREAM 6800 running CHIPOS: a slower 6800 interpreter with the VIP memory layout.
+    pub const DREAM6800: Platform = Platform {
+        name: "dream-6800",
+        start: HEAD_OF_PROGRAM,
+        width: WIDTH,
+        height: HEIGHT,
+        font: &DREAM6800_SPRITES,
+        cpu_hz: 300,
+        quirks: VIP_QUIRKS,
+    };
+    // the original COSMAC VIP interpreter (1977).
+    pub const VIP: Platform = Platform {
+        name: "vip",
+        start: HEAD_OF_PROGRAM,
+        width: WIDTH,
+        height: HEIGHT,
+        font: &SPRITES,
+        cpu_hz: 500,
+        quirks: VIP_QUIRKS,
+    };
+    // SUPER-CHIP 1.1 on the HP 48: 128x64 after 00FF, and a faster calculator.
+    pub const SCHIP: Platform = Platform {
+        name: "schip",
+        start: HEAD_OF_PROGRAM,
+        width: WIDTH,
+        height: HEIGHT,
+        font: &SPRITES,
+        cpu_hz: 1800,
+        quirks: Quirks {
+            vf_reset: false,
+            load_store_increments_i: false,
+            shift_vy: false,
+            jump_vx: true,
+            clipping: true,
+            display_wait: false,
+            half_scroll: true,
+            index_overflow: false,
+            row_collisions: true,
+        },
+    };
+    // XO-CHIP as run by Octo: wrapping sprites and a thousand instructions per frame.
+    pub const XOCHIP: Platform = Platform {
+        name: "xochip",
+        start: HEAD_OF_PROGRAM,
+        width: WIDTH,
+        height: HEIGHT,
+        font: &SPRITES,
+        cpu_hz: 60_000,
+        quirks: Quirks {
+            vf_reset: false,
+            load_store_increments_i: true,
+            shift_vy: true,
+            jump_vx: false,
+            clipping: false,
+            display_wait: false,
+            half_scroll: false,
+            index_overflow: false,
+            row_collisions: false,
+        },
+    };
+    pub const ALL: [Platform; 6] = [
+        Platform::CHIP8,
+        Platform::VIP,
+        Platform::SCHIP,
+        Platform::XOCHIP,
+        Platform::ETI660,
+        Platform::DREAM6800,
+    ];
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        let result = 2 + 2;
-        assert_eq!(result, 4);
+impl Default for Platform {
+    fn default() -> Self {
+        Platform::CHIP8
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for Platform {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .iter()
+            .find(|p| p.name == s.trim())
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = Platform::ALL.iter().map(|p| p.name).collect();
+                format!("unknown platform `{}` (one of {})", s, names.join(", "))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+// colors indexed by the plane bits of a pixel:
+// 0 = background, 1 = first plane, 2 = second plane, 3 = both planes (XO-CHIP).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [Rgb; 4]);
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette([
+            Rgb(0x00, 0x00, 0x00),
+            Rgb(0xff, 0xff, 0xff),
+            Rgb(0xaa, 0xaa, 0xaa),
+            Rgb(0x55, 0x55, 0x55),
+        ])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::str::FromStr for Palette {
+    type Err = String;
+    // parses comma separated `#rrggbb` colors (Octo's background, fill, fill2, blend);
+    // missing trailing colors are taken from the default palette.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut p = Palette::default();
+        let colors: Vec<&str> = s.split(',').map(|c| c.trim()).collect();
+        if colors.len() > 4 {
+            return Err(format!("too many colors in palette: {}", s));
+        }
+        for (i, c) in colors.iter().enumerate() {
+            let hex = c.strip_prefix('#').unwrap_or(c);
+            let n = u32::from_str_radix(hex, 16)
+                .ok()
+                .filter(|_| hex.len() == 6)
+                .ok_or_else(|| format!("invalid color: {}", c))?;
+            p.0[i] = Rgb((n >> 16) as u8, (n >> 8) as u8, n as u8);
+        }
+        Ok(p)
+    }
+}
+
+// formats the palette as parsed by `from_str`.
+impl core::fmt::Display for Palette {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for (i, c) in self.0.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{}#{:02x}{:02x}{:02x}", sep, c.0, c.1, c.2)?;
+        }
+        Ok(())
+    }
+}
+
+// the bits of both XO-CHIP planes in a pixel
+const ALL_PLANES: u8 = 0b11;
+
+// the screen: one palette index (plane bits) per pixel of the current mode, drawn by the core.
+#[derive(Debug, Clone, Eq)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    // size of the low-resolution mode of the platform
+    lores: (usize, usize),
+    hires: bool,
+    // the pixels of the current mode come first
+    pixels: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    // rows changed since the last `mark_presented`
+    dirty: [bool; HIRES_HEIGHT],
+}
+
+impl FrameBuffer {
+    // a screen of at most HIRES_WIDTH x HIRES_HEIGHT pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        let (width, height) = (width.min(HIRES_WIDTH), height.min(HIRES_HEIGHT));
+        FrameBuffer {
+            width,
+            height,
+            lores: (width, height),
+            hires: false,
+            pixels: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            dirty: [true; HIRES_HEIGHT],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    // whether the SUPER-CHIP 128x64 mode is on (00FF); `width`/`height` follow the mode.
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+    // the pixels in row-major order.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels[..self.width * self.height]
+    }
+    fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels[..self.width * self.height]
+    }
+    // whether any row changed since the last `mark_presented`.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty[..self.height].contains(&true)
+    }
+    pub fn row_dirty(&self, y: usize) -> bool {
+        self.dirty[y]
+    }
+    // the rows changed since the last `mark_presented`, top to bottom.
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.height).filter(|y| self.dirty[*y])
+    }
+    // forgets the changes; `Chip` calls it after every `Display::present`.
+    pub fn mark_presented(&mut self) {
+        self.dirty.fill(false);
+    }
+    pub fn clear(&mut self) {
+        self.clear_planes(ALL_PLANES);
+    }
+    // turns off the planes of the bits of `planes` (XO-CHIP 00E0), keeping the others.
+    pub fn clear_planes(&mut self, planes: u8) {
+        self.pixels_mut().iter_mut().for_each(|p| *p &= !planes);
+        self.dirty.fill(true);
+    }
+    // FNV-1a over the pixels of the current mode in row-major order; stable across runs and
+    // builds.
+    pub fn hash(&self) -> u64 {
+        self.pixels().iter().fold(0xcbf29ce484222325, |h, p| {
+            (h ^ *p as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+    // the screen as a binary PBM (P4) image: lit pixels (of any plane) are black.
+    #[cfg(feature = "alloc")]
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut out = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+        for row in self.pixels().chunks(self.width) {
+            out.extend(row.chunks(8).map(|bits| {
+                bits.iter()
+                    .enumerate()
+                    .fold(0u8, |b, (i, p)| b | (((*p != 0) as u8) << (7 - i)))
+            }));
+        }
+        out
+    }
+    // XORs a sprite of 8 pixel wide rows onto the first plane, wrapping around the edges;
+    // returns whether a lit pixel was turned off.
+    pub fn draw(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        self.draw_rows(x, y, sprite, 1, 1) > 0
+    }
+    // draws a 16x16 sprite given as 16 rows of 2 bytes.
+    pub fn draw16(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
+        self.draw_rows(x, y, sprite, 2, 1) > 0
+    }
+    // draws rows of `row_bytes` bytes onto the plane with the bit `plane` and returns how many
+    // rows erased a pixel of that plane.
+    fn draw_rows(&mut self, x: u8, y: u8, sprite: &[u8], row_bytes: usize, plane: u8) -> usize {
+        let (w, h) = (self.width, self.height);
+        let mut collided = 0;
+        for (row, bytes) in sprite.chunks(row_bytes).enumerate() {
+            let mut hit = false;
+            for (col, bits) in bytes.iter().enumerate() {
+                let dx = col * 8;
+                for bit in 0..8 {
+                    if bits >> (7 - bit) & 1 == 0 {
+                        continue;
+                    }
+                    let (tx, ty) = ((x as usize + dx + bit) % w, (y as usize + row) % h);
+                    let p = &mut self.pixels[ty * w + tx];
+                    hit |= *p & plane != 0;
+                    *p ^= plane;
+                    self.dirty[ty] = true;
+                }
+            }
+            collided += hit as usize;
+        }
+        collided
+    }
+    // scrolls down by `rows` pixels.
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.scroll(0, rows as isize, ALL_PLANES);
+    }
+    // scrolls left by `columns` pixels.
+    pub fn scroll_left(&mut self, columns: usize) {
+        self.scroll(-(columns as isize), 0, ALL_PLANES);
+    }
+    // scrolls right by `columns` pixels.
+    pub fn scroll_right(&mut self, columns: usize) {
+        self.scroll(columns as isize, 0, ALL_PLANES);
+    }
+    // moves the planes of the bits of `planes` by (dx, dy) pixels, filling in dark pixels; the
+    // other planes stay (XO-CHIP scrolls the selected planes only).
+    pub fn scroll(&mut self, dx: isize, dy: isize, planes: u8) {
+        let (w, h) = (self.width as isize, self.height as isize);
+        // visits the pixels away from the direction of the move, so that each one is read
+        // before it is overwritten.
+        let xs = (0..w).map(move |x| if dx > 0 { w - 1 - x } else { x });
+        for y in (0..h).map(|y| if dy > 0 { h - 1 - y } else { y }) {
+            for x in xs.clone() {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if (0..w).contains(&sx) && (0..h).contains(&sy) {
+                    self.pixels[(sy * w + sx) as usize] & planes
+                } else {
+                    0
+                };
+                let p = &mut self.pixels[(y * w + x) as usize];
+                *p = *p & !planes | moved;
+            }
+        }
+        self.dirty.fill(true);
+    }
+    // switches between the low-resolution size and HIRES_WIDTH x HIRES_HEIGHT (SUPER-CHIP),
+    // clearing the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        let (w, h) = if hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            self.lores
+        };
+        self.hires = hires;
+        self.resize(w, h);
+    }
+    // sets the low-resolution size of a `Platform` and switches to it.
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        let (width, height) = (width.min(HIRES_WIDTH), height.min(HIRES_HEIGHT));
+        self.lores = (width, height);
+        self.hires = false;
+        self.resize(width, height);
+    }
+    fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels.fill(0);
+        self.dirty.fill(true);
+    }
+    // copies pixels of `pixels` (as returned by `pixels`) into the current mode.
+    pub fn restore(&mut self, pixels: &[u8]) {
+        let n = pixels.len().min(self.width * self.height);
+        self.pixels[..n].copy_from_slice(&pixels[..n]);
+        self.dirty.fill(true);
+    }
+}
+
+// screens are equal by their mode and pixels, whatever was presented.
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        (self.width, self.height, self.lores, self.hires)
+            == (other.width, other.height, other.lores, other.hires)
+            && self.pixels() == other.pixels()
+    }
+}
+
+// shows the screen; the core draws into its `FrameBuffer` and hands it over once per 60Hz frame
+// in which it changed (`FrameBuffer::dirty_rows` tells which rows), and after `load_state` and
+// `set_palette`.
+pub trait Display {
+    fn present(&mut self, frame: &FrameBuffer);
+    fn set_palette(&mut self, _palette: &Palette) {}
+}
+// what the program learned from the keyboard, reported to `Keyboard::observed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCheck {
+    // EX9E/EXA1 asked whether `key` is down.
+    Pressed { key: u8, down: bool },
+    // FX0A received `key` (pressed and released).
+    Waited { key: u8 },
+}
+
+pub trait Keyboard {
+    // must not block: FX0A polls the keys every cycle until one is pressed and released. Must
+    // not change the keys either, other observers ask too.
+    fn is_pressed(&self, key: u8) -> bool;
+    // all the keys `is_pressed` reports, bit n for key n.
+    fn state(&self) -> u16 {
+        (0..0x10)
+            .filter(|k| self.is_pressed(*k))
+            .fold(0, |m, k| m | 1 << k)
+    }
+    // the keys (bit n for key n) EX9E, EXA1 or FX0A just read: a keyboard reporting short taps
+    // as pressed until the machine looked forgets them here.
+    fn consumed(&self, _keys: u16) {}
+    // FX0A started (true) or stopped (false) waiting for a key, e.g. to show a prompt.
+    fn waiting(&self, _waiting: bool) {}
+    // called after each key-checking opcode, e.g. for diagnostics.
+    fn observed(&self, _check: KeyCheck) {}
+}
+
+// sound output; the beep lasts while the sound timer is non-zero.
+pub trait Audio {
+    fn start_beep(&mut self);
+    fn stop_beep(&mut self);
+}
+
+// the default audio, which plays nothing.
+pub struct Silent;
+impl Audio for Silent {
+    fn start_beep(&mut self) {}
+    fn stop_beep(&mut self) {}
+}
+
+// a display showing nothing, for headless runs which read `Chip::framebuffer` themselves.
+pub struct NullDisplay;
+impl Display for NullDisplay {
+    fn present(&mut self, _frame: &FrameBuffer) {}
+}
+
+// a keyboard whose keys are never pressed.
+pub struct NullKeyboard;
+impl Keyboard for NullKeyboard {
+    fn is_pressed(&self, _key: u8) -> bool {
+        false
+    }
+}
+
+// builds a `Chip` from optional parts: `Chip::builder().seed(1).build()` is a headless machine
+// like `Chip::headless`. The platform is applied first, so quirks, clock and start override it.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub struct ChipBuilder {
+    display: Option<Box<dyn Display>>,
+    keyboard: Option<Box<dyn Keyboard>>,
+    audio: Option<Box<dyn Audio>>,
+    platform: Option<Platform>,
+    quirks: Option<Quirks>,
+    cpu_hz: Option<u32>,
+    seed: Option<u64>,
+    start: Option<u16>,
+}
+
+#[cfg(feature = "alloc")]
+impl ChipBuilder {
+    pub fn display(mut self, display: impl Display + 'static) -> Self {
+        self.display = Some(Box::new(display));
+        self
+    }
+    pub fn keyboard(mut self, keyboard: impl Keyboard + 'static) -> Self {
+        self.keyboard = Some(Box::new(keyboard));
+        self
+    }
+    pub fn audio(mut self, audio: impl Audio + 'static) -> Self {
+        self.audio = Some(Box::new(audio));
+        self
+    }
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+    // the clock of `run` (see `Speed::set_cpu_hz`) and `run_headless`.
+    pub fn cpu_hz(mut self, hz: u32) -> Self {
+        self.cpu_hz = Some(hz);
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    // where programs are loaded and start.
+    pub fn start(mut self, start: u16) -> Self {
+        self.start = Some(start);
+        self
+    }
+    pub fn build(self) -> Chip {
+        let mut chip = Chip::new(
+            self.display.unwrap_or_else(|| Box::new(NullDisplay)),
+            self.keyboard.unwrap_or_else(|| Box::new(NullKeyboard)),
+        );
+        if let Some(audio) = self.audio {
+            chip.set_audio(audio);
+        }
+        if let Some(platform) = &self.platform {
+            chip.set_platform(platform);
+        }
+        if let Some(quirks) = self.quirks {
+            chip.set_quirks(quirks);
+        }
+        if let Some(hz) = self.cpu_hz {
+            #[cfg(feature = "std")]
+            chip.speed.set_cpu_hz(Some(hz));
+            chip.set_cycles_per_frame(hz / 60);
+        }
+        if let Some(seed) = self.seed {
+            chip.set_seed(seed);
+        }
+        if let Some(start) = self.start {
+            chip.cpu.start = start;
+            chip.cpu.pc = start;
+        }
+        chip
+    }
+}
+
+// executes one instruction on a headless machine with `ram` copied from address 0 and the
+// registers of `state`, for fuzzing the decoder and executor (`chip8/fuzz`): whatever the
+// input, it returns a `Step` or a `Trap` and never panics.
+pub fn fuzz_step(ram: &[u8], state: CpuView) -> Result<Step, Trap> {
+    let mut core = Core::new(NullDisplay, NullKeyboard);
+    let n = ram.len().min(RAM_SIZE);
+    core.ram.buf[..n].copy_from_slice(&ram[..n]);
+    // mostly inside RAM, so that most inputs reach the executor
+    core.set_registers(CpuView {
+        pc: state.pc % RAM_SIZE as u16,
+        ..state
+    });
+    core.step()
+}
+
+// a keyboard whose keys the embedder sets with `press`/`release` (browser, C callers); clones
+// share the keys. A key pressed and released before the machine read it is reported pressed
+// until it was read.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Default)]
+pub struct Keypad(alloc::rc::Rc<KeypadState>);
+
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+struct KeypadState {
+    // bit n is set while key n is held, or since it was pressed until it is read (`consumed`)
+    held: Cell<u16>,
+    tapped: Cell<u16>,
+}
+
+#[cfg(feature = "alloc")]
+impl Keypad {
+    pub fn new() -> Self {
+        Keypad::default()
+    }
+    pub fn press(&self, key: u8) {
+        let bit = 1 << (key & 0xF);
+        self.0.held.set(self.0.held.get() | bit);
+        self.0.tapped.set(self.0.tapped.get() | bit);
+    }
+    pub fn release(&self, key: u8) {
+        self.0.held.set(self.0.held.get() & !(1 << (key & 0xF)));
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Keyboard for Keypad {
+    fn is_pressed(&self, key: u8) -> bool {
+        (self.0.held.get() | self.0.tapped.get()) & 1 << (key & 0xF) != 0
+    }
+    fn consumed(&self, keys: u16) {
+        self.0.tapped.set(self.0.tapped.get() & !keys);
+    }
+}
+
+// the tests drive `run`, so they need the `std` feature.
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let result = 2 + 2;
+        assert_eq!(result, 4);
+    }
+
+    // an empty program leaves the program area blank.
+    fn chip_with(program: &[u8]) -> Chip {
+        let mut chip = Chip::headless();
+        if !program.is_empty() {
+            chip.load_slice(program).unwrap();
+        }
+        chip
+    }
+
+    #[test]
+    fn quirk_hit_is_reported_once() {
+        let mut chip = chip_with(&[0x80, 0x06, 0x80, 0x0E, 0x60, 0x01]);
+        chip.warn_quirks(true);
+        chip.cycle().unwrap();
+        assert_eq!(chip.quirk_hit(), Some(Quirk::Shift));
+        chip.cycle().unwrap();
+        assert_eq!(chip.quirk_hit(), None);
+        chip.cycle().unwrap();
+        assert_eq!(chip.quirk_hit(), None);
+    }
+
+    #[test]
+    fn big_sprites_clip_at_their_width() {
+        // HIGH ; LD V0, 112 ; LD V1, 48 ; DRW V0, V1, 0 ; LD V0, 113 ; DRW V0, V1, 0
+        let mut chip = chip_with(&[
+            0x00, 0xFF, 0x60, 0x70, 0x61, 0x30, 0xD0, 0x10, 0x60, 0x71, 0xD0, 0x10,
+        ]);
+        chip.warn_quirks(true);
+        for _ in 0..4 {
+            chip.cycle().unwrap();
+        }
+        assert_eq!(chip.quirk_hit(), None);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        assert_eq!(chip.quirk_hit(), Some(Quirk::Clipping));
+    }
+
+    #[test]
+    fn drw_draws_into_the_framebuffer() {
+        // LD V1, 0xE ; LD F, V1 ; LD V0, 62 ; DRW V0, V0, 5 ; DRW V0, V0, 5
+        let mut chip = chip_with(&[0x61, 0x0E, 0xF1, 0x29, 0x60, 0x3E, 0xD0, 0x05, 0xD0, 0x05]);
+        for _ in 0..4 {
+            chip.cycle().unwrap();
+        }
+        let fb = chip.framebuffer();
+        assert_eq!(chip.cpu.v[0xF], 0);
+        // the sprite wraps around both edges
+        assert_eq!(
+            (fb.pixel(62, 31), fb.pixel(1, 30), fb.pixel(1, 0)),
+            (1, 1, 1)
+        );
+        assert_eq!(fb.pixels().iter().filter(|p| **p != 0).count(), 14);
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.v[0xF], 1);
+        assert!(chip.framebuffer().pixels().iter().all(|p| *p == 0));
+    }
+
+    #[test]
+    fn framebuffer_exports_pbm() {
+        let mut fb = FrameBuffer::new(WIDTH, HEIGHT);
+        fb.draw(0, 1, &[0b1010_0000]);
+        fb.draw(62, 31, &[0b1100_0000]);
+        let pbm = fb.to_pbm();
+        let header = b"P4\n64 32\n";
+        assert_eq!(pbm.len(), header.len() + 8 * 32);
+        assert_eq!(pbm[header.len() + 8], 0b1010_0000);
+        assert_eq!(*pbm.last().unwrap(), 0b0000_0011);
+    }
+
+    #[test]
+    fn xo_chip_planes() {
+        let mut prog = vec![
+            0xF2, 0x01, // PLANE 2
+            0xA2, 0x20, // LD I, 0x220
+            0xD0, 0x01, // DRW V0, V0, 1
+            0xF3, 0x01, // PLANE 3
+            0xD0, 0x01, // DRW V0, V0, 1 (a row for each plane)
+            0xF1, 0x01, // PLANE 1
+            0x00, 0xE0, // CLS
+            0xF2, 0x01, // PLANE 2
+            0x00, 0xFB, // SCR
+        ];
+        prog.resize(0x20, 0);
+        prog.extend([0x80, 0xC0]);
+        let mut chip = chip_with(&prog);
+        let row = |c: &Chip| c.framebuffer().pixels()[..8].to_vec();
+        for _ in 0..3 {
+            chip.cycle().unwrap();
+        }
+        assert_eq!(row(&chip), [2, 0, 0, 0, 0, 0, 0, 0]);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        // plane 1 lit at 0, plane 2 turned off there and lit at 1
+        assert_eq!(row(&chip), [1, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(chip.cpu.v[0xF], 1);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        assert_eq!(row(&chip), [0, 2, 0, 0, 0, 0, 0, 0]);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        assert_eq!(row(&chip), [0, 0, 0, 0, 0, 2, 0, 0]);
+        assert_eq!(chip.save_state().planes, 2);
+        chip.reset();
+        assert_eq!(chip.cpu.planes, 1);
+    }
+
+    #[test]
+    fn framebuffer_scrolls_and_switches_modes() {
+        let mut fb = FrameBuffer::new(WIDTH, HEIGHT);
+        fb.draw(0, 0, &[0x80]);
+        fb.scroll_down(2);
+        fb.scroll_right(4);
+        assert_eq!(fb.pixel(4, 2), 1);
+        fb.scroll_left(4);
+        assert_eq!(fb.pixel(0, 2), 1);
+        fb.set_hires(true);
+        assert_eq!((fb.width(), fb.height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert!(!fb.draw16(0, 0, &[0xFF; 32]));
+        fb.set_hires(false);
+        assert_eq!((fb.width(), fb.height()), (WIDTH, HEIGHT));
+    }
+
+    #[test]
+    fn dirty_rows_are_presented_once_per_frame() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        // rows the display saw changed, per present
+        struct Rows(Rc<RefCell<Vec<Vec<usize>>>>);
+        impl Display for Rows {
+            fn present(&mut self, frame: &FrameBuffer) {
+                self.0.borrow_mut().push(frame.dirty_rows().collect());
+            }
+        }
+        let presented = Rc::new(RefCell::new(Vec::new()));
+        let mut chip = Chip::new(Box::new(Rows(presented.clone())), Box::new(NullKeyboard));
+        // LD V0, 4 ; DRW V0, V0, 2 ; DRW V0, V0, 1
+        chip.load_slice(&[0x60, 0x04, 0xD0, 0x02, 0xD0, 0x01])
+            .unwrap();
+        chip.tick_60hz();
+        presented.borrow_mut().clear();
+        for _ in 0..3 {
+            chip.cycle().unwrap();
+        }
+        assert!(presented.borrow().is_empty());
+        chip.tick_60hz();
+        chip.tick_60hz();
+        assert_eq!(*presented.borrow(), [vec![4, 5]]);
+        assert!(!chip.framebuffer().is_dirty());
+    }
+
+    #[test]
+    fn scrolling_in_low_resolution() {
+        // LD I, font 0 ; DRW V0, V0, 1 ; SCD 2 ; SCR ; SCL ; SCR
+        let program = [
+            0xA0, 0x00, 0xD0, 0x01, 0x00, 0xC2, 0x00, 0xFB, 0x00, 0xFC, 0x00, 0xFB,
+        ];
+        let lit = |chip: &Chip| {
+            let fb = chip.framebuffer();
+            (0..HEIGHT)
+                .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+                .find(|(x, y)| fb.pixel(*x, *y) != 0)
+        };
+        let mut chip = chip_with(&program);
+        for _ in 0..6 {
+            chip.cycle().unwrap();
+        }
+        assert_eq!(lit(&chip), Some((4, 2)));
+        let mut chip = chip_with(&program).with_quirks("half-scroll".parse().unwrap());
+        for _ in 0..6 {
+            chip.cycle().unwrap();
+        }
+        assert_eq!(lit(&chip), Some((2, 1)));
+    }
+
+    #[test]
+    fn step_tells_what_happened() {
+        // ADD V1, V2; SE V0, 0; CLS; LD I, 0x300; DRW V0, V0, 1; JP 0x200
+        let mut chip = chip_with(&[
+            0x81, 0x24, 0x30, 0x00, 0x00, 0xE0, 0xA3, 0x00, 0xD0, 0x01, 0x12, 0x00,
+        ]);
+        chip.cpu.v[1] = 0xFF;
+        chip.cpu.v[2] = 1;
+        let add = chip.step().unwrap();
+        assert_eq!(add.addr, 0x200);
+        assert_eq!(add.opcode, Some(opcode::Opcode::AddVxVy { x: 1, y: 2 }));
+        assert_eq!(add.control, Control::Next);
+        assert_eq!(add.changed_v, 1 << 1 | 1 << 0xF);
+        assert!(!add.changed_i && !add.display_changed);
+        assert_eq!(chip.step().unwrap().control, Control::Skip);
+        let ld = chip.step().unwrap();
+        assert_eq!((ld.addr, ld.changed_i, ld.changed_v), (0x206, true, 0));
+        assert!(chip.step().unwrap().display_changed);
+        assert_eq!(chip.step().unwrap().control, Control::Jump(0x200));
+    }
+
+    // runs `op` once with I and V0..=VF set, returns the chip.
+    fn exec(op: [u8; 2], i: u16, v: [u8; 16], quirks: Quirks) -> Chip {
+        let mut chip = chip_with(&op);
+        chip.set_quirks(quirks);
+        chip.cpu.i = i;
+        chip.cpu.v = v;
+        chip.cycle().unwrap();
+        chip
+    }
+
+    #[test]
+    fn bcd_stores_digits() {
+        for v in [0u8, 1, 9, 10, 99, 100, 109, 255] {
+            let mut regs = [0; 16];
+            regs[3] = v;
+            let chip = exec([0xF3, 0x33], 0x300, regs, Quirks::default());
+            assert_eq!(chip.ram.buf[0x300..0x303], [v / 100, v / 10 % 10, v % 10]);
+        }
+    }
+
+    #[test]
+    fn bcd_near_end_of_ram_traps() {
+        for i in RAM_SIZE as u16 - 3..=RAM_SIZE as u16 + 2 {
+            let mut chip = chip_with(&[0xF0, 0x33]);
+            chip.cpu.i = i;
+            chip.cpu.v[0] = 123;
+            let fits = usize::from(i) + 3 <= RAM_SIZE;
+            match chip.cycle() {
+                Ok(()) => assert!(fits),
+                Err(trap) => {
+                    assert!(!fits);
+                    assert_eq!(
+                        trap,
+                        Trap::MemoryOutOfRange {
+                            pc: 0x200,
+                            i,
+                            len: 3
+                        }
+                    );
+                    assert_eq!(chip.cpu.pc, HEAD_OF_PROGRAM);
+                    assert!(chip.ram.buf[RAM_SIZE - 3..].iter().all(|b| *b == 0));
+                }
+            }
+        }
+        let chip = exec(
+            [0xF0, 0x33],
+            RAM_SIZE as u16 - 3,
+            [123; 16],
+            Quirks::default(),
+        );
+        assert_eq!(chip.ram.buf[RAM_SIZE - 3..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn load_store_registers_at_boundaries() {
+        let regs: [u8; 16] = core::array::from_fn(|n| n as u8 + 1);
+        for x in 0..16u8 {
+            for i in [0x300, RAM_SIZE as u16 - 16, RAM_SIZE as u16 - 1, 0xffff] {
+                let fits = usize::from(i) + (x as usize) < RAM_SIZE;
+                for inc in [false, true] {
+                    let quirks = Quirks {
+                        load_store_increments_i: inc,
+                        ..Quirks::default()
+                    };
+                    let mut store = chip_with(&[0xF0 | x, 0x55]);
+                    store.set_quirks(quirks);
+                    store.cpu.i = i;
+                    store.cpu.v = regs;
+                    let mut load = chip_with(&[0xF0 | x, 0x65]);
+                    load.set_quirks(quirks);
+                    load.cpu.i = i;
+                    if !fits {
+                        let trap = Trap::MemoryOutOfRange {
+                            pc: 0x200,
+                            i,
+                            len: x as usize + 1,
+                        };
+                        assert_eq!(store.cycle(), Err(trap));
+                        assert_eq!(load.cycle(), Err(trap));
+                        assert_eq!((store.cpu.i, load.cpu.i), (i, i));
+                        assert_eq!(load.cpu.v, [0; 16]);
+                        continue;
+                    }
+                    store.cycle().unwrap();
+                    let n = i as usize;
+                    assert_eq!(store.ram.buf[n..=n + x as usize], regs[..=x as usize]);
+                    let expected_i = if inc { i + x as u16 + 1 } else { i };
+                    assert_eq!(store.cpu.i, expected_i);
+                    load.ram.buf[n..].copy_from_slice(&store.ram.buf[n..]);
+                    load.cycle().unwrap();
+                    assert_eq!(load.cpu.v[..=x as usize], regs[..=x as usize]);
+                    assert_eq!(load.cpu.i, expected_i);
+                }
+            }
+        }
+        // DRW with the sprite past the end of RAM
+        let mut chip = chip_with(&[0xD0, 0x05]);
+        chip.cpu.i = RAM_SIZE as u16 - 4;
+        assert_eq!(
+            chip.cycle(),
+            Err(Trap::MemoryOutOfRange {
+                pc: 0x200,
+                i: RAM_SIZE as u16 - 4,
+                len: 5
+            })
+        );
+    }
+
+    #[test]
+    fn skip_over_long_instruction() {
+        // SE V0, 0 ; F000 1234 (4 bytes) ; SE V0, 1 ; F000 1234
+        let mut chip = chip_with(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x30, 0x01, 0xF0, 0x00]);
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.pc, HEAD_OF_PROGRAM + 6);
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.pc, HEAD_OF_PROGRAM + 8);
+
+        // LD I, long 0x1234 ; then an F000 cut off by the end of RAM
+        let mut chip = chip_with(&[0xF0, 0x00, 0x12, 0x34]);
+        chip.cycle().unwrap();
+        assert_eq!((chip.cpu.i, chip.cpu.pc), (0x1234, HEAD_OF_PROGRAM + 4));
+        let end = (RAM_SIZE - 2) as u16;
+        chip.ram.buf[end as usize..].copy_from_slice(&[0xF0, 0x00]);
+        chip.cpu.pc = end;
+        assert_eq!(chip.cycle(), Err(Trap::PcOutOfRange { pc: end }));
+    }
+
+    #[test]
+    fn profiling_counts_executed_instructions() {
+        // LD V0, 1 ; JP 0x202
+        let mut chip = chip_with(&[0x60, 0x01, 0x12, 0x02]);
+        chip.run_headless(5).unwrap();
+        assert!(chip.profile().is_none());
+        chip.set_profiling(true);
+        chip.reset();
+        chip.run_headless(10).unwrap();
+        let p = chip.profile().unwrap();
+        assert_eq!((p.total(), p.at(0x200), p.at(0x202)), (10, 1, 9));
+        assert_eq!(p.opcode("1NNN"), 9);
+        chip.set_profiling(true);
+        assert_eq!(chip.profile().unwrap().total(), 0);
+    }
+
+    #[test]
+    fn state_hash_follows_execution() {
+        // LD V0, 1 ; LD I, 0x300 ; LD B, V0
+        let program = [0x60, 0x01, 0xA3, 0x00, 0xF0, 0x33];
+        let (mut a, mut b) = (chip_with(&program), chip_with(&program));
+        for _ in 0..3 {
+            assert_eq!(a.state_hash(), b.state_hash());
+            a.cycle().unwrap();
+            assert_ne!(a.state_hash(), b.state_hash());
+            b.cycle().unwrap();
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    struct Beeps(Arc<Mutex<Vec<bool>>>);
+    impl Audio for Beeps {
+        fn start_beep(&mut self) {
+            self.0.lock().unwrap().push(true);
+        }
+        fn stop_beep(&mut self) {
+            self.0.lock().unwrap().push(false);
+        }
+    }
+
+    #[test]
+    fn sound_timer_drives_the_beep() {
+        // LD V0, 2 ; LD ST, V0 ; LD ST, V0
+        let mut chip = chip_with(&[0x60, 0x02, 0xF0, 0x18, 0xF0, 0x18]);
+        let beeps = Arc::new(Mutex::new(Vec::new()));
+        chip.set_audio(Box::new(Beeps(beeps.clone())));
+        chip.cycle().unwrap();
+        assert!(beeps.lock().unwrap().is_empty());
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        assert_eq!(*beeps.lock().unwrap(), vec![true]);
+        chip.cpu.st.set(0);
+        chip.cycle().unwrap();
+        assert_eq!(*beeps.lock().unwrap(), vec![true, false]);
+    }
+
+    #[test]
+    fn timers_count_down_per_tick() {
+        // LD V0, 3 ; LD DT, V0 ; LD V1, DT
+        let mut chip = chip_with(&[0x60, 0x03, 0xF0, 0x15, 0xF1, 0x07]);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        chip.tick_60hz();
+        chip.tick_60hz();
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.v[1], 1);
+        (0..3).for_each(|_| chip.tick_60hz());
+        assert_eq!(chip.cpu.dt.get(), 0);
+    }
+
+    #[test]
+    fn ram_records_accesses() {
+        // LD I, 0x300 ; LD B, V0 ; LD V2, [I]
+        let mut chip = chip_with(&[0xA3, 0x00, 0xF0, 0x33, 0xF2, 0x65]);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        let ram = &chip.ram;
+        assert!(ram.accessed(0x201, Access::Executed));
+        assert!(!ram.accessed(0x204, Access::Executed));
+        assert!(ram.accessed(0x302, Access::Written));
+        assert!(!ram.accessed(0x303, Access::Written));
+        ram.clear_recent();
+        assert!(!ram.recently(0x300, Access::Written));
+        chip.cycle().unwrap();
+        assert!(chip.ram.recently(0x302, Access::Read));
+        assert!(!chip.ram.recently(0x300, Access::Written));
+        assert!(chip.ram.accessed(0x300, Access::Written));
+    }
+
+    #[test]
+    fn seeded_random_is_reproducible() {
+        // RND V0, 0xFF ; RND V1, 0xFF ; RND V2, 0x0F
+        let program = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0x0F];
+        let run = |seed| {
+            let mut chip = chip_with(&program).with_seed(seed);
+            (0..3).for_each(|_| chip.cycle().unwrap());
+            chip.cpu.v
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+        assert!(run(1)[2] < 0x10);
+        let mut chip = chip_with(&program);
+        chip.set_rng(rand::rngs::mock::StepRng::new(0x55, 0));
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.v[0], 0x55);
+    }
+
+    #[test]
+    fn load_state_rewinds_the_machine() {
+        // LD V0, 1 ; LD I, 0x300 ; ADD V0, 1 ; LD B, V0 ; CALL 0x204
+        let mut chip = chip_with(&[0x60, 0x01, 0xA3, 0x00, 0x70, 0x01, 0xF0, 0x33, 0x22, 0x04]);
+        (0..4).for_each(|_| chip.cycle().unwrap());
+        let saved = chip.save_state();
+        let hash = chip.state_hash();
+        (0..7).for_each(|_| chip.cycle().unwrap());
+        assert_ne!(chip.state_hash(), hash);
+        chip.load_state(saved.clone());
+        assert_eq!(chip.state_hash(), hash);
+        assert_eq!(chip.save_state(), saved);
+        assert_eq!(saved.ram[0x302], 2);
+    }
+
+    #[test]
+    fn load_state_resumes_a_key_wait() {
+        let keypad = Keypad::new();
+        let mut chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        // LD V1, K ; LD V2, 1
+        chip.load_slice(&[0xF1, 0x0A, 0x62, 0x01]).unwrap();
+        keypad.press(3);
+        chip.step().unwrap();
+        let held = chip.save_state();
+        keypad.release(3);
+        keypad.press(7);
+        chip.step().unwrap();
+        let pressed = chip.save_state();
+        keypad.release(7);
+        chip.step().unwrap();
+        assert!(!chip.waiting_for_key());
+        // back in FX0A with 7 pressed: releasing it completes the wait
+        chip.load_state(pressed);
+        assert!(chip.waiting_for_key());
+        chip.step().unwrap();
+        assert_eq!((chip.pc(), chip.cpu.v[1]), (0x202, 7));
+        // back to when 3 was held: it still does not count
+        chip.load_state(held);
+        keypad.press(3);
+        chip.step().unwrap();
+        keypad.release(3);
+        chip.step().unwrap();
+        assert!(chip.waiting_for_key());
+    }
+
+    #[test]
+    fn rewind_restores_older_frames() {
+        // ADD V0, 1 ; JP 0x200 -- V0 counts the frames at 2 cycles per frame
+        let mut chip = chip_with(&[0x70, 0x01, 0x12, 0x00]);
+        assert_eq!(chip.rewind(1), None);
+        chip.set_rewind(4, 2);
+        for _ in 0..10 {
+            chip.tick_60hz();
+            (0..2).for_each(|_| chip.cycle().unwrap());
+        }
+        assert_eq!(chip.cpu.v[0], 10);
+        // snapshots of frames 2, 4, 6 and 8; frame 9 is the current one
+        assert_eq!(chip.rewind(3), Some(3));
+        assert_eq!(chip.cpu.v[0], 6);
+        assert_eq!(chip.rewind(100), Some(4));
+        assert_eq!(chip.cpu.v[0], 2);
+    }
+
+    #[test]
+    fn unknown_opcode_traps() {
+        // LD V0, 1 ; 8XY8 (undefined)
+        let mut chip = chip_with(&[0x60, 0x01, 0x80, 0x18]);
+        chip.cycle().unwrap();
+        let trap = Trap::UnknownOpcode {
+            pc: HEAD_OF_PROGRAM + 2,
+            opcode: 0x8018,
+        };
+        assert_eq!(chip.cycle(), Err(trap));
+        assert_eq!(chip.pc(), HEAD_OF_PROGRAM + 2);
+        assert_eq!(chip.run(1000), Err(trap));
+        assert_eq!(trap.to_string(), "unknown opcode 8018 at 202");
+    }
+
+    #[test]
+    fn schip_mode_and_exit() {
+        // HIGH ; LD V0, 3 ; LD HF, V0 ; LD R, V1 ; LD V0, 0 ; LD V1, R ; EXIT
+        let mut chip = chip_with(&[
+            0x00, 0xFF, 0x60, 0x03, 0xF0, 0x30, 0xF1, 0x75, 0x60, 0x00, 0xF1, 0x85, 0x00, 0xFD,
+        ]);
+        for _ in 0..7 {
+            chip.cycle().unwrap();
+        }
+        assert!(chip.framebuffer().hires());
+        assert_eq!(chip.cpu.i, HEAD_OF_BIG_SPRITE as u16 + 30);
+        assert_eq!(chip.ram.buf[chip.cpu.i as usize], BIG_SPRITES[3][0]);
+        assert_eq!(chip.cpu.v[0], 3);
+        assert!(chip.cpu.halted);
+        assert_eq!(chip.run(1000), Ok(Stopped::Halted));
+        assert_eq!(chip.cpu.pc, HEAD_OF_PROGRAM + 12);
+        // a halted machine executes nothing until it is reset
+        assert!(chip.halted());
+        let step = chip.step().unwrap();
+        assert_eq!(
+            (step.addr, step.control),
+            (HEAD_OF_PROGRAM + 12, Control::Wait)
+        );
+        chip.reset();
+        assert!(!chip.halted());
+        assert_eq!(chip.step().unwrap().control, Next);
+        // LD V0, 0xFE ; LD HF, V0: the low nibble picks one of the 16 digits
+        let mut chip = chip_with(&[0x60, 0xFE, 0xF0, 0x30]);
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        let i = chip.cpu.i as usize;
+        assert_eq!(i, HEAD_OF_BIG_SPRITE + 0xE * 10);
+        assert_eq!(chip.ram.buf[i..i + 10], BIG_SPRITES[0xE]);
+        assert!(i + 10 <= FONT_END && FONT_END <= PROGRAM_START);
+        // LD R, V1 ; LD V3, R: flags survive a new machine when carried over
+        let mut chip = chip_with(&[0xF1, 0x75]);
+        chip.cpu.v[..2].copy_from_slice(&[7, 9]);
+        chip.cycle().unwrap();
+        assert_eq!(chip.rpl_flags(), [7, 9, 0, 0, 0, 0, 0, 0]);
+        let mut next = chip_with(&[0xF3, 0x85]);
+        next.set_rpl_flags(chip.rpl_flags());
+        next.cycle().unwrap();
+        assert_eq!(next.cpu.v[..4], [7, 9, 0, 0]);
+    }
+
+    #[test]
+    fn key_wait_completes_on_release() {
+        let keypad = Keypad::new();
+        let mut chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        // LD V1, K ; LD V2, 1
+        chip.load_slice(&[0xF1, 0x0A, 0x62, 0x01]).unwrap();
+        // a key held before FX0A starts counts only after it was released and pressed again.
+        keypad.press(3);
+        chip.step().unwrap();
+        assert!(chip.waiting_for_key());
+        keypad.release(3);
+        chip.step().unwrap();
+        keypad.press(7);
+        chip.step().unwrap();
+        chip.step().unwrap();
+        assert_eq!(chip.pc(), 0x200);
+        keypad.release(7);
+        chip.step().unwrap();
+        assert!(!chip.waiting_for_key());
+        assert_eq!((chip.pc(), chip.cpu.v[1]), (0x202, 7));
+        // a tap between two cycles is not missed.
+        chip.load_slice(&[0xF1, 0x0A]).unwrap();
+        chip.set_registers(CpuView {
+            pc: 0x200,
+            ..chip.registers()
+        });
+        chip.step().unwrap();
+        keypad.press(9);
+        keypad.release(9);
+        chip.step().unwrap();
+        chip.step().unwrap();
+        assert_eq!(chip.cpu.v[1], 9);
+    }
+
+    #[test]
+    fn taps_last_until_the_machine_reads_them() {
+        let keypad = Keypad::new();
+        let mut chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        // SKP V0 ; SKP V0
+        chip.load_slice(&[0xE0, 0x9E, 0xE0, 0x9E]).unwrap();
+        keypad.press(0);
+        keypad.release(0);
+        // other observers do not take the tap away
+        assert!(chip.keyboard.is_pressed(0) && chip.keyboard.is_pressed(0));
+        chip.step().unwrap();
+        assert_eq!(chip.pc(), 0x204);
+        assert!(!chip.keyboard.is_pressed(0));
+    }
+
+    #[test]
+    fn display_wait_idles_until_the_next_frame() {
+        // DRW V0, V0, 1 ; ADD V1, 1
+        let mut chip =
+            chip_with(&[0xD0, 0x01, 0x71, 0x01]).with_quirks("display-wait".parse().unwrap());
+        chip.add_breakpoint(0x202);
+        assert!(chip.step().unwrap().display_changed);
+        assert!(chip.waiting_for_vblank() && !chip.at_breakpoint());
+        assert_eq!(chip.step().unwrap().control, Control::Wait);
+        assert_eq!((chip.pc(), chip.cpu.v[1]), (0x202, 0));
+        chip.tick_60hz();
+        assert!(chip.at_breakpoint());
+        chip.step().unwrap();
+        assert_eq!(chip.cpu.v[1], 1);
+    }
+
+    #[test]
+    fn run_stops_at_breakpoints() {
+        // LD V0, 1 ; ADD V0, 1 ; JP 0x202
+        let mut chip = chip_with(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+        chip.add_breakpoint(HEAD_OF_PROGRAM + 2);
+        assert_eq!(
+            chip.run(60_000),
+            Ok(Stopped::Breakpoint(HEAD_OF_PROGRAM + 2))
+        );
+        assert_eq!(chip.cpu.v[0], 1);
+        // continuing executes the instruction at the breakpoint once per loop
+        assert_eq!(
+            chip.run(60_000),
+            Ok(Stopped::Breakpoint(HEAD_OF_PROGRAM + 2))
+        );
+        assert_eq!(chip.cpu.v[0], 2);
+        assert!(chip.at_breakpoint());
+        assert!(chip.remove_breakpoint(HEAD_OF_PROGRAM + 2));
+        assert!(!chip.remove_breakpoint(HEAD_OF_PROGRAM + 2));
+        assert_eq!(chip.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn run_stops_at_watchpoints() {
+        // LD V0, 7 ; LD I, 0x300 ; LD B, V0 ; LD DT, V0 ; JP 0x208
+        let mut chip = chip_with(&[0x60, 0x07, 0xA3, 0x00, 0xF0, 0x33, 0xF0, 0x15, 0x12, 0x08]);
+        for w in ["v0", "302", "dt", "i"] {
+            chip.add_watchpoint(w.parse().unwrap());
+        }
+        let stops: Vec<Stopped> = (0..4).map(|_| chip.run(60_000).unwrap()).collect();
+        let stop = |w: &str, pc| Stopped::Watchpoint {
+            watch: w.parse().unwrap(),
+            pc,
+        };
+        assert_eq!(
+            stops,
+            [
+                stop("V0", 0x200),
+                stop("I", 0x202),
+                stop("302", 0x204),
+                stop("DT", 0x206)
+            ]
+        );
+        assert_eq!(chip.watch_hit(), Some(Watch::Dt));
+        assert!(chip.remove_watchpoint(Watch::Ram {
+            start: 0x302,
+            end: 0x303
+        }));
+        assert_eq!(chip.watchpoints().len(), 3);
+        assert_eq!(
+            "300..310".parse(),
+            Ok(Watch::Ram {
+                start: 0x300,
+                end: 0x310
+            })
+        );
+        assert!("310..300".parse::<Watch>().is_err());
+    }
+
+    #[test]
+    fn hooks_trace_pause_and_modify() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        // LD V0, 1 ; ADD V0, 1 ; JP 0x202
+        let mut chip = chip_with(&[0x60, 0x01, 0x70, 0x01, 0x12, 0x02]);
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let t = trace.clone();
+        chip.set_hook(move |cpu, ram, op| {
+            t.borrow_mut().push((cpu.pc, *op));
+            // the hook sees the program in RAM
+            assert_eq!(ram.buf[0x200], 0x60);
+            match t.borrow().len() {
+                6 => HookAction::Pause,
+                _ => HookAction::Continue,
+            }
+        });
+        // a cheat keeping V0 at 2 at most
+        chip.set_post_hook(|cpu, _, _| {
+            if cpu.v[0] > 2 {
+                HookAction::Replace(CpuView { v: [2; 16], ..*cpu })
+            } else {
+                HookAction::Continue
+            }
+        });
+        assert_eq!(chip.run(60_000), Ok(Stopped::Paused(HEAD_OF_PROGRAM + 2)));
+        assert_eq!(chip.cpu.v[0], 2);
+        assert_eq!(trace.borrow().len(), 6);
+        assert_eq!(
+            trace.borrow()[0],
+            (0x200, opcode::Opcode::LdVxByte { x: 0, byte: 1 })
+        );
+        chip.step().unwrap();
+        assert!(!chip.hook_paused());
+        assert_eq!(chip.cpu.v[0], 2);
+        chip.clear_hooks();
+        chip.step().unwrap();
+        chip.step().unwrap();
+        assert_eq!(chip.cpu.v[0], 3);
+    }
+
+    #[test]
+    fn frame_hook_sees_every_tick() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+        // LD V0, 0 ; DRW V0, V0, 1 ; JP 0x204
+        let mut chip = chip_with(&[0x60, 0x00, 0xD0, 0x01, 0x12, 0x04]);
+        let lit = Rc::new(RefCell::new(Vec::new()));
+        let l = lit.clone();
+        chip.set_frame_hook(move |fb| l.borrow_mut().push(fb.pixel(0, 0)));
+        chip.tick_60hz();
+        chip.cycle().unwrap();
+        chip.cycle().unwrap();
+        chip.tick_60hz();
+        chip.cycle().unwrap();
+        chip.tick_60hz();
+        assert_eq!(*lit.borrow(), [0, 1, 1]);
+    }
+
+    #[test]
+    fn frame_step_grants_single_frames() {
+        let fs = FrameStep::new();
+        assert!(!fs.wait_frame());
+        fs.step();
+        assert!(fs.is_paused());
+        fs.step();
+        fs.step();
+        assert!(fs.wait_frame());
+        assert!(fs.wait_frame());
+        let waiter = fs.clone();
+        let t = thread::spawn(move || waiter.wait_frame());
+        fs.resume();
+        assert!(!t.join().unwrap());
+    }
+
+    #[test]
+    fn stopper_ends_run_from_another_thread() {
+        // JP 0x200
+        let mut chip = chip_with(&[0x12, 0x00]);
+        let stopper = chip.stopper();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            stopper.stop();
+        });
+        assert_eq!(chip.run(60_000), Ok(Stopped::Requested));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn run_ticks_once_per_frame() {
+        use alloc::rc::Rc;
+
+        // LD V0, 100 ; LD DT, V0 ; JP 0x204
+        let mut chip = chip_with(&[0x60, 0x64, 0xF0, 0x15, 0x12, 0x04]);
+        chip.speed().set_turbo(true);
+        let ticks = Rc::new(Cell::new(0));
+        let counted = ticks.clone();
+        chip.set_frame_hook(move |_| counted.set(counted.get() + 1));
+        let stopper = chip.stopper();
+        // 630 Hz: frames of 10 and 11 cycles, starting at cycles 0, 10, 21, 31 and 42
+        chip.run_with(630, |cycles, _| {
+            if cycles == 41 {
+                stopper.stop();
+            }
+        })
+        .unwrap();
+        assert_eq!((ticks.get(), chip.cpu.dt.get()), (4, 97));
+    }
+
+    #[test]
+    fn controller_drives_a_running_machine() {
+        // LD V0, K ; ADD V1, 1 ; JP 0x202
+        let mut chip = chip_with(&[0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02]);
+        let controller = chip.controller();
+        let t = thread::spawn(move || {
+            let snapshot = || controller.request_snapshot().recv().unwrap();
+            assert_eq!(snapshot().pc, 0x200);
+            controller.press(5);
+            controller.release(5);
+            while snapshot().v[0] != 5 {}
+            controller.pause();
+            assert!(controller.is_paused());
+            controller.resume();
+            // back to waiting for a key
+            controller.reset();
+            while snapshot().v != [0; 0x10] {}
+            assert_eq!(snapshot().pc, 0x200);
+            // LD V2, 7 ; JP 0x202
+            let reloaded = controller.reload(vec![0x62, 0x07, 0x12, 0x02]);
+            assert_eq!(reloaded.recv().unwrap().unwrap(), 4);
+            let state = snapshot();
+            assert_eq!((state.v[2], &state.ram[0x204..0x206]), (7, &[0, 0][..]));
+            controller.stop();
+        });
+        assert_eq!(chip.run(60_000), Ok(Stopped::Requested));
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn reload_replaces_the_program() {
+        // LD V0, 1 ; JP 0x202 ; then a shorter build: LD V0, 2
+        let mut chip = chip_with(&[0x60, 0x01, 0x12, 0x02]);
+        chip.run_headless(10).unwrap();
+        assert_eq!(chip.reload(&mut &[0x60, 0x02][..]).unwrap(), 2);
+        assert_eq!((chip.cpu.pc, chip.cpu.v[0]), (HEAD_OF_PROGRAM, 0));
+        assert_eq!(chip.ram.buf[0x202..0x204], [0, 0]);
+        chip.cycle().unwrap();
+        assert_eq!(chip.cpu.v[0], 2);
+        assert!(matches!(chip.reload(&mut &[][..]), Err(LoadError::Empty)));
+        assert_eq!((chip.cpu.pc, chip.ram.buf[0x201]), (HEAD_OF_PROGRAM + 2, 2));
+        // `reset` goes back to the reloaded program
+        chip.reset();
+        assert_eq!(chip.ram.buf[0x200..0x204], [0x60, 0x02, 0, 0]);
+    }
+
+    #[test]
+    fn reset_restores_the_loaded_rom() {
+        // LD V0, 5 ; LD I, 0x200 ; LD [I], V0 ; DRW V0, V0, 5 ; JP 0x208
+        let mut chip = chip_with(&[0x60, 0x05, 0xA2, 0x00, 0xF0, 0x55, 0xD0, 0x05, 0x12, 0x08]);
+        let stopper = chip.stopper();
+        let mut resets = 0;
+        chip.run_with(60_000, |cycles, chip| {
+            if cycles == 5 && resets == 0 {
+                assert_eq!(chip.ram.buf[0x200], 5);
+                assert!(chip.framebuffer().pixels().iter().any(|p| *p != 0));
+                stopper.reset();
+                resets += 1;
+            } else if cycles == 6 {
+                stopper.stop();
+            }
+        })
+        .unwrap();
+        // the reset ran before cycle 6, which executed the first instruction again
+        assert_eq!((chip.cpu.pc, chip.cpu.v[0], chip.cpu.i), (0x202, 5, 0));
+        assert_eq!(chip.ram.buf[0x200], 0x60);
+        assert!(chip.framebuffer().pixels().iter().all(|p| *p == 0));
+        chip.pause();
+        assert!(chip.is_paused());
+        chip.resume();
+        assert!(!chip.is_paused());
+    }
+
+    fn run_quirk(program: &[u8], quirks: &str, cycles: usize) -> Chip {
+        let mut chip = chip_with(program).with_quirks(quirks.parse().unwrap());
+        for _ in 0..cycles {
+            chip.cycle().unwrap();
+        }
+        chip
+    }
+
+    #[test]
+    fn quirks_from_str() {
+        let q: Quirks = "shift, jump".parse().unwrap();
+        assert!(q.shift_vy && q.jump_vx && !q.vf_reset);
+        assert_eq!("".parse::<Quirks>(), Ok(Quirks::default()));
+        assert!("nope".parse::<Quirks>().is_err());
+        assert_eq!(q.to_string().parse::<Quirks>(), Ok(q));
+    }
+
+    #[test]
+    #[allow(clippy::assertions_on_constants)]
+    fn vf_reset_quirk() {
+        // LD VF, 5 ; OR/AND/XOR V0, V1
+        for op in [0x11, 0x12, 0x13] {
+            let prog = [0x6F, 0x05, 0x80, op];
+            assert_eq!(run_quirk(&prog, "", 2).cpu.v[0xF], 5);
+            assert_eq!(run_quirk(&prog, "vf-reset", 2).cpu.v[0xF], 0);
+        }
+        // the VIP platforms enable it
+        assert!(Platform::DREAM6800.quirks.vf_reset);
+        assert!(!Platform::CHIP8.quirks.vf_reset);
+    }
+
+    #[test]
+    fn shift_quirk() {
+        // LD V0, 0x81 ; LD V1, 0x40 ; SHR V0, V1
+        let prog = [0x60, 0x81, 0x61, 0x40, 0x80, 0x16];
+        let c = run_quirk(&prog, "", 3);
+        assert_eq!((c.cpu.v[0], c.cpu.v[0xF]), (0x40, 1));
+        let c = run_quirk(&prog, "shift", 3);
+        assert_eq!((c.cpu.v[0], c.cpu.v[0xF]), (0x20, 0));
+        // LD V0, 0x81 ; LD V1, 0x40 ; SHL V0, V1
+        let prog = [0x60, 0x81, 0x61, 0x40, 0x80, 0x1E];
+        let c = run_quirk(&prog, "", 3);
+        assert_eq!((c.cpu.v[0], c.cpu.v[0xF]), (0x02, 1));
+        let c = run_quirk(&prog, "shift", 3);
+        assert_eq!((c.cpu.v[0], c.cpu.v[0xF]), (0x80, 0));
+        // LD VF, 0x81 ; SHL VF: the flag wins over the result
+        let c = run_quirk(&[0x6F, 0x81, 0x8F, 0xFE], "", 2);
+        assert_eq!(c.cpu.v[0xF], 1);
+    }
+
+    #[test]
+    fn index_overflow_quirk() {
+        // LD I, 0xFFE ; LD V0, 3 ; LD VF, 7 ; ADD I, V0 ; LD V0, 1 ; ADD I, V0
+        let prog = [
+            0xAF, 0xFE, 0x60, 0x03, 0x6F, 0x07, 0xF0, 0x1E, 0x60, 0x01, 0xF0, 0x1E,
+        ];
+        let c = run_quirk(&prog, "", 4);
+        assert_eq!((c.cpu.i, c.cpu.v[0xF]), (0x1001, 7));
+        let mut c = run_quirk(&prog, "index-overflow", 4);
+        assert_eq!((c.cpu.i, c.cpu.v[0xF]), (0x1001, 1));
+        // I wraps around at 16 bits instead of overflowing
+        c.cpu.i = 0xFFFF;
+        c.cycle().unwrap();
+        c.cycle().unwrap();
+        assert_eq!((c.cpu.i, c.cpu.v[0xF]), (0, 1));
+        c.cpu.i = 0x300;
+        c.cpu.pc = 0x20A;
+        c.cycle().unwrap();
+        assert_eq!((c.cpu.i, c.cpu.v[0xF]), (0x301, 0));
+    }
+
+    #[test]
+    fn row_collisions_quirk() {
+        // HIGH ; LD V0, 0 ; LD V1, 56 ; LD I, 0x20C ; DRW V0, V1, 0 ; DRW V0, V1, 0 ; 16x16 sprite
+        let mut prog = vec![
+            0x00, 0xFF, 0x60, 0x00, 0x61, 0x38, 0xA2, 0x0C, 0xD0, 0x10, 0xD0, 0x10,
+        ];
+        prog.extend([0xFF; 32]);
+        assert_eq!(run_quirk(&prog, "", 5).cpu.v[0xF], 0);
+        assert_eq!(run_quirk(&prog, "", 6).cpu.v[0xF], 1);
+        // 8 rows wrap around to the top
+        assert_eq!(run_quirk(&prog, "row-collisions", 6).cpu.v[0xF], 16);
+        // 8 rows are clipped, and count as well
+        let quirks = "row-collisions,clipping";
+        assert_eq!(run_quirk(&prog, quirks, 5).cpu.v[0xF], 8);
+        assert_eq!(run_quirk(&prog, quirks, 6).cpu.v[0xF], 16);
+        // not in the low-resolution mode
+        prog[..2].copy_from_slice(&[0x00, 0xE0]);
+        prog[9] = 0x1F;
+        prog[11] = 0x1F;
+        assert_eq!(run_quirk(&prog, quirks, 6).cpu.v[0xF], 1);
+    }
+
+    #[test]
+    fn jump_quirk() {
+        // LD V0, 2 ; LD V3, 4 ; JP V0, 0x300
+        let prog = [0x60, 0x02, 0x63, 0x04, 0xB3, 0x00];
+        assert_eq!(run_quirk(&prog, "", 3).cpu.pc, 0x302);
+        assert_eq!(run_quirk(&prog, "jump", 3).cpu.pc, 0x304);
+    }
+
+    #[test]
+    fn clipping_quirk() {
+        let mut chip = chip_with(&[]).with_quirks("clipping".parse().unwrap());
+        chip.ram.load_slice(0x300, &[0xFF, 0xFF, 0xFF]);
+        chip.cpu.i = 0x300;
+        chip.cpu.v[0] = 60;
+        chip.cpu.v[1] = 30;
+        let (x, y, sprite) = chip
+            .cpu
+            .sprite(&chip.ram, &chip.framebuffer, 0, 1, 3, 1, 0)
+            .unwrap();
+        assert_eq!((x, y, &*sprite), (60, 30, &[0xF0, 0xF0][..]));
+        chip.cpu.v[0] = 64 + 1;
+        let (x, _, sprite) = chip
+            .cpu
+            .sprite(&chip.ram, &chip.framebuffer, 0, 1, 1, 2, 0)
+            .unwrap();
+        assert_eq!((x, &*sprite), (1, &[0xFF, 0xFF][..]));
+        chip.set_quirks(Quirks::default());
+        chip.cpu.v[0] = 60;
+        let (_, _, sprite) = chip
+            .cpu
+            .sprite(&chip.ram, &chip.framebuffer, 0, 1, 3, 1, 0)
+            .unwrap();
+        assert_eq!(*sprite, [0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn sprites_wrap_or_clip_on_screen() {
+        // LD V0, 60 ; LD V1, 30 ; LD I, 0x20A ; DRW V0, V1, 3 ; JP 0x208 ; 3 rows of 8 pixels
+        let prog = [
+            0x60, 0x3C, 0x61, 0x1E, 0xA2, 0x0A, 0xD0, 0x13, 0x12, 0x08, 0xFF, 0xFF, 0xFF,
+        ];
+        let lit = |c: &Chip| {
+            let fb = c.framebuffer();
+            fb.pixels()
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| **p != 0)
+                .map(|(n, _)| (n % fb.width(), n / fb.width()))
+                .collect::<Vec<_>>()
+        };
+        // wrapping: the part past the right edge reappears on the left, the last row on top
+        let wrapped = lit(&run_quirk(&prog, "", 4));
+        assert_eq!(wrapped.len(), 24);
+        assert!(wrapped.contains(&(3, 0)) && wrapped.contains(&(63, 31)));
+        let clipped = lit(&run_quirk(&prog, "clipping", 4));
+        assert_eq!(clipped.len(), 8);
+        assert!(clipped.iter().all(|&(x, y)| x >= 60 && y >= 30));
+    }
+
+    #[test]
+    fn platform_moves_the_program_and_resizes_the_screen() {
+        let platform: Platform = "eti-660".parse().unwrap();
+        let mut chip =
+            Chip::new(Box::new(NullDisplay), Box::new(NullKeyboard)).with_platform(&platform);
+        // LD V0, 0x2F
+        assert_eq!(chip.load_slice(&[0x60, 0x2F]).unwrap(), 2);
+        assert_eq!(chip.ram.buf[0x600], 0x60);
+        chip.cycle().unwrap();
+        assert_eq!((chip.pc(), chip.cpu.v[0]), (0x602, 0x2F));
+        let fb = chip.framebuffer();
+        assert_eq!((fb.width(), fb.height()), (64, 48));
+        assert!(chip.quirks().vf_reset);
+        assert!("cosmac".parse::<Platform>().is_err());
+    }
+
+    #[test]
+    fn digits_come_from_the_platform_font() {
+        for (platform, font) in [
+            (Platform::CHIP8, &SPRITES),
+            (Platform::ETI660, &ETI660_SPRITES),
+            (Platform::DREAM6800, &DREAM6800_SPRITES),
+        ] {
+            let mut chip = Chip::headless().with_platform(&platform);
+            // LD V0, 4 ; LD F, V0
+            chip.load_slice(&[0x60, 0x04, 0xF0, 0x29]).unwrap();
+            chip.cycle().unwrap();
+            chip.cycle().unwrap();
+            let i = chip.cpu.i as usize;
+            assert_eq!(chip.ram.buf[i..i + 5], font[4], "{}", platform.name);
+        }
+        assert_ne!(ETI660_SPRITES[4], SPRITES[4]);
+        assert_ne!(DREAM6800_SPRITES[4], ETI660_SPRITES[4]);
+    }
+
+    #[test]
+    fn presets_bring_quirks_and_speed() {
+        let names: Vec<&str> = Platform::ALL.iter().map(|p| p.name).collect();
+        assert_eq!(names[..4], ["chip8", "vip", "schip", "xochip"]);
+        let vip: Platform = "vip".parse().unwrap();
+        assert_eq!(
+            vip.quirks,
+            "vf-reset,load-store,shift,clipping,display-wait"
+                .parse()
+                .unwrap()
+        );
+        let schip: Platform = "schip".parse().unwrap();
+        let mut chip = Chip::headless().with_platform(&schip);
+        assert_eq!(chip.quirks(), schip.quirks);
+        assert!(chip.quirks().jump_vx && chip.quirks().row_collisions);
+        assert_eq!(chip.cycles_per_frame(), 30);
+        // HIGH
+        chip.load_slice(&[0x00, 0xFF]).unwrap();
+        chip.cycle().unwrap();
+        assert_eq!(chip.framebuffer().width(), HIRES_WIDTH);
+        let xochip: Platform = "xochip".parse().unwrap();
+        assert!(!xochip.quirks.clipping && xochip.quirks.load_store_increments_i);
+        assert_eq!(xochip.cpu_hz / 60, 1000);
+    }
+
+    #[test]
+    fn palette_from_str() {
+        let p: Palette = "#102030, ffffff".parse().unwrap();
+        assert_eq!(p.0[0], Rgb(0x10, 0x20, 0x30));
+        assert_eq!(p.0[1], Rgb(0xff, 0xff, 0xff));
+        assert_eq!(p.0[2], Palette::default().0[2]);
+        assert_eq!(p.to_string().parse::<Palette>(), Ok(p));
+        assert!("#12345".parse::<Palette>().is_err());
+        assert!("#000000,#000000,#000000,#000000,#000000"
+            .parse::<Palette>()
+            .is_err());
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let s = Speed::new();
+        assert_eq!(s.percent(), 100);
+        assert_eq!(s.set_percent(10), Speed::MIN);
+        assert_eq!(s.set_percent(1000), Speed::MAX);
+        assert_eq!(s.clone().set_percent(150), 150);
+        assert_eq!(s.percent(), 150);
+        assert_eq!(s.cpu_hz(), None);
+        assert_eq!(s.set_cpu_hz(Some(10)), Some(Speed::MIN_HZ));
+        assert_eq!(s.clone().set_cpu_hz(Some(1200)), Some(1200));
+        assert_eq!(s.cpu_hz(), Some(1200));
+        assert_eq!(s.set_cpu_hz(None), None);
+    }
+
+    #[test]
+    fn run_reads_the_clock_at_every_frame() {
+        // LD V0, 60 ; LD DT, V0 ; ADD V1, 1 ; JP 0x204
+        let mut chip = chip_with(&[0x60, 0x3C, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+        let speed = chip.speed();
+        speed.set_cpu_hz(Some(120_000));
+        speed.set_turbo(true);
+        let stopper = chip.stopper();
+        let start = Instant::now();
+        chip.run_with(60, |cycles, _| {
+            if cycles == 20_002 {
+                stopper.stop();
+            }
+        })
+        .unwrap();
+        // 2000 instructions per frame instead of one at 60Hz, without sleeping
+        assert_eq!(chip.registers().dt, 50);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn speed_scales_the_instructions_per_frame() {
+        use alloc::rc::Rc;
+
+        // 600 Hz runs 10 cycles a frame at 100%
+        for (percent, per_frame) in [(50, 5), (200, 20)] {
+            // JP 0x200
+            let mut chip = chip_with(&[0x12, 0x00]);
+            chip.speed().set_percent(percent);
+            chip.speed().set_turbo(true);
+            let ticks = Rc::new(Cell::new(0));
+            let counted = ticks.clone();
+            chip.set_frame_hook(move |_| counted.set(counted.get() + 1));
+            let stopper = chip.stopper();
+            chip.run_with(600, |cycles, _| {
+                if cycles == 10 * per_frame - 1 {
+                    stopper.stop();
+                }
+            })
+            .unwrap();
+            assert_eq!(ticks.get(), 10, "{}%", percent);
+        }
+    }
+
+    #[test]
+    fn timers_stay_at_60hz_at_any_speed() {
+        for percent in [50, 200] {
+            // LD V0, 255 ; LD DT, V0 ; JP 0x204
+            let mut chip = chip_with(&[0x60, 0xFF, 0xF0, 0x15, 0x12, 0x04]);
+            chip.speed().set_percent(percent);
+            let stopper = chip.stopper();
+            let t = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(500));
+                stopper.stop();
+            });
+            chip.run(600).unwrap();
+            t.join().unwrap();
+            // 30 ticks in half a second, give or take the scheduler
+            let dropped = 255 - chip.registers().dt;
+            assert!((27..=33).contains(&dropped), "{}%: {}", percent, dropped);
+        }
+    }
+
+    #[test]
+    fn builder_defaults_to_a_headless_machine() {
+        let platform: Platform = "eti-660".parse().unwrap();
+        let mut chip = Chip::builder()
+            .platform(platform)
+            .quirks(Quirks::default())
+            .cpu_hz(1200)
+            .seed(7)
+            .start(0x300)
+            .build();
+        assert_eq!(chip.quirks(), Quirks::default());
+        assert_eq!(chip.cycles_per_frame(), 20);
+        assert_eq!(chip.speed().cpu_hz(), Some(1200));
+        assert_eq!(chip.registers().pc, 0x300);
+        assert_eq!(chip.framebuffer().height(), platform.height);
+        // RND V0, 0xFF
+        chip.load_slice(&[0xC0, 0xFF]).unwrap();
+        chip.cycle().unwrap();
+        let mut seeded = Chip::headless().with_seed(7);
+        seeded.load_slice(&[0xC0, 0xFF]).unwrap();
+        seeded.cycle().unwrap();
+        assert_eq!(chip.registers().v[0], seeded.registers().v[0]);
+        assert_eq!(Chip::builder().build().cycles_per_frame(), 10);
+    }
+
+    #[test]
+    fn core_matches_chip() {
+        struct Presents(u32);
+        impl Display for Presents {
+            fn present(&mut self, _: &FrameBuffer) {
+                self.0 += 1;
+            }
+        }
+        // LD V0, 0x3C ; LD DT, V0 ; LD F, V0 ; DRW V0, V0, 5 ; JP 0x208
+        let rom = [0x60, 0x3C, 0xF0, 0x15, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+        let mut core = Core::new(Presents(0), NullKeyboard).with_platform(&Platform::default());
+        assert!(core.load_slice(&[]).is_err());
+        assert_eq!(core.load_slice(&rom).unwrap(), rom.len());
+        let mut chip = chip_with(&rom);
+        for _ in 0..5 {
+            assert_eq!(core.step().unwrap(), chip.step().unwrap());
+        }
+        core.tick_60hz();
+        core.tick_60hz();
+        assert_eq!(core.display.0, 1);
+        assert_eq!(core.framebuffer(), chip.framebuffer());
+        assert_eq!(core.registers().dt, 0x3A);
+    }
+
+    #[test]
+    fn registers_by_name() {
+        let mut chip = chip_with(&[]);
+        let v3: Register = "V3".parse().unwrap();
+        chip.set_register(v3, 0x11f);
+        chip.set_register("i".parse().unwrap(), 0x300);
+        chip.set_register(Register::Sp, 40);
+        chip.set_register(Register::St, 5);
+        let r = chip.registers();
+        assert_eq!((r.v[3], r.i, r.sp, r.st), (0x1f, 0x300, 16, 5));
+        assert_eq!(chip.register(v3), 0x1f);
+        assert_eq!(v3.to_string(), "V3");
+        assert!("vg".parse::<Register>().is_err());
+        assert_eq!(Register::ALL[17], Register::Pc);
+    }
+
+    #[test]
+    fn loads_whole_roms_only() {
+        let mut chip = Chip::headless();
+        // two reads: `load` keeps reading until the end of the stream
+        let mut rom = std::io::Read::chain(&[0x60, 0x01][..], &[0x61, 0x02][..]);
+        assert_eq!(chip.load(&mut rom).unwrap(), 4);
+        assert_eq!(&chip.ram.buf[0x200..0x204], &[0x60, 0x01, 0x61, 0x02]);
+        assert!(matches!(chip.load(&mut &[][..]), Err(LoadError::Empty)));
+        assert!(matches!(
+            chip.load_slice(&[0; 0xE01]),
+            Err(LoadError::TooLarge {
+                size: 0xE01,
+                max: 0xE00
+            })
+        ));
+        assert_eq!(chip.load_slice(&[0; 0xE00]).unwrap(), 0xE00);
+    }
+
+    #[test]
+    fn random_programs_trap_instead_of_panicking() {
+        let mut x = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move || {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x
+        };
+        for _ in 0..200 {
+            let rom: Vec<u8> = (0..64).map(|_| random() as u8).collect();
+            let mut chip = chip_with(&rom);
+            let _ = chip.run_headless(2000);
+        }
+        let ram: Vec<u8> = (0..RAM_SIZE).map(|_| random() as u8).collect();
+        for _ in 0..10_000 {
+            let state = CpuView {
+                v: (random() as u128).to_le_bytes(),
+                i: random() as u16,
+                pc: random() as u16,
+                sp: random() as u8,
+                stack: [random() as u16; 16],
+                dt: random() as u8,
+                st: random() as u8,
+            };
+            let _ = fuzz_step(&ram, state);
+        }
+        // CALL 0x200 forever ; RET
+        let mut chip = chip_with(&[0x22, 0x00]);
+        assert_eq!(
+            chip.run_headless(100),
+            Err(Trap::StackOverflow { pc: 0x200 })
+        );
+        assert_eq!(chip.registers().sp, 16);
+        let mut chip = chip_with(&[0x00, 0xEE]);
+        assert_eq!(
+            chip.run_headless(100),
+            Err(Trap::StackUnderflow { pc: 0x200 })
+        );
+    }
+
+    #[test]
+    fn pc_out_of_range_traps() {
+        // JP 0x100
+        let mut chip = chip_with(&[0x11, 0x00]);
+        assert_eq!(chip.run_headless(10), Err(Trap::PcOutOfRange { pc: 0x100 }));
+        let mut chip = chip_with(&[]);
+        let pc = 0xFFF;
+        chip.set_registers(CpuView {
+            pc,
+            ..chip.registers()
+        });
+        assert_eq!(chip.step(), Err(Trap::PcOutOfRange { pc }));
+        assert_eq!(chip.registers().pc, pc);
+    }
+
+    #[test]
+    fn runs_headless_for_a_number_of_cycles() {
+        // LD V0, 60 ; LD DT, V0 ; ADD V1, 1 ; JP 0x204
+        let mut chip = chip_with(&[0x60, 0x3C, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+        assert_eq!(chip.run_headless(302), Ok(Stopped::CycleLimit));
+        // a tick every 10 cycles, the first before the first instruction
+        assert_eq!(chip.registers().dt, 30);
+        // LD F, V0 ; DRW V0, V0, 5 ; EXIT
+        let draw = [0xF0, 0x29, 0xD0, 0x05, 0x00, 0xFD];
+        let (mut a, mut b) = (chip_with(&draw), chip_with(&draw));
+        let blank = a.framebuffer().hash();
+        assert_eq!(a.run_headless(1000), Ok(Stopped::Halted));
+        assert_eq!(b.run_headless(1000), Ok(Stopped::Halted));
+        assert_ne!(a.framebuffer().hash(), blank);
+        assert_eq!(a.framebuffer().hash(), b.framebuffer().hash());
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn runs_until_a_stop_condition() {
+        // LD F, V0 ; DRW V0, V0, 5 ; ADD V1, 1 ; SE V1, 4 ; JP 0x202 ; JP 0x20A
+        let program = [
+            0xF0, 0x29, 0xD0, 0x05, 0x71, 0x01, 0x31, 0x04, 0x12, 0x02, 0x12, 0x0A,
+        ];
+        let mut chip = chip_with(&program);
+        assert_eq!(
+            chip.run_until(StopCondition::Pc(0x202)),
+            Ok(Stopped::Reached(0x202))
+        );
+        assert_eq!(
+            chip.run_until(StopCondition::Pc(0x202)),
+            Ok(Stopped::Reached(0x202))
+        );
+        // the first stop is right after LD F, the second after a turn of the loop
+        assert_eq!(chip.registers().v[1], 1);
+        // the last DRW is 4 instructions before the final loop
+        assert_eq!(
+            chip.run_until(StopCondition::DisplaySettled(20)),
+            Ok(Stopped::Settled)
+        );
+        assert_eq!(chip.registers().pc, 0x20A);
+        chip.add_breakpoint(0x20A);
+        assert_eq!(
+            chip.run_until(StopCondition::Breakpoint),
+            Ok(Stopped::Breakpoint(0x20A))
+        );
+        let deadline = Instant::now() + Duration::from_millis(20);
+        assert_eq!(
+            chip_with(&[0x12, 0x00]).run_until(StopCondition::Deadline(deadline)),
+            Ok(Stopped::Deadline)
+        );
+        assert!(Instant::now() >= deadline);
+    }
+
+    #[test]
+    fn run_until_with_sees_every_cycle() {
+        // ADD V0, 1 ; JP 0x200
+        let mut chip = chip_with(&[0x70, 0x01, 0x12, 0x00]);
+        let mut seen = Vec::new();
+        let stopped = chip.run_until_with(StopCondition::Cycles(4), |cycle, chip| {
+            seen.push((cycle, chip.registers().v[0]))
+        });
+        assert_eq!(stopped, Ok(Stopped::CycleLimit));
+        assert_eq!(seen, [(0, 0), (1, 1), (2, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn stops_on_a_jump_to_itself() {
+        // LD V0, 1 ; JP 0x204 ; JP 0x204
+        let program = [0x60, 0x01, 0x12, 0x04, 0x12, 0x04];
+        let mut chip = chip_with(&program);
+        assert_eq!(chip.run_headless(100), Ok(Stopped::CycleLimit));
+        assert!(chip.at_self_jump());
+        let mut chip = chip_with(&program);
+        chip.set_stop_on_self_jump(true);
+        assert_eq!(chip.run_headless(100), Ok(Stopped::SelfJump(0x204)));
+        assert_eq!(chip.run_headless(100), Ok(Stopped::SelfJump(0x204)));
+        assert_eq!(chip.registers().v[0], 1);
     }
 }
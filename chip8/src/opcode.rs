@@ -0,0 +1,283 @@
+// the instruction set as data: `decode` turns a word into an `Opcode` and `encode` back.
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 00CN, 00DN, 00FB, 00FC (SUPER-CHIP / XO-CHIP scrolling)
+    ScrollDown(u8),
+    ScrollUp(u8),
+    ScrollRight,
+    ScrollLeft,
+    // 00FD, 00FE, 00FF (SUPER-CHIP)
+    Exit,
+    Lores,
+    Hires,
+    // 0NNN
+    Sys(u16),
+    // 1NNN, 2NNN
+    JumpAddr(u16),
+    CallAddr(u16),
+    // 3XNN, 4XNN, 5XY0, 9XY0
+    SkipEqVxByte { x: u8, byte: u8 },
+    SkipNeVxByte { x: u8, byte: u8 },
+    SkipEqVxVy { x: u8, y: u8 },
+    SkipNeVxVy { x: u8, y: u8 },
+    // 5XY2, 5XY3 (XO-CHIP): store / load VX..=VY at I
+    SaveRange { x: u8, y: u8 },
+    LoadRange { x: u8, y: u8 },
+    // 6XNN, 7XNN
+    LdVxByte { x: u8, byte: u8 },
+    AddVxByte { x: u8, byte: u8 },
+    // 8XY0..8XYE
+    LdVxVy { x: u8, y: u8 },
+    OrVxVy { x: u8, y: u8 },
+    AndVxVy { x: u8, y: u8 },
+    XorVxVy { x: u8, y: u8 },
+    AddVxVy { x: u8, y: u8 },
+    SubVxVy { x: u8, y: u8 },
+    ShrVxVy { x: u8, y: u8 },
+    SubnVxVy { x: u8, y: u8 },
+    ShlVxVy { x: u8, y: u8 },
+    // ANNN, BNNN, CXNN
+    LdIAddr(u16),
+    JumpV0Addr(u16),
+    RndVxByte { x: u8, byte: u8 },
+    // DXYN; n = 0 draws a 16x16 sprite (SUPER-CHIP)
+    Drw { x: u8, y: u8, n: u8 },
+    // EX9E, EXA1
+    SkipKey { x: u8 },
+    SkipNotKey { x: u8 },
+    // F000 NNNN (XO-CHIP): the address is the next word
+    LdILong,
+    // FN01, F002 (XO-CHIP)
+    Plane(u8),
+    Audio,
+    // FX07, FX0A, FX15, FX18, FX1E
+    LdVxDt { x: u8 },
+    LdVxK { x: u8 },
+    LdDtVx { x: u8 },
+    LdStVx { x: u8 },
+    AddIVx { x: u8 },
+    // FX29, FX30 (SUPER-CHIP big digits), FX33, FX3A (XO-CHIP)
+    LdFVx { x: u8 },
+    LdHfVx { x: u8 },
+    LdBVx { x: u8 },
+    PitchVx { x: u8 },
+    // FX55, FX65
+    LdIVx { x: u8 },
+    LdVxI { x: u8 },
+    // FX75, FX85 (SUPER-CHIP RPL flags)
+    LdRVx { x: u8 },
+    LdVxR { x: u8 },
+}
+
+use Opcode::*;
+
+// returns the opcode of `word`, or None for words which are not instructions (e.g. data).
+pub fn decode(word: u16) -> Option<Opcode> {
+    let n = [
+        (word >> 12) as u8,
+        (word >> 8 & 0xf) as u8,
+        (word >> 4 & 0xf) as u8,
+        (word & 0xf) as u8,
+    ];
+    let (x, y, nnn, byte) = (n[1], n[2], word & 0xfff, word as u8);
+    Some(match n {
+        [0, 0, 0, 0] => return None,
+        [0, 0, 0xE, 0] => Cls,
+        [0, 0, 0xE, 0xE] => Ret,
+        [0, 0, 0xC, n] => ScrollDown(n),
+        [0, 0, 0xD, n] => ScrollUp(n),
+        [0, 0, 0xF, 0xB] => ScrollRight,
+        [0, 0, 0xF, 0xC] => ScrollLeft,
+        [0, 0, 0xF, 0xD] => Exit,
+        [0, 0, 0xF, 0xE] => Lores,
+        [0, 0, 0xF, 0xF] => Hires,
+        [0, ..] => Sys(nnn),
+        [1, ..] => JumpAddr(nnn),
+        [2, ..] => CallAddr(nnn),
+        [3, ..] => SkipEqVxByte { x, byte },
+        [4, ..] => SkipNeVxByte { x, byte },
+        [5, _, _, 0] => SkipEqVxVy { x, y },
+        [5, _, _, 2] => SaveRange { x, y },
+        [5, _, _, 3] => LoadRange { x, y },
+        [6, ..] => LdVxByte { x, byte },
+        [7, ..] => AddVxByte { x, byte },
+        [8, _, _, 0] => LdVxVy { x, y },
+        [8, _, _, 1] => OrVxVy { x, y },
+        [8, _, _, 2] => AndVxVy { x, y },
+        [8, _, _, 3] => XorVxVy { x, y },
+        [8, _, _, 4] => AddVxVy { x, y },
+        [8, _, _, 5] => SubVxVy { x, y },
+        [8, _, _, 6] => ShrVxVy { x, y },
+        [8, _, _, 7] => SubnVxVy { x, y },
+        [8, _, _, 0xE] => ShlVxVy { x, y },
+        [9, _, _, 0] => SkipNeVxVy { x, y },
+        [0xA, ..] => LdIAddr(nnn),
+        [0xB, ..] => JumpV0Addr(nnn),
+        [0xC, ..] => RndVxByte { x, byte },
+        [0xD, _, _, n] => Drw { x, y, n },
+        [0xE, _, 9, 0xE] => SkipKey { x },
+        [0xE, _, 0xA, 1] => SkipNotKey { x },
+        [0xF, 0, 0, 0] => LdILong,
+        [0xF, _, 0, 1] => Plane(x),
+        [0xF, 0, 0, 2] => Audio,
+        [0xF, _, 0, 7] => LdVxDt { x },
+        [0xF, _, 0, 0xA] => LdVxK { x },
+        [0xF, _, 1, 5] => LdDtVx { x },
+        [0xF, _, 1, 8] => LdStVx { x },
+        [0xF, _, 1, 0xE] => AddIVx { x },
+        [0xF, _, 2, 9] => LdFVx { x },
+        [0xF, _, 3, 0] => LdHfVx { x },
+        [0xF, _, 3, 3] => LdBVx { x },
+        [0xF, _, 3, 0xA] => PitchVx { x },
+        [0xF, _, 5, 5] => LdIVx { x },
+        [0xF, _, 6, 5] => LdVxI { x },
+        [0xF, _, 7, 5] => LdRVx { x },
+        [0xF, _, 8, 5] => LdVxR { x },
+        _ => return None,
+    })
+}
+
+impl Opcode {
+    // the word of the opcode; register numbers and immediates are masked to their fields.
+    pub fn encode(self) -> u16 {
+        let xy = |base: u16, x: u8, y: u8| base | (x as u16 & 0xf) << 8 | (y as u16 & 0xf) << 4;
+        let xb = |base: u16, x: u8, byte: u8| base | (x as u16 & 0xf) << 8 | byte as u16;
+        let fx = |low: u16, x: u8| 0xF000 | (x as u16 & 0xf) << 8 | low;
+        match self {
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xf),
+            ScrollUp(n) => 0x00D0 | (n as u16 & 0xf),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Sys(a) => a & 0xfff,
+            JumpAddr(a) => 0x1000 | (a & 0xfff),
+            CallAddr(a) => 0x2000 | (a & 0xfff),
+            SkipEqVxByte { x, byte } => xb(0x3000, x, byte),
+            SkipNeVxByte { x, byte } => xb(0x4000, x, byte),
+            SkipEqVxVy { x, y } => xy(0x5000, x, y),
+            SaveRange { x, y } => xy(0x5002, x, y),
+            LoadRange { x, y } => xy(0x5003, x, y),
+            LdVxByte { x, byte } => xb(0x6000, x, byte),
+            AddVxByte { x, byte } => xb(0x7000, x, byte),
+            LdVxVy { x, y } => xy(0x8000, x, y),
+            OrVxVy { x, y } => xy(0x8001, x, y),
+            AndVxVy { x, y } => xy(0x8002, x, y),
+            XorVxVy { x, y } => xy(0x8003, x, y),
+            AddVxVy { x, y } => xy(0x8004, x, y),
+            SubVxVy { x, y } => xy(0x8005, x, y),
+            ShrVxVy { x, y } => xy(0x8006, x, y),
+            SubnVxVy { x, y } => xy(0x8007, x, y),
+            ShlVxVy { x, y } => xy(0x800E, x, y),
+            SkipNeVxVy { x, y } => xy(0x9000, x, y),
+            LdIAddr(a) => 0xA000 | (a & 0xfff),
+            JumpV0Addr(a) => 0xB000 | (a & 0xfff),
+            RndVxByte { x, byte } => xb(0xC000, x, byte),
+            Drw { x, y, n } => xy(0xD000, x, y) | (n as u16 & 0xf),
+            SkipKey { x } => xb(0xE09E, x, 0),
+            SkipNotKey { x } => xb(0xE0A1, x, 0),
+            LdILong => 0xF000,
+            Plane(n) => fx(0x01, n),
+            Audio => 0xF002,
+            LdVxDt { x } => fx(0x07, x),
+            LdVxK { x } => fx(0x0A, x),
+            LdDtVx { x } => fx(0x15, x),
+            LdStVx { x } => fx(0x18, x),
+            AddIVx { x } => fx(0x1E, x),
+            LdFVx { x } => fx(0x29, x),
+            LdHfVx { x } => fx(0x30, x),
+            LdBVx { x } => fx(0x33, x),
+            PitchVx { x } => fx(0x3A, x),
+            LdIVx { x } => fx(0x55, x),
+            LdVxI { x } => fx(0x65, x),
+            LdRVx { x } => fx(0x75, x),
+            LdVxR { x } => fx(0x85, x),
+        }
+    }
+}
+
+// formats the mnemonic as printed by `disasm` and read by `asm`.
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Sys(a) => write!(f, "SYS 0x{:03X}", a),
+            JumpAddr(a) => write!(f, "JP 0x{:03X}", a),
+            CallAddr(a) => write!(f, "CALL 0x{:03X}", a),
+            SkipEqVxByte { x, byte } => write!(f, "SE V{:X}, 0x{:02X}", x, byte),
+            SkipNeVxByte { x, byte } => write!(f, "SNE V{:X}, 0x{:02X}", x, byte),
+            SkipEqVxVy { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            SkipNeVxVy { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            LdVxByte { x, byte } => write!(f, "LD V{:X}, 0x{:02X}", x, byte),
+            AddVxByte { x, byte } => write!(f, "ADD V{:X}, 0x{:02X}", x, byte),
+            LdVxVy { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            OrVxVy { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            AndVxVy { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            XorVxVy { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddVxVy { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            SubVxVy { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShrVxVy { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubnVxVy { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShlVxVy { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            LdIAddr(a) => write!(f, "LD I, 0x{:03X}", a),
+            JumpV0Addr(a) => write!(f, "JP V0, 0x{:03X}", a),
+            RndVxByte { x, byte } => write!(f, "RND V{:X}, 0x{:02X}", x, byte),
+            Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey { x } => write!(f, "SKP V{:X}", x),
+            SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            LdILong => write!(f, "LD I, long"),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            LdVxK { x } => write!(f, "LD V{:X}, K", x),
+            LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            AddIVx { x } => write!(f, "ADD I, V{:X}", x),
+            LdFVx { x } => write!(f, "LD F, V{:X}", x),
+            LdHfVx { x } => write!(f, "LD HF, V{:X}", x),
+            LdBVx { x } => write!(f, "LD B, V{:X}", x),
+            PitchVx { x } => write!(f, "PITCH V{:X}", x),
+            LdIVx { x } => write!(f, "LD [I], V{:X}", x),
+            LdVxI { x } => write!(f, "LD V{:X}, [I]", x),
+            LdRVx { x } => write!(f, "LD R, V{:X}", x),
+            LdVxR { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_and_encode_round_trip() {
+        assert_eq!(decode(0x7A12), Some(AddVxByte { x: 0xA, byte: 0x12 }));
+        assert_eq!(decode(0xD345), Some(Drw { x: 3, y: 4, n: 5 }));
+        assert_eq!(decode(0x8128), None);
+        assert_eq!(decode(0x0000), None);
+        for word in 0..=0xFFFF {
+            if let Some(op) = decode(word) {
+                assert_eq!(op.encode(), word, "{:?}", op);
+            }
+        }
+    }
+}
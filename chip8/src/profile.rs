@@ -0,0 +1,118 @@
+// execution counts per opcode type and per address, collected by `Chip::step` after
+// `Chip::set_profiling(true)`: the hot loops of a ROM, and the code it never reached.
+use crate::{disasm, usage};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+// addresses listed by the report, hottest first.
+const HOT_ADDRESSES: usize = 16;
+
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    total: u64,
+    // pattern (see `usage::classify`) -> executions
+    opcodes: BTreeMap<&'static str, u64>,
+    // address -> (the word executed there last, executions)
+    addresses: BTreeMap<u16, (u16, u64)>,
+}
+
+impl Profile {
+    pub fn record(&mut self, addr: u16, op: u16) {
+        self.total += 1;
+        if let Some((_, p)) = usage::classify(op) {
+            *self.opcodes.entry(p).or_default() += 1;
+        }
+        let e = self.addresses.entry(addr).or_default();
+        *e = (op, e.1 + 1);
+    }
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+    // executions of the opcode type `pattern`, e.g. "DXYN".
+    pub fn opcode(&self, pattern: &str) -> u64 {
+        self.opcodes.get(pattern).copied().unwrap_or_default()
+    }
+    pub fn at(&self, addr: u16) -> u64 {
+        self.addresses.get(&addr).map_or(0, |e| e.1)
+    }
+    // the opcode types by executions, most executed first.
+    pub fn opcodes(&self) -> Vec<(&'static str, u64)> {
+        let mut ops: Vec<_> = self.opcodes.iter().map(|(p, n)| (*p, *n)).collect();
+        ops.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        ops
+    }
+    // the executed addresses by executions, most executed first.
+    pub fn hot_addresses(&self) -> Vec<(u16, u64)> {
+        let mut addrs: Vec<_> = self.addresses.iter().map(|(a, e)| (*a, e.1)).collect();
+        addrs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addrs
+    }
+    // the aligned addresses of `start..end` which were never executed (the operand of an
+    // executed `F000 NNNN` counts as executed); data shows up here too.
+    pub fn unexecuted(&self, start: u16, end: u16) -> Vec<u16> {
+        let long = |a: u16| {
+            a.checked_sub(2)
+                .and_then(|a| self.addresses.get(&a))
+                .is_some_and(|e| e.0 == 0xF000)
+        };
+        (start..end)
+            .step_by(2)
+            .filter(|a| !self.addresses.contains_key(a) && !long(*a))
+            .collect()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let share = |n: u64| n as f64 * 100.0 / self.total.max(1) as f64;
+        writeln!(f, "instructions: {}", self.total)?;
+        writeln!(f, "opcodes:")?;
+        for (p, n) in self.opcodes() {
+            writeln!(f, "  {:<6}{:>12} {:>5.1}%", p, n, share(n))?;
+        }
+        write!(f, "hot addresses:")?;
+        for (addr, n) in self.hot_addresses().into_iter().take(HOT_ADDRESSES) {
+            let op = self.addresses[&addr].0;
+            let text = disasm::mnemonic(op);
+            write!(
+                f,
+                "\n  {:03X}  {:<18}{:>12} {:>5.1}%",
+                addr,
+                text,
+                n,
+                share(n)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn counts_opcodes_and_addresses() {
+        let mut p = Profile::default();
+        p.record(0x200, 0x6001);
+        p.record(0x20A, 0xF000);
+        for _ in 0..3 {
+            p.record(0x202, 0xD015);
+            p.record(0x204, 0x1202);
+        }
+        assert_eq!(p.total(), 8);
+        assert_eq!(
+            (p.opcode("DXYN"), p.opcode("6XNN"), p.opcode("00E0")),
+            (3, 1, 0)
+        );
+        assert_eq!(p.opcodes()[0], ("1NNN", 3));
+        assert_eq!(p.hot_addresses()[..3], [(0x202, 3), (0x204, 3), (0x200, 1)]);
+        assert_eq!((p.at(0x204), p.at(0x206)), (3, 0));
+        assert_eq!(p.unexecuted(0x200, 0x210), [0x206, 0x208, 0x20E]);
+        let report = p.to_string();
+        assert!(report.starts_with("instructions: 8\n"), "{}", report);
+        assert!(report.contains("  202  "), "{}", report);
+    }
+}
@@ -0,0 +1,199 @@
+// known ROMs by the SHA-1 of their image, with the platform, quirks and clock they play best
+// at. The database is text, a ROM per line (`#` starts a comment):
+//
+//     <sha1> <platform> <quirks, comma separated, or -> <cpu hz> <title>
+//
+// `RomDb::builtin` holds the entries of `romdb.txt`; files in the same format add more.
+use crate::{Platform, Quirks};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+pub type Sha1 = [u8; 20];
+
+// the settings recommended for a known ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub title: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub cpu_hz: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDb {
+    entries: BTreeMap<Sha1, Entry>,
+}
+
+impl RomDb {
+    pub fn builtin() -> Self {
+        RomDb::parse(include_str!("romdb.txt")).expect("romdb.txt")
+    }
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut db = RomDb::default();
+        db.extend(text)?;
+        Ok(db)
+    }
+    // adds the entries of `text`, replacing those with the same hash.
+    pub fn extend(&mut self, text: &str) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Ok((hash, entry)) => self.entries.insert(hash, entry),
+                Err(e) => return Err(format!("line {}: {}", n + 1, e)),
+            };
+        }
+        Ok(())
+    }
+    pub fn lookup(&self, rom: &[u8]) -> Option<&Entry> {
+        self.entries.get(&sha1(rom))
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Sha1, Entry), String> {
+    // the fields are separated by blanks, except in the title, which takes the rest
+    let mut rest = line;
+    let mut field = |name: &str| {
+        let s = rest.trim_start();
+        let end = s.find(char::is_whitespace).unwrap_or(s.len());
+        rest = &s[end..];
+        match &s[..end] {
+            "" => Err(format!("missing {}", name)),
+            f => Ok(f),
+        }
+    };
+    let hash = parse_sha1(field("sha1")?)?;
+    let platform = field("platform")?.parse()?;
+    let quirks = match field("quirks")? {
+        "-" => Quirks::default(),
+        q => q.parse()?,
+    };
+    let hz = field("cpu hz")?;
+    let cpu_hz = hz.parse().map_err(|_| format!("bad cpu hz `{}`", hz))?;
+    let title = match rest.trim() {
+        "" => return Err("missing title".to_string()),
+        t => t.to_string(),
+    };
+    let entry = Entry {
+        title,
+        platform,
+        quirks,
+        cpu_hz,
+    };
+    Ok((hash, entry))
+}
+
+fn parse_sha1(s: &str) -> Result<Sha1, String> {
+    let bad = || format!("bad sha1 `{}`", s);
+    if s.len() != 40 {
+        return Err(bad());
+    }
+    let mut hash = [0; 20];
+    for (i, b) in hash.iter_mut().enumerate() {
+        let digits = s.get(i * 2..i * 2 + 2).ok_or_else(bad)?;
+        *b = u8::from_str_radix(digits, 16).map_err(|_| bad())?;
+    }
+    Ok(hash)
+}
+
+// the hash as 40 lowercase hex digits, as in the database.
+pub fn hex(hash: &Sha1) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// SHA-1 (FIPS 180-4); ROMs are small, so the padded message is built in one piece.
+pub fn sha1(data: &[u8]) -> Sha1 {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut msg: Vec<u8> = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in msg.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (w, c) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *w = u32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = (a.rotate_left(5))
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, t);
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+    let mut hash = [0; 20];
+    for (out, h) in hash.chunks_exact_mut(4).zip(h) {
+        out.copy_from_slice(&h.to_be_bytes());
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_matches_known_digests() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // 56 bytes: the length no longer fits in the first block
+        let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(
+            hex(&sha1(two_blocks)),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn looks_up_roms_by_hash() {
+        let rom = [0x12, 0x00];
+        assert!(RomDb::builtin().lookup(&rom).is_none());
+        let line = format!(
+            "{}  eti-660 shift,jump\t1000 Spin  Again # a comment",
+            hex(&sha1(&rom))
+        );
+        let db = RomDb::parse(&format!("# known ROMs\n\n{}\n", line)).unwrap();
+        let entry = db.lookup(&rom).unwrap();
+        assert_eq!(entry.title, "Spin  Again");
+        assert_eq!((entry.platform.name, entry.cpu_hz), ("eti-660", 1000));
+        assert_eq!(entry.quirks, "shift,jump".parse().unwrap());
+        assert!(db.lookup(&[0x12, 0x02]).is_none());
+        let err =
+            RomDb::parse("\nda39a3ee5e6b4b0d3255bfef95601890afd80709 chip8 - fast X").unwrap_err();
+        assert_eq!(err, "line 2: bad cpu hz `fast`");
+        assert!(RomDb::parse("da39 chip8 - 500 X").is_err());
+        assert_eq!(
+            RomDb::parse("da39a3ee5e6b4b0d3255bfef95601890afd80709 chip8 - 500 ").unwrap_err(),
+            "line 1: missing title"
+        );
+    }
+}
@@ -0,0 +1,118 @@
+// synthetic ROMs stressing one part of an interpreter, for benchmarks and for measuring how
+// fast a frontend draws on given hardware. The programs loop forever.
+use super::asm;
+use super::PROGRAM_START;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Workload {
+    // DXYN over the whole screen, cycling through the hex digits
+    Draw,
+    // conditional skips, calls and returns
+    Branch,
+    // FX33 and FX65 on a counter
+    Bcd,
+}
+
+impl Workload {
+    pub const ALL: [Workload; 3] = [Workload::Draw, Workload::Branch, Workload::Bcd];
+    pub fn name(self) -> &'static str {
+        match self {
+            Workload::Draw => "draw",
+            Workload::Branch => "branch",
+            Workload::Bcd => "bcd",
+        }
+    }
+    // the assembly of the program (see `asm`).
+    pub fn source(self) -> &'static str {
+        match self {
+            Workload::Draw => {
+                "
+                row:    LD F, V2
+                        DRW V0, V1, 5
+                        ADD V0, 5
+                        SE V0, 60
+                        JP row
+                        LD V0, 0
+                        ADD V1, 6
+                        SE V1, 30
+                        JP row
+                        LD V1, 0
+                        ADD V2, 1
+                        SNE V2, 16
+                        LD V2, 0
+                        JP row
+                "
+            }
+            Workload::Branch => {
+                "
+                loop:   ADD V0, 1
+                        SNE V0, 0
+                        ADD V1, 1
+                        SE V1, V0
+                        CALL wrap
+                        SKP V2
+                        JP loop
+                        JP loop
+                wrap:   SNE V1, 16
+                        LD V1, 0
+                        RET
+                "
+            }
+            Workload::Bcd => {
+                "
+                loop:   LD I, 0x800
+                        ADD V5, 3
+                        LD B, V5
+                        LD V2, [I]
+                        JP loop
+                "
+            }
+        }
+    }
+    pub fn rom(self) -> Vec<u8> {
+        asm::assemble(self.source(), PROGRAM_START as u16)
+            .unwrap_or_else(|e| panic!("{} workload: {}", self.name(), e))
+    }
+}
+
+impl core::str::FromStr for Workload {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Workload::ALL
+            .iter()
+            .find(|w| w.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown workload `{}` (one of draw, branch, bcd)", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{usage, Chip};
+
+    #[test]
+    fn workloads_run_forever() {
+        for w in Workload::ALL {
+            let mut chip = Chip::headless();
+            chip.load_slice(&w.rom()).unwrap();
+            for _ in 0..10_000 {
+                chip.cycle().unwrap();
+            }
+            let end = PROGRAM_START as u16 + w.rom().len() as u16;
+            assert!((PROGRAM_START as u16..end).contains(&chip.pc()));
+        }
+    }
+
+    #[test]
+    fn workloads_assemble_to_chip8_programs() {
+        for w in Workload::ALL {
+            let rom = w.rom();
+            assert_eq!(usage::Report::scan(&rom).required(), usage::Level::Chip8);
+            assert_eq!(w.name().parse::<Workload>(), Ok(w));
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// a harness for the well-known test ROMs (the Timendus suite and corax89's opcode test): runs a
+// ROM headless until it settles on a jump to itself, as they all do once the results are drawn,
+// and compares the screen with an expected one written as text (`#` for lit pixels, `.` for dark
+// ones, a line per row). The ROMs are not distributed with the crate; the tests look for them in
+// the directory named by `CHIP8_TEST_ROMS`, next to `<file stem>.txt` screens taken on a
+// reference interpreter.
+use super::{Chip, FrameBuffer, LoadError, Platform, Stopped, Trap};
+use alloc::format;
+use alloc::string::String;
+
+// the address the Timendus ROMs read to skip their menu
+const MENU_CHOICE: usize = 0x1FF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestRom {
+    pub file: &'static str,
+    // the menu entry stored at 0x1FF (1 for CHIP-8 in the quirks test)
+    pub menu_choice: Option<u8>,
+    // enough cycles to draw every result on the emulated platform
+    pub max_cycles: u64,
+}
+
+// the non-interactive ROMs of the Timendus suite and corax89's test.
+pub const SUITE: [TestRom; 6] = [
+    TestRom {
+        file: "1-chip8-logo.ch8",
+        menu_choice: None,
+        max_cycles: 10_000,
+    },
+    TestRom {
+        file: "2-ibm-logo.ch8",
+        menu_choice: None,
+        max_cycles: 10_000,
+    },
+    TestRom {
+        file: "3-corax+.ch8",
+        menu_choice: None,
+        max_cycles: 100_000,
+    },
+    TestRom {
+        file: "4-flags.ch8",
+        menu_choice: None,
+        max_cycles: 100_000,
+    },
+    TestRom {
+        file: "5-quirks.ch8",
+        menu_choice: Some(1),
+        max_cycles: 1_000_000,
+    },
+    TestRom {
+        file: "test_opcode.ch8",
+        menu_choice: None,
+        max_cycles: 100_000,
+    },
+];
+
+// why a test ROM did not run to the end.
+#[derive(Debug)]
+pub enum Failure {
+    Load(LoadError),
+    Trap(Trap),
+}
+
+impl core::fmt::Display for Failure {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Failure::Load(e) => e.fmt(f),
+            Failure::Trap(t) => t.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    // the ROM reached its final loop (or 00FD) within `max_cycles`
+    pub completed: bool,
+    pub cycles: u64,
+    // `screen_text` of the final screen
+    pub screen: String,
+}
+
+impl TestRom {
+    // runs `rom` on a headless machine of `platform`.
+    pub fn run(&self, rom: &[u8], platform: &Platform) -> Result<Report, Failure> {
+        let mut chip = Chip::builder().platform(*platform).seed(0).build();
+        chip.set_stop_on_self_jump(true);
+        chip.load_slice(rom).map_err(Failure::Load)?;
+        if let Some(choice) = self.menu_choice {
+            chip.ram.buf[MENU_CHOICE] = choice;
+        }
+        let frame = u64::from(chip.cycles_per_frame());
+        let mut cycles = 0;
+        let completed = loop {
+            if cycles >= self.max_cycles {
+                break false;
+            }
+            match chip.run_headless(frame).map_err(Failure::Trap)? {
+                Stopped::CycleLimit => cycles += frame,
+                _ => break true,
+            }
+        };
+        Ok(Report {
+            completed,
+            cycles,
+            screen: screen_text(chip.framebuffer()),
+        })
+    }
+    // runs `rom` and compares its final screen with `expected` (see `screen_text`).
+    pub fn check(&self, rom: &[u8], platform: &Platform, expected: &str) -> Result<(), String> {
+        let report = self
+            .run(rom, platform)
+            .map_err(|e| format!("{}: {}", self.file, e))?;
+        if !report.completed {
+            return Err(format!(
+                "{}: not done after {} cycles",
+                self.file, report.cycles
+            ));
+        }
+        if report.screen.trim() != expected.trim() {
+            return Err(format!(
+                "{}: the screen differs, got\n{}",
+                self.file, report.screen
+            ));
+        }
+        Ok(())
+    }
+}
+
+// the screen as text: `#` for pixels of any plane, `.` for the background, a line per row.
+pub fn screen_text(frame: &FrameBuffer) -> String {
+    let mut text = String::with_capacity((frame.width() + 1) * frame.height());
+    for row in frame.pixels().chunks(frame.width()) {
+        text.extend(row.iter().map(|&p| if p == 0 { '.' } else { '#' }));
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn runs_until_the_final_loop() {
+        // LD F, V0 ; DRW V0, V0, 5 ; JP 0x204
+        let rom = [0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04];
+        let test = TestRom {
+            file: "zero.ch8",
+            menu_choice: None,
+            max_cycles: 1000,
+        };
+        let report = test.run(&rom, &Platform::default()).unwrap();
+        assert!(report.completed);
+        let rows: Vec<&str> = report.screen.lines().collect();
+        assert_eq!(rows.len(), 32);
+        assert_eq!((&rows[0][..6], &rows[1][..6]), ("####..", "#..#.."));
+        assert!(test
+            .check(&rom, &Platform::default(), &report.screen)
+            .is_ok());
+        assert!(test.check(&rom, &Platform::default(), "#").is_err());
+        // a ROM which never settles
+        let rom = [0x70, 0x01, 0x12, 0x00];
+        let report = test.run(&rom, &Platform::default()).unwrap();
+        assert_eq!((report.completed, report.cycles), (false, 1000));
+        // a ROM which does not fit is not run at all
+        let rom = [0; 0xE01];
+        let run = test.run(&rom, &Platform::default());
+        assert!(matches!(
+            run,
+            Err(Failure::Load(LoadError::TooLarge { .. }))
+        ));
+        assert!(test.check(&rom, &Platform::default(), "").is_err());
+    }
+
+    // runs the suite found in `CHIP8_TEST_ROMS` against the `.txt` screens next to the ROMs;
+    // ROMs without a screen only have to finish without a trap.
+    #[cfg(feature = "std")]
+    #[test]
+    #[ignore = "needs the test ROMs in the directory named by CHIP8_TEST_ROMS"]
+    fn suite_matches_reference_screens() {
+        let dir = std::env::var("CHIP8_TEST_ROMS").expect("CHIP8_TEST_ROMS is not set");
+        let dir = std::path::Path::new(&dir);
+        let platform = Platform::default();
+        let mut found = 0;
+        for test in SUITE {
+            let Ok(rom) = std::fs::read(dir.join(test.file)) else {
+                continue;
+            };
+            found += 1;
+            match std::fs::read_to_string(dir.join(test.file).with_extension("txt")) {
+                Ok(expected) => test.check(&rom, &platform, &expected).unwrap(),
+                Err(_) => assert!(
+                    test.run(&rom, &platform).unwrap().completed,
+                    "{}",
+                    test.file
+                ),
+            }
+        }
+        assert!(found > 0, "no test ROM in {}", dir.display());
+    }
+}
@@ -0,0 +1,218 @@
+// instruction-set usage of a ROM: which opcodes it contains (static) and executes (dynamic),
+// and which instruction-set level they require.
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Level::Chip8 => "CHIP-8",
+            Level::SuperChip => "SUPER-CHIP",
+            Level::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+// returns the instruction-set level introducing `op` and its mnemonic pattern, or None for data.
+pub fn classify(op: u16) -> Option<(Level, &'static str)> {
+    let n = [op >> 12, op >> 8 & 0xf, op >> 4 & 0xf, op & 0xf];
+    let level = match n {
+        [0, 0, 0xE, 0] => (Level::Chip8, "00E0"),
+        [0, 0, 0xE, 0xE] => (Level::Chip8, "00EE"),
+        [0, 0, 0xC, _] => (Level::SuperChip, "00CN"),
+        [0, 0, 0xD, _] => (Level::XoChip, "00DN"),
+        [0, 0, 0xF, 0xB] => (Level::SuperChip, "00FB"),
+        [0, 0, 0xF, 0xC] => (Level::SuperChip, "00FC"),
+        [0, 0, 0xF, 0xD] => (Level::SuperChip, "00FD"),
+        [0, 0, 0xF, 0xE] => (Level::SuperChip, "00FE"),
+        [0, 0, 0xF, 0xF] => (Level::SuperChip, "00FF"),
+        [0, 0, 0, 0] => return None,
+        [0, ..] => (Level::Chip8, "0NNN"),
+        [1, ..] => (Level::Chip8, "1NNN"),
+        [2, ..] => (Level::Chip8, "2NNN"),
+        [3, ..] => (Level::Chip8, "3XNN"),
+        [4, ..] => (Level::Chip8, "4XNN"),
+        [5, _, _, 0] => (Level::Chip8, "5XY0"),
+        [5, _, _, 2] => (Level::XoChip, "5XY2"),
+        [5, _, _, 3] => (Level::XoChip, "5XY3"),
+        [6, ..] => (Level::Chip8, "6XNN"),
+        [7, ..] => (Level::Chip8, "7XNN"),
+        [8, _, _, 0] => (Level::Chip8, "8XY0"),
+        [8, _, _, 1] => (Level::Chip8, "8XY1"),
+        [8, _, _, 2] => (Level::Chip8, "8XY2"),
+        [8, _, _, 3] => (Level::Chip8, "8XY3"),
+        [8, _, _, 4] => (Level::Chip8, "8XY4"),
+        [8, _, _, 5] => (Level::Chip8, "8XY5"),
+        [8, _, _, 6] => (Level::Chip8, "8XY6"),
+        [8, _, _, 7] => (Level::Chip8, "8XY7"),
+        [8, _, _, 0xE] => (Level::Chip8, "8XYE"),
+        [9, _, _, 0] => (Level::Chip8, "9XY0"),
+        [0xA, ..] => (Level::Chip8, "ANNN"),
+        [0xB, ..] => (Level::Chip8, "BNNN"),
+        [0xC, ..] => (Level::Chip8, "CXNN"),
+        [0xD, _, _, 0] => (Level::SuperChip, "DXY0"),
+        [0xD, ..] => (Level::Chip8, "DXYN"),
+        [0xE, _, 9, 0xE] => (Level::Chip8, "EX9E"),
+        [0xE, _, 0xA, 1] => (Level::Chip8, "EXA1"),
+        [0xF, 0, 0, 0] => (Level::XoChip, "F000"),
+        [0xF, _, 0, 1] => (Level::XoChip, "FN01"),
+        [0xF, 0, 0, 2] => (Level::XoChip, "F002"),
+        [0xF, _, 0, 7] => (Level::Chip8, "FX07"),
+        [0xF, _, 0, 0xA] => (Level::Chip8, "FX0A"),
+        [0xF, _, 1, 5] => (Level::Chip8, "FX15"),
+        [0xF, _, 1, 8] => (Level::Chip8, "FX18"),
+        [0xF, _, 1, 0xE] => (Level::Chip8, "FX1E"),
+        [0xF, _, 2, 9] => (Level::Chip8, "FX29"),
+        [0xF, _, 3, 0] => (Level::SuperChip, "FX30"),
+        [0xF, _, 3, 3] => (Level::Chip8, "FX33"),
+        [0xF, _, 3, 0xA] => (Level::XoChip, "FX3A"),
+        [0xF, _, 5, 5] => (Level::Chip8, "FX55"),
+        [0xF, _, 6, 5] => (Level::Chip8, "FX65"),
+        [0xF, _, 7, 5] => (Level::SuperChip, "FX75"),
+        [0xF, _, 8, 5] => (Level::SuperChip, "FX85"),
+        _ => return None,
+    };
+    Some(level)
+}
+
+// the level which a ROM loaded at `origin` requires, from the opcodes reachable from its first
+// instruction: jumps, calls and skips are followed, so that sprites and other data are not taken
+// for opcodes as by `Report::scan`. The targets of `BNNN` are unknown and not followed.
+pub fn detect(rom: &[u8], origin: u16) -> Level {
+    let word = |addr: u16| {
+        let i = usize::from(addr.checked_sub(origin)?);
+        let w = rom.get(i..i + 2)?;
+        Some(u16::from_be_bytes([w[0], w[1]]))
+    };
+    // `F000 NNNN` is the only 4-byte instruction
+    let after = |addr: u16| addr + if word(addr) == Some(0xF000) { 4 } else { 2 };
+    let mut seen = BTreeSet::new();
+    let mut todo = vec![origin];
+    let mut level = Level::Chip8;
+    while let Some(addr) = todo.pop() {
+        if addr >= 0x1000 || !seen.insert(addr) {
+            continue;
+        }
+        // a word which is no opcode ends the path
+        let Some((op, (l, pattern))) = word(addr).and_then(|op| Some((op, classify(op)?))) else {
+            continue;
+        };
+        level = level.max(l);
+        let (next, target) = (after(addr), op & 0xFFF);
+        match pattern {
+            "00EE" | "00FD" | "BNNN" => {}
+            "1NNN" => todo.push(target),
+            "2NNN" => todo.extend([target, next]),
+            "3XNN" | "4XNN" | "5XY0" | "9XY0" | "EX9E" | "EXA1" => todo.extend([next, after(next)]),
+            _ => todo.push(next),
+        }
+    }
+    level
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    // pattern -> (level, occurrences in the ROM image)
+    pub static_usage: BTreeMap<&'static str, (Level, usize)>,
+    // pattern -> (level, executions)
+    pub dynamic_usage: BTreeMap<&'static str, (Level, usize)>,
+}
+
+impl Report {
+    // counts every aligned word of the ROM image; data words may show up as opcodes.
+    pub fn scan(rom: &[u8]) -> Self {
+        let mut r = Report::default();
+        for w in rom.chunks_exact(2) {
+            if let Some((level, p)) = classify(u16::from_be_bytes([w[0], w[1]])) {
+                r.static_usage.entry(p).or_insert((level, 0)).1 += 1;
+            }
+        }
+        r
+    }
+    pub fn record(&mut self, op: u16) {
+        if let Some((level, p)) = classify(op) {
+            self.dynamic_usage.entry(p).or_insert((level, 0)).1 += 1;
+        }
+    }
+    // the level required by executed opcodes, or by the ROM image when nothing was executed.
+    pub fn required(&self) -> Level {
+        let usage = if self.dynamic_usage.is_empty() {
+            &self.static_usage
+        } else {
+            &self.dynamic_usage
+        };
+        usage
+            .values()
+            .map(|(l, _)| *l)
+            .max()
+            .unwrap_or(Level::Chip8)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for level in [Level::Chip8, Level::SuperChip, Level::XoChip] {
+            let ops: Vec<String> = self
+                .static_usage
+                .iter()
+                .filter(|(_, (l, _))| *l == level)
+                .map(|(p, (_, n))| match self.dynamic_usage.get(p) {
+                    Some((_, e)) => format!("{}(x{}, executed x{})", p, n, e),
+                    None => format!("{}(x{})", p, n),
+                })
+                .collect();
+            writeln!(f, "{:<10}: {}", level.to_string(), ops.join(" "))?;
+        }
+        write!(f, "requires  : {}", self.required())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        assert_eq!(classify(0x00E0), Some((Level::Chip8, "00E0")));
+        assert_eq!(classify(0x00FF), Some((Level::SuperChip, "00FF")));
+        assert_eq!(classify(0xD120), Some((Level::SuperChip, "DXY0")));
+        assert_eq!(classify(0xF000), Some((Level::XoChip, "F000")));
+        assert_eq!(classify(0x0000), None);
+        assert_eq!(classify(0x8128), None);
+    }
+
+    #[test]
+    fn detects_the_level_of_reachable_code() {
+        // CLS ; JP 0x206 ; then a sprite looking like 00FF, skipped by the jump
+        let rom = [0x00, 0xE0, 0x12, 0x06, 0x00, 0xFF, 0x12, 0x06];
+        assert_eq!(Report::scan(&rom).required(), Level::SuperChip);
+        assert_eq!(detect(&rom, 0x200), Level::Chip8);
+        // SE V0, 0 ; LD I, long 0x00FF ; 00FF after the skip over both
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x00, 0xFF, 0x00, 0xFF];
+        assert_eq!(detect(&rom, 0x200), Level::XoChip);
+        // CALL 0x206 ; JP 0x202 ; HIGH (never reached) ; RET, then the same calling HIGH
+        let rom = [0x22, 0x06, 0x12, 0x02, 0x00, 0xFF, 0x00, 0xEE];
+        assert_eq!(detect(&rom, 0x200), Level::Chip8);
+        let rom = [0x22, 0x04, 0x12, 0x02, 0x00, 0xFF, 0x00, 0xEE];
+        assert_eq!(detect(&rom, 0x200), Level::SuperChip);
+    }
+
+    #[test]
+    fn executed_opcodes_decide_the_level() {
+        let mut r = Report::scan(&[0x00, 0xE0, 0x00, 0xFF]);
+        assert_eq!(r.required(), Level::SuperChip);
+        r.record(0x00E0);
+        assert_eq!(r.required(), Level::Chip8);
+    }
+}
@@ -0,0 +1,118 @@
+// browser bindings (`wasm` feature): the page calls `run_frame` from `requestAnimationFrame`,
+// draws `framebuffer` on a canvas and forwards key events to `key_down`/`key_up`.
+use super::{Chip, Keypad, NullDisplay};
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct WasmChip {
+    chip: Chip,
+    keypad: Keypad,
+}
+
+#[wasm_bindgen]
+impl WasmChip {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmChip {
+        let keypad = Keypad::new();
+        // the page reads the framebuffer itself.
+        let chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        WasmChip { chip, keypad }
+    }
+    // copies the ROM to the program start; returns the number of bytes loaded, or throws for an
+    // empty ROM or one which does not fit in RAM.
+    pub fn load(&mut self, rom: &[u8]) -> Result<usize, JsValue> {
+        self.chip
+            .load_slice(rom)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+    // the name of one of `Platform::ALL`; call it before `load`.
+    pub fn set_platform(&mut self, name: &str) -> Result<(), JsValue> {
+        let platform = name.parse().map_err(|e: String| JsValue::from_str(&e))?;
+        self.chip.set_platform(&platform);
+        Ok(())
+    }
+    // comma separated quirk names as accepted by `--quirks`.
+    pub fn set_quirks(&mut self, quirks: &str) -> Result<(), JsValue> {
+        let quirks = quirks.parse().map_err(|e: String| JsValue::from_str(&e))?;
+        self.chip.set_quirks(quirks);
+        Ok(())
+    }
+    // there is no entropy source without `rand`; seed from e.g. `Date.now()`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.chip.set_seed(seed);
+    }
+    // executes one instruction; a trap is thrown as its message.
+    pub fn step(&mut self) -> Result<(), JsValue> {
+        self.chip
+            .step()
+            .map(|_| ())
+            .map_err(|trap| JsValue::from_str(&trap.to_string()))
+    }
+    // executes `cycles` instructions and counts the timers down once: one 60Hz frame.
+    pub fn run_frame(&mut self, cycles: u32) -> Result<(), JsValue> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+        self.chip.tick_60hz();
+        Ok(())
+    }
+    // back to the power-on state with the ROM still loaded.
+    pub fn reset(&mut self) {
+        self.chip.reset();
+    }
+    // one palette index per pixel, row by row (`width` x `height`).
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip.framebuffer().pixels().to_vec()
+    }
+    pub fn width(&self) -> usize {
+        self.chip.framebuffer().width()
+    }
+    pub fn height(&self) -> usize {
+        self.chip.framebuffer().height()
+    }
+    pub fn key_down(&mut self, key: u8) {
+        self.keypad.press(key);
+    }
+    pub fn key_up(&mut self, key: u8) {
+        self.keypad.release(key);
+    }
+    // the sound timer is running: the page should beep.
+    pub fn beeping(&self) -> bool {
+        self.chip.registers().st > 0
+    }
+    pub fn pc(&self) -> u16 {
+        self.chip.pc()
+    }
+}
+
+impl Default for WasmChip {
+    fn default() -> Self {
+        WasmChip::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_reach_the_machine() {
+        let mut chip = WasmChip::new();
+        // LD V0, K ; SKP V0 ; JP 0x202
+        chip.load(&[0xF0, 0x0A, 0xE0, 0x9E, 0x12, 0x02]).unwrap();
+        chip.run_frame(10).unwrap();
+        assert_eq!(chip.pc(), 0x200);
+        chip.key_down(7);
+        chip.run_frame(2).unwrap();
+        assert_eq!(chip.pc(), 0x200);
+        chip.key_up(7);
+        chip.run_frame(1).unwrap();
+        chip.key_down(7);
+        chip.run_frame(1).unwrap();
+        assert_eq!(chip.pc(), 0x206);
+        assert_eq!(chip.framebuffer().len(), chip.width() * chip.height());
+    }
+}
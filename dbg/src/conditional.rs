@@ -0,0 +1,106 @@
+// conditional breakpoints (`break ADDR if COND`, `--break 'ADDR if COND'`): the machine's hook
+// tests them before every instruction, so `step` pauses on them in every frontend. Continuing
+// executes the instruction the machine paused before.
+use crate::expr::Condition;
+use chip8::{Chip, HookAction};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Debug, Default)]
+struct State {
+    // number, address and condition of each breakpoint
+    set: Vec<(usize, u16, Condition)>,
+    // the breakpoint the machine paused on, let through until PC moves on
+    hit: Option<(usize, u16, Condition)>,
+}
+
+// a handle on the breakpoints tested by the hook of `install`.
+#[derive(Debug, Clone, Default)]
+pub struct Conditional(Rc<RefCell<State>>);
+
+impl Conditional {
+    // makes `chip` pause before an instruction with a breakpoint whose condition holds.
+    pub fn install(chip: &mut Chip) -> Self {
+        let c = Conditional::default();
+        let state = c.0.clone();
+        chip.set_hook(move |cpu, ram, _| {
+            let mut s = state.borrow_mut();
+            if s.hit.is_some_and(|(_, a, _)| a == cpu.pc) {
+                return HookAction::Continue;
+            }
+            s.hit = s
+                .set
+                .iter()
+                .find(|(_, a, c)| *a == cpu.pc && c.holds(cpu, ram))
+                .copied();
+            match s.hit {
+                Some(_) => HookAction::Pause,
+                None => HookAction::Continue,
+            }
+        });
+        c
+    }
+
+    pub fn add(&self, n: usize, addr: u16, condition: Condition) {
+        self.0.borrow_mut().set.push((n, addr, condition));
+    }
+
+    pub fn remove(&self, n: usize) {
+        self.0.borrow_mut().set.retain(|b| b.0 != n);
+    }
+
+    // the breakpoints in the order they were added.
+    pub fn list(&self) -> Vec<(usize, u16, Condition)> {
+        self.0.borrow().set.clone()
+    }
+
+    // the number and condition of the breakpoint which paused the last `step`.
+    pub fn hit(&self, chip: &Chip) -> Option<(usize, Condition)> {
+        let hit = self.0.borrow().hit;
+        hit.filter(|_| chip.hook_paused()).map(|(n, _, c)| (n, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_when_the_condition_holds() {
+        // loop: ADD V0, 1 ; JP 0x200
+        let mut chip = Chip::headless();
+        chip.load_slice(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let breaks = Conditional::install(&mut chip);
+        let c = Condition::parse(&["v0", "==", "3"]).unwrap();
+        breaks.add(1, 0x202, c);
+        let mut steps = 0;
+        while breaks.hit(&chip).is_none() {
+            chip.step().unwrap();
+            steps += 1;
+        }
+        // three ADDs and two JPs, then the pause before the third JP
+        assert_eq!(steps, 6);
+        assert_eq!((chip.pc(), chip.registers().v[0]), (0x202, 3));
+        assert_eq!(breaks.hit(&chip), Some((1, c)));
+        // continuing executes the JP, and V0 never is 3 again
+        for _ in 0..100 {
+            chip.step().unwrap();
+            assert!(breaks.hit(&chip).is_none());
+        }
+        breaks.remove(1);
+        assert!(breaks.list().is_empty());
+    }
+
+    #[test]
+    fn conditions_see_ram() {
+        // LD I, 0x300 ; LD [I], V0 ; ADD V0, 1 ; JP 0x202
+        let mut chip = Chip::headless();
+        chip.load_slice(&[0xA3, 0x00, 0xF0, 0x55, 0x70, 0x01, 0x12, 0x02])
+            .unwrap();
+        let breaks = Conditional::install(&mut chip);
+        breaks.add(4, 0x204, Condition::parse(&["[300]", "==", "5"]).unwrap());
+        assert_eq!(chip.run_headless(10_000), Ok(chip8::Stopped::Paused(0x204)));
+        assert_eq!(breaks.hit(&chip).map(|h| h.0), Some(4));
+        assert_eq!(chip.ram.buf[0x300], 5);
+    }
+}
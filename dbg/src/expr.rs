@@ -0,0 +1,174 @@
+// conditions on the machine state, as in `assert v0 == 5` or `assert [300] != 0`: a register
+// or a RAM byte (`[ADDR]`), a comparison and a hex value; and breakpoints which have one, as in
+// `break 230 if v4 == 10`.
+use crate::parse_addr;
+use chip8::{CpuView, Ram, Register};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Ram(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub operand: Operand,
+    pub cmp: &'static str,
+    pub value: u16,
+}
+
+const COMPARISONS: [&str; 6] = ["==", "!=", "<=", ">=", "<", ">"];
+
+// a breakpoint: `ADDR` or `ADDR if COND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Break {
+    pub addr: u16,
+    pub condition: Option<Condition>,
+}
+
+impl Operand {
+    pub fn get(&self, cpu: &CpuView, ram: &Ram) -> u16 {
+        match self {
+            Operand::Register(r) => cpu.get(*r),
+            Operand::Ram(a) => ram.buf.get(*a as usize).copied().unwrap_or(0).into(),
+        }
+    }
+}
+
+impl Condition {
+    // parses the words after the command, e.g. ["v0", "==", "5"].
+    pub fn parse(words: &[&str]) -> Result<Self, String> {
+        let (operand, cmp, value) = match words {
+            [o, c, v] => (*o, *c, *v),
+            _ => return Err("expected `OPERAND CMP VALUE`, e.g. `v0 == 5`".to_string()),
+        };
+        let operand = match operand.strip_prefix('[').and_then(|a| a.strip_suffix(']')) {
+            Some(a) => {
+                let addr = parse_addr(a).map_err(|_| format!("invalid address: {}", a))?;
+                Operand::Ram(addr)
+            }
+            None => Operand::Register(operand.parse()?),
+        };
+        let cmp = COMPARISONS.iter().find(|c| **c == cmp).ok_or_else(|| {
+            format!(
+                "unknown comparison `{}` (one of {})",
+                cmp,
+                COMPARISONS.join(" ")
+            )
+        })?;
+        let value = parse_addr(value).map_err(|_| format!("invalid value: {}", value))?;
+        Ok(Condition {
+            operand,
+            cmp,
+            value,
+        })
+    }
+
+    pub fn holds(&self, cpu: &CpuView, ram: &Ram) -> bool {
+        let v = self.operand.get(cpu, ram);
+        match self.cmp {
+            "==" => v == self.value,
+            "!=" => v != self.value,
+            "<=" => v <= self.value,
+            ">=" => v >= self.value,
+            "<" => v < self.value,
+            _ => v > self.value,
+        }
+    }
+}
+
+impl FromStr for Break {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let (addr, condition) = match words[..] {
+            [a] => (a, None),
+            [a, "if", ref c @ ..] => (a, Some(Condition::parse(c)?)),
+            _ => return Err("expected `ADDR [if COND]`".to_string()),
+        };
+        let addr = parse_addr(addr).map_err(|_| format!("invalid address: {}", addr))?;
+        Ok(Break { addr, condition })
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{}", r),
+            Operand::Ram(a) => write!(f, "[{:03x}]", a),
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {:x}", self.operand, self.cmp, self.value)
+    }
+}
+
+impl fmt::Display for Break {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03x}", self.addr)?;
+        match &self.condition {
+            Some(c) => write!(f, " if {}", c),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::Chip;
+
+    #[test]
+    fn parses_conditions() {
+        let c = Condition::parse(&["v4", "==", "0x10"]).unwrap();
+        assert_eq!(c.operand, Operand::Register(Register::V(4)));
+        assert_eq!((c.cmp, c.value), ("==", 0x10));
+        assert_eq!(c.to_string(), "V4 == 10");
+        let c = Condition::parse(&["[300]", ">=", "2"]).unwrap();
+        assert_eq!((c.operand, c.cmp), (Operand::Ram(0x300), ">="));
+        assert_eq!(c.to_string(), "[300] >= 2");
+        assert!(Condition::parse(&["v0", "==",]).is_err());
+        assert!(Condition::parse(&["vg", "==", "1"]).is_err());
+        assert!(Condition::parse(&["v0", "=", "1"]).is_err());
+        assert!(Condition::parse(&["[xyz]", "==", "1"]).is_err());
+        assert!(Condition::parse(&["v0", "==", "1g"]).is_err());
+    }
+
+    #[test]
+    fn evaluates_registers_and_ram() {
+        let mut chip = Chip::headless();
+        chip.set_register(Register::V(4), 0x10);
+        chip.set_register(Register::I, 0x300);
+        chip.ram.buf[0x300] = 7;
+        let holds = |words: &[&str]| {
+            let c = Condition::parse(words).unwrap();
+            c.holds(&chip.registers(), &chip.ram)
+        };
+        assert!(holds(&["v4", "==", "10"]));
+        assert!(!holds(&["v4", "!=", "10"]));
+        assert!(holds(&["v4", "<", "11"]) && !holds(&["v4", ">", "10"]));
+        assert!(holds(&["i", "<=", "300"]) && holds(&["i", ">=", "300"]));
+        assert!(holds(&["[300]", "==", "7"]));
+        // RAM past the end reads as 0
+        assert!(holds(&["[ffff]", "==", "0"]));
+    }
+
+    #[test]
+    fn parses_breakpoints() {
+        let b: Break = "230".parse().unwrap();
+        assert_eq!((b.addr, b.condition), (0x230, None));
+        let b: Break = "0x230 if v4 == 10".parse().unwrap();
+        assert_eq!(b.addr, 0x230);
+        assert_eq!(b.to_string(), "230 if V4 == 10");
+        assert!("230 v4 == 10".parse::<Break>().is_err());
+        assert!("230 if v4".parse::<Break>().is_err());
+        assert!("zz".parse::<Break>().is_err());
+        assert!("".parse::<Break>().is_err());
+    }
+}
@@ -0,0 +1,420 @@
+// GDB remote serial protocol server (`dbg --gdb 127.0.0.1:1234`), one client at a time.
+//
+// Registers, in `g`/`p` order: V0-VF (8 bits), I and PC (16 bits, big-endian), SP, DT, ST
+// (8 bits). Supported packets: ? g G p P m M c s Z0/z0 (breakpoints) Z2/z2 (write watchpoints)
+// qSupported qAttached H D k, and ^C while running. The conditional breakpoints of `--break`
+// stop `c` and `s` like breakpoints.
+use chip8::{Chip, Register, Watch};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const REGISTERS: usize = Register::ALL.len();
+// instructions executed between checks for ^C while continuing
+const BURST: u64 = 1000;
+
+// hex digits of a register in packets.
+fn width(r: Register) -> usize {
+    match r {
+        Register::I | Register::Pc => 4,
+        _ => 2,
+    }
+}
+
+// the modulo 256 sum of a packet's data.
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |s, b| s.wrapping_add(*b))
+}
+
+pub fn serve(chip: &mut Chip, addr: &str, cycles_per_frame: u64) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!("waiting for a gdb client on {}", listener.local_addr()?);
+    let (stream, peer) = listener.accept()?;
+    println!("gdb client {} attached", peer);
+    Session {
+        chip,
+        reader: BufReader::new(stream.try_clone()?),
+        stream,
+        cycles_per_frame: cycles_per_frame.max(1),
+        cycle: 0,
+    }
+    .run()
+}
+
+struct Session<'a> {
+    chip: &'a mut Chip,
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+    cycles_per_frame: u64,
+    cycle: u64,
+}
+
+impl Session<'_> {
+    fn run(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.receive()? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                _ => self.handle(&packet),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, packet: &str) -> String {
+        // an empty packet, or one starting with a character the protocol does not use
+        let Some((cmd, rest)) = packet.split_at_checked(1) else {
+            return String::new();
+        };
+        match cmd {
+            "?" => "S05".to_string(),
+            "g" => (0..REGISTERS).map(|n| self.register(n)).collect(),
+            "G" => self.write_registers(rest),
+            "p" => usize::from_str_radix(rest, 16)
+                .ok()
+                .filter(|n| *n < REGISTERS)
+                .map_or_else(|| "E01".to_string(), |n| self.register(n)),
+            "P" => self.write_register(rest),
+            "m" => self.read_memory(rest),
+            "M" => self.write_memory(rest),
+            "s" => self.resume(true),
+            "c" => self.resume(false),
+            "Z" | "z" => self.watch(cmd == "Z", rest),
+            "H" => "OK".to_string(),
+            "q" if rest.starts_with("Supported") => "PacketSize=1000;swbreak+".to_string(),
+            "q" if rest == "Attached" => "1".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn register(&self, n: usize) -> String {
+        let r = Register::ALL[n];
+        format!("{:01$x}", self.chip.register(r), width(r))
+    }
+
+    fn write_registers(&mut self, hex: &str) -> String {
+        let mut r = self.chip.registers();
+        let mut rest = hex;
+        for reg in Register::ALL {
+            let width = width(reg);
+            match rest
+                .get(..width)
+                .and_then(|h| u16::from_str_radix(h, 16).ok())
+            {
+                Some(value) => r.set(reg, value),
+                None => return "E01".to_string(),
+            }
+            rest = &rest[width..];
+        }
+        self.chip.set_registers(r);
+        "OK".to_string()
+    }
+
+    fn write_register(&mut self, arg: &str) -> String {
+        let parsed = arg.split_once('=').and_then(|(n, v)| {
+            let n = usize::from_str_radix(n, 16)
+                .ok()
+                .filter(|n| *n < REGISTERS)?;
+            Some((n, u16::from_str_radix(v, 16).ok()?))
+        });
+        match parsed {
+            Some((n, value)) => {
+                self.chip.set_register(Register::ALL[n], value);
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    // `addr,len` within RAM
+    fn range(&self, arg: &str) -> Option<(usize, usize)> {
+        let (addr, len) = arg.split_once(',')?;
+        let addr = usize::from_str_radix(addr, 16).ok()?;
+        let len = usize::from_str_radix(len, 16).ok()?;
+        (addr.checked_add(len)? <= self.chip.ram.buf.len()).then_some((addr, len))
+    }
+
+    fn read_memory(&self, arg: &str) -> String {
+        match self.range(arg) {
+            Some((addr, len)) => self.chip.ram.buf[addr..addr + len]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, arg: &str) -> String {
+        let parsed = arg.split_once(':').and_then(|(range, data)| {
+            let (addr, len) = self.range(range)?;
+            let bytes = (0..len)
+                .map(|n| u8::from_str_radix(data.get(2 * n..2 * n + 2)?, 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Some((addr, bytes))
+        });
+        match parsed {
+            Some((addr, bytes)) => {
+                self.chip.ram.buf[addr..addr + bytes.len()].copy_from_slice(&bytes);
+                "OK".to_string()
+            }
+            None => "E01".to_string(),
+        }
+    }
+
+    // Z0/z0 (software breakpoint) and Z2/z2 (write watchpoint): `type,addr,kind`
+    fn watch(&mut self, insert: bool, arg: &str) -> String {
+        let fields: Vec<&str> = arg.split(',').collect();
+        let (kind, addr, end) = match fields.as_slice() {
+            [kind, addr, len] => match (
+                u16::from_str_radix(addr, 16),
+                usize::from_str_radix(len, 16),
+            ) {
+                (Ok(addr), Ok(len)) => match (addr as usize).checked_add(len.max(1)) {
+                    Some(end) => (*kind, addr, end),
+                    None => return "E01".to_string(),
+                },
+                _ => return "E01".to_string(),
+            },
+            _ => return "E01".to_string(),
+        };
+        let watch = Watch::Ram {
+            start: addr as usize,
+            end,
+        };
+        match (kind, insert) {
+            ("0", true) => self.chip.add_breakpoint(addr),
+            ("0", false) => {
+                self.chip.remove_breakpoint(addr);
+            }
+            ("2", true) => self.chip.add_watchpoint(watch),
+            ("2", false) => {
+                self.chip.remove_watchpoint(watch);
+            }
+            _ => return String::new(),
+        }
+        "OK".to_string()
+    }
+
+    // executes one instruction, or continues until a breakpoint, a watchpoint, EXIT (reported
+    // as exited), a trap or ^C.
+    fn resume(&mut self, single: bool) -> String {
+        let mut first = true;
+        loop {
+            for _ in 0..BURST {
+                if !first && self.chip.at_breakpoint() {
+                    return "T05swbreak:;".to_string();
+                }
+                first = false;
+                if let Err(trap) = self.chip.step() {
+                    println!("trap: {}", trap);
+                    return "S04".to_string();
+                }
+                // the hook of a conditional breakpoint left the instruction unexecuted
+                if self.chip.hook_paused() {
+                    return "T05swbreak:;".to_string();
+                }
+                self.cycle += 1;
+                if self.cycle.is_multiple_of(self.cycles_per_frame) {
+                    self.chip.tick_60hz();
+                }
+                if let Some(Watch::Ram { start, .. }) = self.chip.watch_hit() {
+                    return format!("T05watch:{:x};", start);
+                }
+                if self.chip.halted() {
+                    return "W00".to_string();
+                }
+                if single {
+                    return "S05".to_string();
+                }
+            }
+            if self.interrupted() {
+                return "S02".to_string();
+            }
+        }
+    }
+
+    fn interrupted(&mut self) -> bool {
+        // the ^C may already have been read into the buffer along with an earlier packet
+        if !self.reader.buffer().is_empty() {
+            let got = self.reader.buffer()[0] == 0x03;
+            if got {
+                self.reader.consume(1);
+            }
+            return got;
+        }
+        let mut byte = [0];
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let got = matches!(self.stream.peek(&mut byte), Ok(1) if byte[0] == 0x03);
+        if got {
+            let _ = self.reader.read_exact(&mut byte);
+        }
+        let _ = self.stream.set_nonblocking(false);
+        got
+    }
+
+    // reads the next `$data#checksum` packet and acknowledges it; None when the client left.
+    // (a packet with a bad checksum is refused with `-` and the client sends it again)
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        let mut byte = [0];
+        loop {
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            loop {
+                self.reader.read_exact(&mut byte)?;
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+            let expected = u8::from_str_radix(&String::from_utf8_lossy(&checksum), 16).ok();
+            if expected != Some(checksum_of(&data)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        write!(
+            self.stream,
+            "${}#{:02x}",
+            data,
+            checksum_of(data.as_bytes())
+        )?;
+        self.stream.flush()?;
+        // the acknowledgement
+        let mut ack = [0];
+        self.reader.read_exact(&mut ack)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conditional::Conditional;
+    use crate::expr::Condition;
+
+    // a session on a loopback connection, and the client's end of it
+    fn session(chip: &mut Chip) -> (Session<'_>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let session = Session {
+            chip,
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            stream,
+            cycles_per_frame: 10,
+            cycle: 0,
+        };
+        (session, client)
+    }
+
+    fn read_n(client: &mut TcpStream, n: usize) -> String {
+        let mut buf = vec![0; n];
+        client.read_exact(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn checksums_are_the_sum_modulo_256() {
+        assert_eq!(checksum_of(b""), 0);
+        assert_eq!(checksum_of(b"OK"), 0x9a);
+        assert_eq!(checksum_of(&[0xff, 0x02]), 0x01);
+    }
+
+    #[test]
+    fn refuses_bad_checksums_until_a_good_packet() {
+        let mut chip = Chip::headless();
+        let (mut s, mut client) = session(&mut chip);
+        client.write_all(b"$?#00$?#3f").unwrap();
+        assert_eq!(s.receive().unwrap().as_deref(), Some("?"));
+        assert_eq!(read_n(&mut client, 2), "-+");
+        client.write_all(b"+").unwrap();
+        s.send("OK").unwrap();
+        assert_eq!(read_n(&mut client, 6), "$OK#9a");
+        drop(client);
+        assert_eq!(s.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn ignores_empty_and_unknown_packets() {
+        let mut chip = Chip::headless();
+        let (mut s, _client) = session(&mut chip);
+        assert_eq!(s.handle(""), "");
+        assert_eq!(s.handle("\u{fffd}"), "");
+        assert_eq!(s.handle("?"), "S05");
+    }
+
+    #[test]
+    fn reads_and_writes_memory() {
+        let mut chip = Chip::headless();
+        let (mut s, _client) = session(&mut chip);
+        assert_eq!(s.handle("M300,2:abcd"), "OK");
+        assert_eq!(s.handle("m2ff,3"), "00abcd");
+        assert_eq!(s.handle("mfff,2"), "E01");
+        assert_eq!(s.handle("m1,ffffffffffffffff"), "E01");
+        assert_eq!(s.handle("Mffffffffffffffff,2:abcd"), "E01");
+        assert_eq!(s.handle("M300,2:ab"), "E01");
+    }
+
+    #[test]
+    fn sets_breakpoints_and_watchpoints() {
+        let mut chip = Chip::headless();
+        // LD V0, 1 ; LD I, 0x300 ; LD [I], V0 ; JP 0x200
+        chip.load_slice(&[0x60, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+        let (mut s, _client) = session(&mut chip);
+        assert_eq!(s.handle("Z0,204,2"), "OK");
+        assert_eq!(s.handle("c"), "T05swbreak:;");
+        assert_eq!(s.chip.pc(), 0x204);
+        assert_eq!(s.handle("z0,204,2"), "OK");
+        assert_eq!(s.handle("Z2,300,1"), "OK");
+        assert_eq!(s.handle("c"), "T05watch:300;");
+        assert_eq!(s.handle("z2,300,1"), "OK");
+        assert_eq!(s.handle("Z2,300,ffffffffffffffff"), "E01");
+        assert_eq!(s.handle("Z0,204"), "E01");
+        assert_eq!(s.handle("Z1,204,2"), "");
+    }
+
+    #[test]
+    fn stops_at_conditional_breakpoints() {
+        let mut chip = Chip::headless();
+        // ADD V0, 1 ; JP 0x200
+        chip.load_slice(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let conditional = Conditional::install(&mut chip);
+        conditional.add(1, 0x202, Condition::parse(&["v0", "==", "3"]).unwrap());
+        let (mut s, _client) = session(&mut chip);
+        assert_eq!(s.handle("c"), "T05swbreak:;");
+        assert_eq!((s.chip.pc(), s.chip.register(Register::V(0))), (0x202, 3));
+        // V0 wraps around to 3 again
+        assert_eq!(s.handle("c"), "T05swbreak:;");
+        assert_eq!(s.cycle, 2 * 256 + 5);
+    }
+
+    #[test]
+    fn sees_a_buffered_interrupt() {
+        let mut chip = Chip::headless();
+        let (mut s, mut client) = session(&mut chip);
+        client.write_all(b"$c#63\x03").unwrap();
+        assert_eq!(s.receive().unwrap().as_deref(), Some("c"));
+        assert!(s.interrupted());
+        assert!(!s.interrupted());
+    }
+}
@@ -1,9 +1,16 @@
-use chip8::Chip;
+mod conditional;
+mod expr;
+mod gdb;
+mod memmap;
+mod repl;
+mod tui;
+
+use chip8::keymap::KeyMap;
+use chip8::{disasm, usage, Chip};
 use clap::Parser;
 use std::boxed::Box;
-use std::collections::HashMap;
 use std::fs::File;
-use std::io;
+use std::io::{self, IsTerminal, Read};
 use std::sync::{Arc, Mutex};
 
 // tracing tool of state of CHIP-8
@@ -12,70 +19,183 @@ use std::sync::{Arc, Mutex};
 struct Args {
     #[clap(short, long)]
     rom: String,
+    /// stop with a notice the first time each quirk-sensitive opcode is executed
+    #[clap(long)]
+    break_on_quirk: bool,
+    /// quirks to enable instead of those of the platform: vf-reset, load-store, shift, jump, clipping, display-wait, half-scroll, index-overflow, row-collisions (comma separated)
+    #[clap(long)]
+    quirks: Option<chip8::Quirks>,
+    /// interpreter to emulate: chip8, vip, schip, xochip, eti-660, dream-6800
+    #[clap(long, default_value = "chip8")]
+    platform: chip8::Platform,
+    /// print the instruction-set level the ROM requires and exit
+    #[clap(long)]
+    usage: bool,
+    /// print the disassembly of the ROM and exit
+    #[clap(long)]
+    disasm: bool,
+    /// cycles to execute for the dynamic part of --usage
+    #[clap(long, default_value = "10000")]
+    cycles: u64,
+    /// keyboard keys of the keypad: qwerty, azerty, qwertz, cosmac or a TOML/JSON keymap file
+    #[clap(long, default_value = "qwerty")]
+    keymap: KeyMap,
+    /// seed of the random numbers (CXNN)
+    #[clap(long)]
+    seed: Option<u64>,
+    /// print a map of the address space after every cycle
+    #[clap(long)]
+    memory_map: bool,
+    /// print the screen as text after every command of --lines which executes instructions
+    #[clap(long)]
+    show_display: bool,
+    /// print what changed instead of the last instruction of every command of --lines which executes instructions
+    #[clap(long)]
+    diff: bool,
+    /// cycles per 60Hz frame; the timers count down once per frame
+    #[clap(long, default_value = "10")]
+    cycles_per_frame: u64,
+    /// address (hex) where `!` stops, or `ADDR if COND` to stop there only when COND holds (e.g. '230 if v4 == 10'); repeat for more (`+ADDR`/`-ADDR` add/remove while running)
+    #[clap(long = "break")]
+    r#break: Vec<expr::Break>,
+    /// where `!` stops after a change: v0-vf, i, dt, ADDR or ADDR..END (RAM writes); repeat for more (`*W`/`~W` add/remove while running)
+    #[clap(long)]
+    watch: Vec<chip8::Watch>,
+    /// read one command per line instead of the full-screen debugger (the default without a terminal)
+    #[clap(long)]
+    lines: bool,
+    /// read the commands of --lines from this file; exits with 1 when an assertion fails or the machine traps
+    #[clap(long)]
+    script: Option<String>,
+    /// serve the GDB remote serial protocol on this address (e.g. 127.0.0.1:1234) instead of reading commands
+    #[clap(long)]
+    gdb: Option<String>,
+    /// frames kept for rewinding with `<N` (go back N frames)
+    #[clap(long, default_value = "0")]
+    rewind_depth: usize,
+    /// instructions kept for stepping back with `back [N]` in --lines; 0 turns it off
+    #[clap(long, default_value = "1000")]
+    back_depth: usize,
 }
 
-fn init_keyboard_map(key_map: &mut HashMap<char, u8>) {
-    key_map.insert('1', 0x1);
-    key_map.insert('2', 0x2);
-    key_map.insert('3', 0x3);
-    key_map.insert('q', 0x4);
-    key_map.insert('w', 0x5);
-    key_map.insert('e', 0x6);
-    key_map.insert('a', 0x7);
-    key_map.insert('s', 0x8);
-    key_map.insert('d', 0x9);
-    key_map.insert('z', 0xa);
-    key_map.insert('x', 0x0);
-    key_map.insert('c', 0xb);
-    key_map.insert('4', 0xc);
-    key_map.insert('v', 0xf);
+fn parse_addr(s: &str) -> Result<u16, std::num::ParseIntError> {
+    u16::from_str_radix(s.trim_start_matches("0x"), 16)
 }
 
 fn main() {
     env_logger::init();
-    let mut key_map: HashMap<char, u8> = HashMap::new();
-    init_keyboard_map(&mut key_map);
     let args = Args::parse();
-    let dsp = Mock {};
     let kbd = Box::new(Keyboard::new());
     let setter = kbd.pressed.clone();
+    // the full-screen debugger needs a terminal.
+    let full_screen = !args.lines
+        && args.script.is_none()
+        && args.gdb.is_none()
+        && io::stdin().is_terminal()
+        && io::stdout().is_terminal();
 
-    let mut chip = Chip::new(Box::new(dsp), kbd);
+    let mut chip = Chip::new(Box::new(chip8::NullDisplay), kbd);
+    chip.set_platform(&args.platform);
+    if let Some(q) = args.quirks {
+        chip.set_quirks(q);
+    }
+    chip.warn_quirks(args.break_on_quirk);
+    if let Some(s) = args.seed {
+        chip.set_seed(s);
+    }
+    chip.set_rewind(args.rewind_depth, 1);
 
-    let l = chip.load(&mut File::open(args.rom).unwrap()).unwrap();
+    let mut rom = Vec::new();
+    File::open(args.rom).unwrap().read_to_end(&mut rom).unwrap();
+    let l = chip.load(&mut &rom[..]).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
     println!("load:{}[byte]", l);
-    let stdin = io::stdin();
-    loop {
-        let mut line = String::new();
-        let _ = stdin.read_line(&mut line).unwrap();
-        line = line.trim().to_string();
-        println!("input:`{}`", line);
-        let mut keys = line
-            .chars()
-            .map(|c| key_map.get(&c))
-            .filter(|c| match c {
-                Some(_) => true,
-                _ => false,
-            })
-            .map(|c| *(c.unwrap()))
-            .collect();
-        {
-            let mut r = setter.lock().unwrap();
-            r.clear();
-            r.append(&mut keys);
+    if args.disasm {
+        for line in disasm::disassemble(&rom, args.platform.start) {
+            println!("{}", line);
+        }
+        return;
+    }
+    if args.usage {
+        println!("{}", usage_report(&mut chip, &rom, args.cycles));
+        println!("reachable : {}", usage::detect(&rom, args.platform.start));
+        return;
+    }
+    // plain breakpoints are numbered first, then the conditional ones in order
+    let conditional = conditional::Conditional::install(&mut chip);
+    for b in args.r#break.iter().filter(|b| b.condition.is_none()) {
+        chip.add_breakpoint(b.addr);
+    }
+    let mut n = chip.breakpoints().count();
+    for b in &args.r#break {
+        if let Some(c) = b.condition {
+            n += 1;
+            conditional.add(n, b.addr, c);
+        }
+    }
+    for w in &args.watch {
+        chip.add_watchpoint(*w);
+    }
+    if let Some(addr) = &args.gdb {
+        if let Err(e) = gdb::serve(&mut chip, addr, args.cycles_per_frame) {
+            eprintln!("gdb server: {}", e);
+        }
+        return;
+    }
+    if full_screen {
+        if let Err(e) = tui::run(
+            &mut chip,
+            &conditional,
+            &args.keymap,
+            setter,
+            args.cycles_per_frame,
+        ) {
+            eprintln!("debugger: {}", e);
+        }
+        return;
+    }
+    match repl::run(
+        &mut chip,
+        &conditional,
+        &args.keymap,
+        setter,
+        args.cycles_per_frame,
+        args.memory_map,
+        args.show_display,
+        args.diff,
+        args.back_depth,
+        args.script.as_deref(),
+    ) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("debugger: {}", e);
+            std::process::exit(1);
         }
-        chip.cycle();
     }
 }
 
-struct Mock {}
-impl chip8::Display for Mock {
-    fn clear(&mut self) {
-        print!("clear")
-    }
-    fn draw(&mut self, _x: u8, _y: u8, _sprite: &[u8]) -> bool {
-        false
+// scans the ROM image, then runs it while it only executes plain CHIP-8 opcodes.
+fn usage_report(chip: &mut Chip, rom: &[u8], cycles: u64) -> usage::Report {
+    let mut report = usage::Report::scan(rom);
+    for _ in 0..cycles {
+        if chip.waiting_for_vblank() {
+            chip.tick_60hz();
+        }
+        let pc = chip.pc() as usize;
+        if pc + 1 >= chip.ram.buf.len() {
+            break;
+        }
+        let op = u16::from_be_bytes([chip.ram.buf[pc], chip.ram.buf[pc + 1]]);
+        report.record(op);
+        match usage::classify(op) {
+            Some((usage::Level::Chip8, _)) if chip.cycle().is_ok() => {}
+            _ => break,
+        }
     }
+    report
 }
 
 struct Keyboard {
@@ -97,7 +217,4 @@ impl chip8::Keyboard for Keyboard {
         }
         false
     }
-    fn wait(&self) -> u8 {
-        0
-    }
 }
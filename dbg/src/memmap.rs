@@ -0,0 +1,101 @@
+// the address space as cells of `CELL` bytes classified by how they were accessed, for the
+// `--memory-map` of the line debugger and the map panel of the full-screen one.
+use chip8::{Access, Chip};
+
+// bytes of RAM per cell, and cells per row
+pub const CELL: usize = 16;
+pub const ROW: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Pc,
+    I,
+    Written,
+    Code,
+    Data,
+    Font,
+    Untouched,
+}
+
+impl Region {
+    // the region of the cell at `start`; PC and I win over how the cell was accessed.
+    pub fn of(chip: &Chip, start: usize) -> Region {
+        let addrs = start..start + CELL;
+        let any = |a: Access| addrs.clone().any(|n| chip.ram.accessed(n, a));
+        if addrs.contains(&(chip.pc() as usize)) {
+            Region::Pc
+        } else if addrs.contains(&(chip.i() as usize)) {
+            Region::I
+        } else if any(Access::Written) {
+            Region::Written
+        } else if any(Access::Executed) {
+            Region::Code
+        } else if any(Access::Read) {
+            Region::Data
+        } else if start < chip8::FONT_END {
+            Region::Font
+        } else {
+            Region::Untouched
+        }
+    }
+    // f font, c code, d data read, w written, . untouched, P/I for PC and I.
+    pub fn letter(self) -> char {
+        match self {
+            Region::Pc => 'P',
+            Region::I => 'I',
+            Region::Written => 'w',
+            Region::Code => 'c',
+            Region::Data => 'd',
+            Region::Font => 'f',
+            Region::Untouched => '.',
+        }
+    }
+}
+
+// whether the cell at `start` was accessed since `Ram::clear_recent`.
+pub fn recent(chip: &Chip, start: usize) -> bool {
+    [Access::Executed, Access::Read, Access::Written]
+        .iter()
+        .any(|a| (start..start + CELL).any(|n| chip.ram.recently(n, *a)))
+}
+
+// the first address of every cell.
+pub fn cells(chip: &Chip) -> impl Iterator<Item = usize> {
+    (0..chip.ram.buf.len()).step_by(CELL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_cells_by_access() {
+        let mut chip = Chip::headless();
+        // LD I, 0x300 ; LD [I], V0 ; LD I, 0x400 ; LD V0, [I] ; JP 0x220
+        chip.load_slice(&[0xA3, 0x00, 0xF0, 0x55, 0xA4, 0x00, 0xF0, 0x65, 0x12, 0x20])
+            .unwrap();
+        chip.ram.clear_recent();
+        for _ in 0..5 {
+            chip.step().unwrap();
+        }
+        let regions: Vec<Region> = [0, 0x200, 0x220, 0x300, 0x400, 0x500]
+            .iter()
+            .map(|a| Region::of(&chip, *a))
+            .collect();
+        assert_eq!(
+            regions,
+            [
+                Region::Font,
+                Region::Code,
+                Region::Pc,
+                Region::Written,
+                Region::I,
+                Region::Untouched
+            ]
+        );
+        assert!(recent(&chip, 0x300) && !recent(&chip, 0x500));
+        chip.ram.clear_recent();
+        assert!(!recent(&chip, 0x300));
+        assert_eq!(cells(&chip).count(), 0x1000 / CELL);
+    }
+}
@@ -0,0 +1,894 @@
+// line debugger (`--lines`, `--script`, or without a terminal): a command per line, and any other
+// line executes one instruction with its characters as the keys held down.
+//
+// Commands: break [ADDR [if COND] | write ADDR..END], delete N, continue, until ADDR,
+// step [N], back [N], x[/N] ADDR, dis [ADDR [N]], reg [R], set R VALUE, assert COND, display
+// (or dump-display), @ADDR, <N, +ADDR/-ADDR, *W/~W, `!`; `#` starts a comment. On a terminal
+// the lines are edited with rustyline: history, and Tab completes commands and register names.
+use crate::conditional::Conditional;
+use crate::expr::{Break, Condition};
+use crate::memmap::{self, Region};
+use crate::parse_addr;
+use chip8::keymap::KeyMap;
+use chip8::{disasm, Chip, ChipState, Control, Register, Step, Watch};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::sync::{Arc, Mutex};
+
+// bytes shown by `x` without a count, and instructions by `dis`
+const EXAMINE_BYTES: usize = 16;
+const DISASSEMBLY_LINES: usize = 10;
+// RAM bytes listed by --diff; more are counted
+const DIFF_BYTES: usize = 16;
+
+// the words Tab completes first on a line, and after `break`
+const COMMANDS: [&str; 13] = [
+    "assert",
+    "back",
+    "break",
+    "continue",
+    "delete",
+    "dis",
+    "display",
+    "dump-display",
+    "reg",
+    "set",
+    "step",
+    "until",
+    "x/",
+];
+const BREAK_WORDS: [&str; 2] = ["if", "write"];
+const PROMPT: &str = "(dbg) ";
+
+// a numbered breakpoint: plain ones and `write` are the core's breakpoints and watchpoints,
+// conditional ones are tested by the machine's hook (`Conditional`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Breakpoint {
+    At(u16),
+    If(u16, Condition),
+    Write(Watch),
+}
+
+impl Breakpoint {
+    fn addr(&self) -> Option<u16> {
+        match self {
+            Breakpoint::At(a) | Breakpoint::If(a, _) => Some(*a),
+            Breakpoint::Write(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::At(a) => write!(f, "{:03x}", a),
+            Breakpoint::If(a, c) => write!(f, "{:03x} if {}", a, c),
+            Breakpoint::Write(w) => write!(f, "write {}", w),
+        }
+    }
+}
+
+struct Repl<'a> {
+    chip: &'a mut Chip,
+    conditional: Conditional,
+    keymap: &'a KeyMap,
+    pressed: Arc<Mutex<Vec<u8>>>,
+    cycles_per_frame: u64,
+    cycle: u64,
+    memory_map: bool,
+    show_display: bool,
+    diff: bool,
+    // the cycle count and the machine before each of the last `back_depth` instructions, newest
+    // last, for `back`
+    history: VecDeque<(u64, ChipState)>,
+    back_depth: usize,
+    // breakpoint N at N - 1, None once deleted; numbers are never reused
+    numbered: Vec<Option<Breakpoint>>,
+    // an assertion failed or the machine trapped
+    failed: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    chip: &mut Chip,
+    conditional: &Conditional,
+    keymap: &KeyMap,
+    pressed: Arc<Mutex<Vec<u8>>>,
+    cycles_per_frame: u64,
+    memory_map: bool,
+    show_display: bool,
+    diff: bool,
+    back_depth: usize,
+    script: Option<&str>,
+) -> io::Result<bool> {
+    // breakpoints given with --break are numbered first
+    let numbered = chip
+        .breakpoints()
+        .map(|a| Some(Breakpoint::At(a)))
+        .chain((conditional.list().into_iter()).map(|(_, a, c)| Some(Breakpoint::If(a, c))))
+        .collect();
+    let mut r = Repl {
+        chip,
+        conditional: conditional.clone(),
+        keymap,
+        pressed,
+        cycles_per_frame: cycles_per_frame.max(1),
+        cycle: 0,
+        memory_map,
+        show_display,
+        diff,
+        history: VecDeque::with_capacity(back_depth),
+        back_depth,
+        numbered,
+        failed: false,
+    };
+    if script.is_none() && io::stdin().is_terminal() {
+        r.edit_lines().map_err(io::Error::other)?;
+        return Ok(!r.failed);
+    }
+    let input: Box<dyn BufRead> = match script {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+    for line in input.lines() {
+        if !r.command(line?.trim()) {
+            break;
+        }
+    }
+    Ok(!r.failed)
+}
+
+impl Repl<'_> {
+    // reads the commands from the terminal until EOF (Ctrl-D); Ctrl-C drops the line.
+    fn edit_lines(&mut self) -> Result<(), ReadlineError> {
+        // Tab lists the candidates like a shell instead of cycling through them
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor: Editor<Completion, DefaultHistory> = Editor::with_config(config)?;
+        editor.set_helper(Some(Completion));
+        loop {
+            let line = match editor.readline(PROMPT) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if !line.trim().is_empty() {
+                editor.add_history_entry(line.as_str())?;
+            }
+            if !self.command(line.trim()) {
+                return Ok(());
+            }
+        }
+    }
+
+    // returns false when the machine cannot go on, or an assertion failed.
+    fn command(&mut self, line: &str) -> bool {
+        if line.starts_with('#') {
+            return true;
+        }
+        let mut words = line.split_whitespace();
+        let (word, arg) = (words.next().unwrap_or_default(), words.next());
+        match (word, arg) {
+            ("step", _) => {
+                return match arg.map_or(Ok(1), str::parse::<u64>) {
+                    Ok(n) => {
+                        self.pressed.lock().unwrap().clear();
+                        self.execute(Some(n.max(1)), None)
+                    }
+                    Err(_) => {
+                        println!("usage: step [N]");
+                        true
+                    }
+                };
+            }
+            ("back", _) => {
+                match arg.map_or(Ok(1), str::parse::<usize>) {
+                    Ok(n) => self.back(n.max(1)),
+                    Err(_) => println!("usage: back [N]"),
+                }
+                return true;
+            }
+            ("assert", Some(_)) => {
+                let words: Vec<&str> = line.split_whitespace().skip(1).collect();
+                let (cpu, ram) = (self.chip.registers(), &self.chip.ram);
+                return match Condition::parse(&words) {
+                    Ok(c) if c.holds(&cpu, ram) => true,
+                    Ok(c) => {
+                        let actual = c.operand.get(&cpu, ram);
+                        println!("assertion failed: {} ({} is {:x})", c, c.operand, actual);
+                        self.failed = true;
+                        false
+                    }
+                    Err(e) => {
+                        println!("{}", e);
+                        self.failed = true;
+                        false
+                    }
+                };
+            }
+            ("display" | "dump-display", None) => {
+                print_display(self.chip);
+                return true;
+            }
+            ("break", None) => {
+                for (n, b) in self.breakpoints() {
+                    println!("{}: {}", n, b);
+                }
+                return true;
+            }
+            ("break", Some("write")) => {
+                match words.next().map(str::parse::<Watch>) {
+                    Some(Ok(w @ Watch::Ram { .. })) => {
+                        self.chip.add_watchpoint(w);
+                        let n = self.number_new(Breakpoint::Write(w));
+                        println!("breakpoint {}: write {}", n, w);
+                    }
+                    _ => println!("usage: break write ADDR..END"),
+                }
+                return true;
+            }
+            ("break", Some(_)) => {
+                let rest = line.split_once(char::is_whitespace).unwrap_or_default().1;
+                match rest.parse::<Break>() {
+                    Ok(Break {
+                        addr,
+                        condition: None,
+                    }) => {
+                        let n = self.add_breakpoint(addr);
+                        println!("breakpoint {} at {:03x}", n, addr);
+                    }
+                    Ok(Break {
+                        addr,
+                        condition: Some(c),
+                    }) => {
+                        let n = self.number_new(Breakpoint::If(addr, c));
+                        self.conditional.add(n, addr, c);
+                        println!("breakpoint {} at {:03x} if {}", n, addr, c);
+                    }
+                    Err(e) => println!("{}", e),
+                }
+                return true;
+            }
+            ("delete", Some(n)) => {
+                let addr = n.parse::<usize>().ok().and_then(|n| self.delete(n));
+                if addr.is_none() {
+                    println!("no breakpoint {}", n);
+                }
+                return true;
+            }
+            ("continue", None) => return self.resume(None),
+            ("until", Some(addr)) => {
+                return match parse_addr(addr) {
+                    Ok(a) => self.resume(Some(a)),
+                    _ => {
+                        println!("invalid address: {}", addr);
+                        true
+                    }
+                };
+            }
+            ("dis", _) => {
+                let addr = arg.map_or(Ok(self.chip.pc()), parse_addr);
+                let count = words.next().map_or(Ok(DISASSEMBLY_LINES), str::parse);
+                match (addr, count) {
+                    (Ok(a), Ok(n)) if (a as usize) < self.chip.ram.buf.len() => {
+                        self.disassemble(a, n)
+                    }
+                    _ => println!("usage: dis [ADDR [N]]"),
+                }
+                return true;
+            }
+            ("reg", None) => {
+                print_registers(self.chip);
+                return true;
+            }
+            ("reg", Some(name)) => {
+                match name.parse::<Register>() {
+                    Ok(r) => println!("{}={:0w$x}", r, self.chip.register(r), w = width(r)),
+                    Err(e) => println!("{}", e),
+                }
+                return true;
+            }
+            ("set", Some(name)) => {
+                let value = words.next().map(parse_addr);
+                match (name.parse::<Register>(), value) {
+                    (Ok(r), Some(Ok(v))) if v > max(r) => {
+                        println!("{} holds at most {:x}", r, max(r))
+                    }
+                    (Ok(r), Some(Ok(v))) => {
+                        self.chip.set_register(r, v);
+                        println!("{}={:0w$x}", r, self.chip.register(r), w = width(r));
+                    }
+                    (Err(e), _) => println!("{}", e),
+                    _ => println!("usage: set R VALUE (hex)"),
+                }
+                return true;
+            }
+            _ => {}
+        }
+        // x/N ADDR: N bytes from ADDR; a lone `x` presses a key
+        let examine = word.starts_with("x/") || (word == "x" && arg.is_some());
+        if let Some(count) = word.strip_prefix('x').filter(|_| examine) {
+            let count = match count.strip_prefix('/') {
+                Some(n) => n.parse(),
+                None => Ok(EXAMINE_BYTES),
+            };
+            match (count, arg.map(parse_addr)) {
+                (Ok(n), Some(Ok(a))) if (a as usize) < self.chip.ram.buf.len() => {
+                    hexdump(self.chip, a as usize, n)
+                }
+                _ => println!("usage: x/N ADDR"),
+            }
+            return true;
+        }
+        if let Some(addr) = line.strip_prefix('@') {
+            match usize::from_str_radix(addr, 16) {
+                Ok(a) if a < self.chip.ram.buf.len() => hexdump(self.chip, a & !0xf, 256),
+                _ => println!("invalid address: {}", addr),
+            }
+            return true;
+        }
+        if let Some(frames) = line.strip_prefix('<') {
+            match frames.parse().ok().and_then(|n| self.chip.rewind(n)) {
+                Some(n) => {
+                    // the instructions kept for `back` now lie in the future
+                    self.history.clear();
+                    println!("rewound {} frames to pc {:03x}", n, self.chip.pc());
+                }
+                None => println!("cannot rewind (see --rewind-depth): {}", frames),
+            }
+            return true;
+        }
+        if let Some(addr) = line.strip_prefix('+') {
+            match parse_addr(addr) {
+                Ok(a) => {
+                    self.add_breakpoint(a);
+                }
+                _ => println!("invalid address: {}", addr),
+            }
+            return true;
+        }
+        if let Some(addr) = line.strip_prefix('-') {
+            match parse_addr(addr) {
+                Ok(a) if self.chip.remove_breakpoint(a) => self.forget(a),
+                _ => println!("no breakpoint at {}", addr),
+            }
+            return true;
+        }
+        if let Some(w) = line.strip_prefix('*') {
+            match w.parse() {
+                Ok(w) => self.chip.add_watchpoint(w),
+                Err(e) => println!("{}", e),
+            }
+            return true;
+        }
+        if let Some(w) = line.strip_prefix('~') {
+            match w.parse() {
+                Ok(w) if self.chip.remove_watchpoint(w) => {}
+                _ => println!("no watchpoint {}", w),
+            }
+            return true;
+        }
+        if line == "!" {
+            return self.resume(None);
+        }
+        println!("input:`{}`", line);
+        let mut keys = line.chars().filter_map(|c| self.keymap.key(c)).collect();
+        let mut r = self.pressed.lock().unwrap();
+        r.clear();
+        r.append(&mut keys);
+        drop(r);
+        self.execute(Some(1), None)
+    }
+
+    // runs without keys until a breakpoint, a watchpoint, `until` or EXIT (or a quirk with
+    // --break-on-quirk).
+    fn resume(&mut self, until: Option<u16>) -> bool {
+        self.pressed.lock().unwrap().clear();
+        self.execute(None, until)
+    }
+
+    // executes `count` instructions, or as many as it takes to stop, and prints the last one;
+    // false after a trap.
+    fn execute(&mut self, count: Option<u64>, until: Option<u16>) -> bool {
+        let mut left = count;
+        let before = self.diff.then(|| self.chip.save_state());
+        let mut last = None;
+        loop {
+            if self.back_depth > 0 {
+                if self.history.len() == self.back_depth {
+                    self.history.pop_front();
+                }
+                self.history.push_back((self.cycle, self.chip.save_state()));
+            }
+            let step = match self.chip.step() {
+                Ok(step) => step,
+                Err(trap) => {
+                    println!("trap: {}", trap);
+                    self.failed = true;
+                    return false;
+                }
+            };
+            // a conditional breakpoint paused the machine before the instruction
+            if let Some((n, _)) = self.conditional.hit(self.chip) {
+                if self.back_depth > 0 {
+                    self.history.pop_back();
+                }
+                match (&before, last) {
+                    (Some(b), _) => println!("{}", diff(b, &self.chip.save_state())),
+                    (None, Some(step)) => println!("{}", describe(&step)),
+                    (None, None) => {}
+                }
+                println!("breakpoint {} at {:03x}", n, self.chip.pc());
+                break;
+            }
+            last = Some(step);
+            self.cycle += 1;
+            left = left.map(|n| n - 1);
+            // the last instruction asked for stops anyway
+            let done = left == Some(0);
+            if self.cycle.is_multiple_of(self.cycles_per_frame) {
+                self.chip.tick_60hz();
+            }
+            let quirk = self.chip.quirk_hit();
+            let watch = self.chip.watch_hit();
+            let pc = self.chip.pc();
+            let breakpoint = !done && self.chip.at_breakpoint();
+            let reached = until == Some(pc) && step.control != Control::Wait;
+            let halted = !done && self.chip.halted();
+            let stop = breakpoint || reached || halted;
+            if done || stop || quirk.is_some() || watch.is_some() {
+                match &before {
+                    Some(b) => println!("{}", diff(b, &self.chip.save_state())),
+                    None => println!("{}", describe(&step)),
+                }
+                if let Some(q) = quirk {
+                    println!("quirk-sensitive opcode: {}", q.description());
+                }
+                if let Some(w) = watch {
+                    println!("watchpoint {} hit", w);
+                }
+                if breakpoint {
+                    match self.number(pc) {
+                        Some(n) => println!("breakpoint {} at {:03x}", n, pc),
+                        None => println!("breakpoint at {:03x}", pc),
+                    }
+                } else if reached {
+                    println!("reached {:03x}", pc);
+                }
+                if halted {
+                    println!("halted (EXIT)");
+                }
+                break;
+            }
+        }
+        if self.show_display {
+            print_display(self.chip);
+        }
+        if self.memory_map {
+            memory_map(self.chip);
+        }
+        true
+    }
+
+    // undoes the last `n` instructions (as many as are kept) and shows the next one.
+    fn back(&mut self, n: usize) {
+        let n = n.min(self.history.len());
+        let Some((cycle, state)) = self.history.drain(self.history.len() - n..).next() else {
+            println!("cannot step back (see --back-depth)");
+            return;
+        };
+        self.cycle = cycle;
+        self.chip.load_state(state);
+        println!("stepped back {}", n);
+        self.disassemble(self.chip.pc(), 1);
+        if self.show_display {
+            print_display(self.chip);
+        }
+    }
+
+    // prints `n` instructions from `addr`: `>` marks PC and `*` breakpoints.
+    fn disassemble(&self, addr: u16, n: usize) {
+        let ram = &self.chip.ram.buf;
+        let pc = self.chip.pc();
+        for l in disasm::disassemble(&ram[addr as usize..], addr).take(n) {
+            let marked = self.breakpoints().any(|(_, b)| b.addr() == Some(l.addr));
+            let mark = match (l.addr == pc, marked) {
+                (true, true) => ">*",
+                (true, false) => "> ",
+                (false, true) => " *",
+                (false, false) => "  ",
+            };
+            println!("{}{}", mark, l);
+        }
+    }
+
+    // the breakpoints by number.
+    fn breakpoints(&self) -> impl Iterator<Item = (usize, Breakpoint)> + '_ {
+        self.numbered
+            .iter()
+            .enumerate()
+            .filter_map(|(n, b)| b.map(|b| (n + 1, b)))
+    }
+
+    // the number of the plain breakpoint at `addr`.
+    fn number(&self, addr: u16) -> Option<usize> {
+        self.breakpoints()
+            .find(|(_, b)| *b == Breakpoint::At(addr))
+            .map(|(n, _)| n)
+    }
+
+    fn number_new(&mut self, b: Breakpoint) -> usize {
+        self.numbered.push(Some(b));
+        self.numbered.len()
+    }
+
+    // returns the number of the breakpoint at `addr`, the existing one if there is one.
+    fn add_breakpoint(&mut self, addr: u16) -> usize {
+        if let Some(n) = self.number(addr) {
+            return n;
+        }
+        self.chip.add_breakpoint(addr);
+        self.number_new(Breakpoint::At(addr))
+    }
+
+    // removes breakpoint `n` from the machine.
+    fn delete(&mut self, n: usize) -> Option<Breakpoint> {
+        let b = self.numbered.get_mut(n.checked_sub(1)?)?.take()?;
+        match b {
+            Breakpoint::At(a) => {
+                self.chip.remove_breakpoint(a);
+            }
+            Breakpoint::Write(w) => {
+                self.chip.remove_watchpoint(w);
+            }
+            Breakpoint::If(..) => self.conditional.remove(n),
+        }
+        Some(b)
+    }
+
+    // `-ADDR` removed the plain breakpoint at `addr`.
+    fn forget(&mut self, addr: u16) {
+        for b in self.numbered.iter_mut() {
+            if *b == Some(Breakpoint::At(addr)) {
+                *b = None;
+            }
+        }
+    }
+}
+
+// completes the first word of a line to a command and the others to register names (and the
+// `if` and `write` of `break`).
+struct Completion;
+
+impl Completer for Completion {
+    type Candidate = Pair;
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = line[start..pos].to_ascii_lowercase();
+        let registers = Register::ALL.map(|r| r.to_string().to_ascii_lowercase());
+        let mut words: Vec<&str> = match start {
+            0 => COMMANDS.to_vec(),
+            _ => registers.iter().map(String::as_str).collect(),
+        };
+        if start > 0 && line.starts_with("break ") {
+            words.extend(BREAK_WORDS);
+        }
+        let candidates = words
+            .into_iter()
+            .filter(|w| w.starts_with(&word))
+            .map(|w| Pair {
+                display: w.to_string(),
+                // `x/` goes on with the count
+                replacement: match w.ends_with('/') {
+                    true => w.to_string(),
+                    false => format!("{} ", w),
+                },
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completion {
+    type Hint = String;
+}
+
+impl Highlighter for Completion {}
+
+impl Validator for Completion {}
+
+impl Helper for Completion {}
+
+// `200: ADD V1, V2  next  changed V1 VF`, plus I and `display` when they changed.
+fn describe(step: &Step) -> String {
+    let text = match (step.opcode, step.control) {
+        (_, Control::Wait) => "(idle)".to_string(),
+        (Some(o), _) => o.to_string(),
+        (None, _) => "(not an opcode)".to_string(),
+    };
+    let control = match step.control {
+        Control::Next => "next".to_string(),
+        Control::Skip => "skip".to_string(),
+        Control::Jump(a) => format!("jump {:03x}", a),
+        Control::Wait => "wait for vblank".to_string(),
+    };
+    let mut changed: Vec<String> = (0..16)
+        .filter(|n| step.changed_v & 1 << n != 0)
+        .map(|n| format!("V{:X}", n))
+        .collect();
+    if step.changed_i {
+        changed.push("I".to_string());
+    }
+    if step.display_changed {
+        changed.push("display".to_string());
+    }
+    let mut line = format!("{:03x}: {:<16}{}", step.addr, text, control);
+    if !changed.is_empty() {
+        line += &format!("  changed {}", changed.join(" "));
+    }
+    line
+}
+
+// prints the address space as rows of cells colored by region (see `memmap::Region::letter`),
+// uppercase when accessed since the last map.
+fn memory_map(chip: &Chip) {
+    let cells: Vec<char> = memmap::cells(chip)
+        .map(
+            |start| match (Region::of(chip, start), memmap::recent(chip, start)) {
+                (r @ (Region::Pc | Region::I), _) => r.letter(),
+                (r, true) => r.letter().to_ascii_uppercase(),
+                (r, false) => r.letter(),
+            },
+        )
+        .collect();
+    for (n, row) in cells.chunks(memmap::ROW).enumerate() {
+        let row: String = row.iter().collect();
+        println!("{:03x} |{}|", n * memmap::ROW * memmap::CELL, row);
+    }
+    chip.ram.clear_recent();
+}
+
+// what changed from `before` to `after`, e.g. `V3: 0x10 -> 0x1f, RAM[0x300] := 0x07`.
+fn diff(before: &ChipState, after: &ChipState) -> String {
+    let mut changes = Vec::new();
+    let mut change = |name: String, old: u16, new: u16, digits: usize| {
+        if old != new {
+            changes.push(format!(
+                "{}: 0x{:03$x} -> 0x{:03$x}",
+                name, old, new, digits
+            ));
+        }
+    };
+    for (n, (old, new)) in before.v.iter().zip(after.v).enumerate() {
+        change(format!("V{:X}", n), *old as u16, new as u16, 2);
+    }
+    change("I".to_string(), before.i, after.i, 3);
+    change("PC".to_string(), before.pc, after.pc, 3);
+    change("SP".to_string(), before.sp as u16, after.sp as u16, 1);
+    for (n, (old, new)) in before.stack.iter().zip(after.stack).enumerate() {
+        change(format!("stack[{}]", n), *old, new, 3);
+    }
+    change("DT".to_string(), before.dt as u16, after.dt as u16, 2);
+    change("ST".to_string(), before.st as u16, after.st as u16, 2);
+    let written: Vec<(usize, u8)> = (before.ram.iter().zip(&after.ram).enumerate())
+        .filter(|(_, (old, new))| old != new)
+        .map(|(a, (_, new))| (a, *new))
+        .collect();
+    for (a, value) in written.iter().take(DIFF_BYTES) {
+        changes.push(format!("RAM[0x{:03x}] := 0x{:02x}", a, value));
+    }
+    if written.len() > DIFF_BYTES {
+        changes.push(format!("{} more RAM bytes", written.len() - DIFF_BYTES));
+    }
+    if before.framebuffer != after.framebuffer || before.hires != after.hires {
+        changes.push("display".to_string());
+    }
+    if after.halted && !before.halted {
+        changes.push("halted".to_string());
+    }
+    match changes.is_empty() {
+        true => "no change".to_string(),
+        false => changes.join(", "),
+    }
+}
+
+// hex digits of a register.
+fn width(r: Register) -> usize {
+    match r {
+        Register::I | Register::Pc => 3,
+        _ => 2,
+    }
+}
+
+fn max(r: Register) -> u16 {
+    match r {
+        Register::I | Register::Pc => u16::MAX,
+        Register::Sp => 16,
+        _ => u8::MAX.into(),
+    }
+}
+
+// V0-VF in two rows, I, PC, SP, DT and ST, and the stack from the top.
+fn print_registers(chip: &Chip) {
+    let r = chip.registers();
+    for (row, vs) in r.v.chunks(8).enumerate() {
+        let cells: Vec<String> = vs
+            .iter()
+            .enumerate()
+            .map(|(n, v)| format!("V{:X}={:02x}", row * 8 + n, v))
+            .collect();
+        println!("{}", cells.join(" "));
+    }
+    println!(
+        "I={:03x} PC={:03x} SP={} DT={:02x} ST={:02x}",
+        r.i, r.pc, r.sp, r.dt, r.st
+    );
+    let stack: String = r.stack[..r.sp as usize]
+        .iter()
+        .rev()
+        .map(|a| format!(" {:03x}", a))
+        .collect();
+    println!("stack:{}", stack);
+}
+
+// the framebuffer, `#` for lit pixels.
+fn print_display(chip: &Chip) {
+    let fb = chip.framebuffer();
+    for y in 0..fb.height() {
+        let row: String = (0..fb.width())
+            .map(|x| if fb.pixel(x, y) != 0 { '#' } else { '.' })
+            .collect();
+        println!("{}", row);
+    }
+}
+
+// prints `len` bytes of RAM from `addr`, 16 per row (`x/N ADDR`, or 256 from the row of
+// `@ADDR`).
+fn hexdump(chip: &Chip, addr: usize, len: usize) {
+    let buf = &chip.ram.buf;
+    let end = addr.saturating_add(len).min(buf.len());
+    for row in (addr..end).step_by(16) {
+        let bytes: Vec<String> = buf[row..(row + 16).min(end)]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        println!("{:03x}: {}", row, bytes.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::{Keypad, NullDisplay};
+
+    fn repl<'a>(chip: &'a mut Chip, keymap: &'a KeyMap) -> Repl<'a> {
+        let conditional = Conditional::install(chip);
+        Repl {
+            chip,
+            conditional,
+            keymap,
+            pressed: Arc::default(),
+            cycles_per_frame: 10,
+            cycle: 0,
+            memory_map: false,
+            show_display: false,
+            diff: false,
+            history: VecDeque::new(),
+            back_depth: 10,
+            numbered: Vec::new(),
+            failed: false,
+        }
+    }
+
+    #[test]
+    fn conditional_breakpoints_stop_continue() {
+        let mut chip = Chip::headless();
+        // ADD V0, 1 ; JP 0x200
+        chip.load_slice(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let keymap = KeyMap::default();
+        let mut r = repl(&mut chip, &keymap);
+        r.command("break 202 if v0 == 3");
+        r.command("break 200");
+        assert_eq!(
+            r.breakpoints()
+                .map(|(_, b)| b.to_string())
+                .collect::<Vec<_>>(),
+            ["202 if V0 == 3", "200"]
+        );
+        r.command("delete 2");
+        r.command("continue");
+        assert_eq!((r.chip.pc(), r.chip.register(Register::V(0))), (0x202, 3));
+        assert_eq!(r.cycle, 5);
+        // the instruction it paused before is executed, and V0 grows past 3
+        r.command("step 2");
+        assert_eq!((r.chip.pc(), r.chip.register(Register::V(0))), (0x202, 4));
+        r.command("delete 1");
+        r.command("step 600");
+        assert_eq!(r.cycle, 607);
+        assert!(!r.failed);
+    }
+
+    #[test]
+    fn sets_registers_and_runs_until_an_address() {
+        let mut chip = Chip::headless();
+        // ADD V0, 1 ; LD [I], V0 ; JP 0x200
+        chip.load_slice(&[0x70, 0x01, 0xF0, 0x55, 0x12, 0x00])
+            .unwrap();
+        let keymap = KeyMap::default();
+        let mut r = repl(&mut chip, &keymap);
+        r.command("set i 300");
+        r.command("set v0 1ff");
+        r.command("set vz 1");
+        assert_eq!(r.chip.register(Register::V(0)), 0);
+        r.command("set v0 10");
+        r.command("until 204");
+        assert_eq!(r.chip.pc(), 0x204);
+        assert!(r.command("assert [300] == 11"));
+        assert!(r.command("assert v0 >= 11"));
+        assert!(!r.failed);
+        assert!(!r.command("assert pc != 204"));
+        assert!(r.failed);
+    }
+
+    #[test]
+    fn completes_commands_and_registers() {
+        let history = DefaultHistory::new();
+        let ctx = Context::new(&history);
+        let complete = |line: &str| {
+            let (start, pairs) = Completion.complete(line, line.len(), &ctx).unwrap();
+            let words: Vec<String> = pairs.into_iter().map(|p| p.replacement).collect();
+            (start, words)
+        };
+        assert_eq!(complete("br"), (0, vec!["break ".to_string()]));
+        assert_eq!(complete("x"), (0, vec!["x/".to_string()]));
+        assert_eq!(complete("reg v").1.len(), 16);
+        assert_eq!(
+            complete("break 200 i"),
+            (10, vec!["i ".to_string(), "if ".to_string()])
+        );
+    }
+
+    #[test]
+    fn steps_back_into_a_key_wait() {
+        let keypad = Keypad::new();
+        let mut chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        // LD V1, K ; LD V2, 1
+        chip.load_slice(&[0xF1, 0x0A, 0x62, 0x01]).unwrap();
+        let keymap = KeyMap::default();
+        let mut r = repl(&mut chip, &keymap);
+        r.command("step");
+        keypad.press(7);
+        r.command("step");
+        keypad.release(7);
+        r.command("step");
+        assert!(!r.chip.waiting_for_key());
+        assert_eq!((r.chip.pc(), r.chip.register(Register::V(1))), (0x202, 7));
+        // back into FX0A, which has seen 7 pressed and completes once it is released
+        r.command("back");
+        assert!(r.chip.waiting_for_key());
+        assert_eq!((r.chip.pc(), r.cycle), (0x200, 2));
+        r.command("step");
+        assert_eq!((r.chip.pc(), r.chip.register(Register::V(1))), (0x202, 7));
+        // back to before the press: it waits again
+        r.command("back 2");
+        r.command("step");
+        assert!(r.chip.waiting_for_key());
+        assert!(!r.failed);
+    }
+}
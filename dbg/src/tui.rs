@@ -0,0 +1,389 @@
+// full-screen debugger: the screen, registers and stack on top, the disassembly around PC, a
+// map of the address space and a RAM hexdump below.
+//
+// Keys: n step, g continue/pause, b toggle a breakpoint at the cursor (conditional ones come from
+// `--break`), Up/Down move the cursor,
+// PageUp/PageDown scroll the hexdump, Tab moves the arrows to the map and back, where Enter
+// shows the selected cell in the hexdump, Esc quit; the keypad keys press CHIP-8 keys for a
+// moment.
+use crate::conditional::Conditional;
+use crate::memmap::{self, Region};
+use chip8::keymap::KeyMap;
+use chip8::{disasm, Chip, FrameBuffer, Step};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_micros(16_667);
+// how long a keypad key stays pressed; terminals do not report releases
+const KEY_HOLD: Duration = Duration::from_millis(100);
+const HEXDUMP_ROW: usize = 16;
+
+// rows of half blocks, two pixel rows per line.
+fn screen_lines(frame: &FrameBuffer) -> Vec<Line<'static>> {
+    let at = |x: usize, y: usize| y < frame.height() && frame.pixel(x, y) != 0;
+    (0..frame.height())
+        .step_by(2)
+        .map(|y| {
+            let row: String = (0..frame.width())
+                .map(|x| match (at(x, y), at(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect();
+            Line::from(row)
+        })
+        .collect()
+}
+
+struct Debugger<'a> {
+    chip: &'a mut Chip,
+    conditional: &'a Conditional,
+    keymap: &'a KeyMap,
+    pressed: Arc<Mutex<Vec<u8>>>,
+    released_at: Option<Instant>,
+    cycles_per_frame: u64,
+    cycle: u64,
+    running: bool,
+    // disassembly line the breakpoint key applies to
+    cursor: u16,
+    hexdump_at: usize,
+    // the arrows move the map's selected cell instead of the cursor
+    map_focus: bool,
+    map_cell: usize,
+    status: String,
+    last: Option<Step>,
+}
+
+pub fn run(
+    chip: &mut Chip,
+    conditional: &Conditional,
+    keymap: &KeyMap,
+    pressed: Arc<Mutex<Vec<u8>>>,
+    cycles_per_frame: u64,
+) -> io::Result<()> {
+    let cursor = chip.pc();
+    let mut d = Debugger {
+        chip,
+        conditional,
+        keymap,
+        pressed,
+        released_at: None,
+        cycles_per_frame: cycles_per_frame.max(1),
+        cycle: 0,
+        running: false,
+        cursor,
+        hexdump_at: 0x200,
+        map_focus: false,
+        map_cell: 0x200 / memmap::CELL,
+        status: "n step  g continue/pause  b breakpoint  Tab map  Esc quit".to_string(),
+        last: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = d.event_loop(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Debugger<'_> {
+    fn event_loop(&mut self, terminal: &mut ratatui::DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.render(f))?;
+            let frame_start = Instant::now();
+            if self.running {
+                self.run_frame();
+            }
+            if self.released_at.is_some_and(|t| t <= Instant::now()) {
+                self.pressed.lock().unwrap().clear();
+                self.released_at = None;
+            }
+            let wait = FRAME.saturating_sub(frame_start.elapsed());
+            if !event::poll(if self.running { wait } else { FRAME })? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(k) if k.kind == KeyEventKind::Press => k.code,
+                _ => continue,
+            };
+            match key {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Char('n') => {
+                    self.running = false;
+                    self.chip.ram.clear_recent();
+                    self.execute();
+                    self.cursor = self.chip.pc();
+                }
+                KeyCode::Char('g') => {
+                    self.running = !self.running;
+                    self.status = if self.running { "running" } else { "paused" }.to_string();
+                    self.cursor = self.chip.pc();
+                }
+                KeyCode::Char('b') => self.toggle_breakpoint(),
+                KeyCode::Tab => {
+                    self.map_focus = !self.map_focus;
+                    self.status = match self.map_focus {
+                        true => "map: arrows select, Enter shows it in the hexdump, Tab back",
+                        false => "code: Up/Down select, b breakpoint",
+                    }
+                    .to_string();
+                }
+                KeyCode::Enter if self.map_focus => {
+                    let last = self.chip.ram.buf.len() - HEXDUMP_ROW;
+                    self.hexdump_at = (self.map_cell * memmap::CELL).min(last);
+                }
+                KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right if self.map_focus => {
+                    self.move_map_cell(key)
+                }
+                KeyCode::Up => self.cursor = self.cursor.saturating_sub(2),
+                KeyCode::Down => self.cursor = self.cursor.saturating_add(2).min(0xFFE),
+                KeyCode::PageUp => {
+                    self.hexdump_at = self.hexdump_at.saturating_sub(HEXDUMP_ROW * 8)
+                }
+                KeyCode::PageDown => {
+                    let last = self.chip.ram.buf.len() - HEXDUMP_ROW;
+                    self.hexdump_at = (self.hexdump_at + HEXDUMP_ROW * 8).min(last);
+                }
+                KeyCode::Char(c) => {
+                    if let Some(k) = self.keymap.key(c) {
+                        let mut p = self.pressed.lock().unwrap();
+                        p.clear();
+                        p.push(k);
+                        self.released_at = Some(Instant::now() + KEY_HOLD);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn move_map_cell(&mut self, key: KeyCode) {
+        let cells = self.chip.ram.buf.len() / memmap::CELL;
+        self.map_cell = match key {
+            KeyCode::Up => self.map_cell.saturating_sub(memmap::ROW),
+            KeyCode::Down => self.map_cell + memmap::ROW,
+            KeyCode::Left => self.map_cell.saturating_sub(1),
+            _ => self.map_cell + 1,
+        }
+        .min(cells - 1);
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        if !self.chip.remove_breakpoint(self.cursor) {
+            self.chip.add_breakpoint(self.cursor);
+        }
+    }
+
+    // executes one 60Hz frame, stopping early at a breakpoint, watchpoint, EXIT or trap.
+    fn run_frame(&mut self) {
+        self.chip.ram.clear_recent();
+        for _ in 0..self.cycles_per_frame {
+            if !self.execute() {
+                self.running = false;
+                self.cursor = self.chip.pc();
+                return;
+            }
+            if self.chip.at_breakpoint() {
+                self.running = false;
+                self.cursor = self.chip.pc();
+                self.status = format!("breakpoint at {:03x}", self.chip.pc());
+                return;
+            }
+            if let Some(w) = self.chip.watch_hit() {
+                self.running = false;
+                self.cursor = self.chip.pc();
+                self.status = format!("watchpoint {} hit", w);
+                return;
+            }
+        }
+    }
+
+    // executes one instruction; false when the machine cannot go on or paused on a conditional
+    // breakpoint before the instruction.
+    fn execute(&mut self) -> bool {
+        match self.chip.step() {
+            Ok(_) if self.chip.hook_paused() => {
+                if let Some((n, c)) = self.conditional.hit(self.chip) {
+                    self.status = format!("breakpoint {} at {:03x} if {}", n, self.chip.pc(), c);
+                }
+                false
+            }
+            Ok(step) => {
+                self.cycle += 1;
+                if self.cycle.is_multiple_of(self.cycles_per_frame) {
+                    self.chip.tick_60hz();
+                }
+                self.last = Some(step);
+                if self.chip.halted() {
+                    self.status = "halted (EXIT)".to_string();
+                    return false;
+                }
+                true
+            }
+            Err(trap) => {
+                self.status = format!("trap: {}", trap);
+                false
+            }
+        }
+    }
+
+    fn render(&self, f: &mut Frame) {
+        let [top, bottom, status] = Layout::vertical([
+            Constraint::Length(self.chip.framebuffer().height().div_ceil(2) as u16 + 2),
+            Constraint::Min(6),
+            Constraint::Length(1),
+        ])
+        .areas(f.area());
+        let width = self.chip.framebuffer().width() as u16;
+        let [screen, registers, stack] = Layout::horizontal([
+            Constraint::Length(width + 2),
+            Constraint::Length(30),
+            Constraint::Min(10),
+        ])
+        .areas(top);
+        let [code, ram] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(20)]).areas(bottom);
+        let block = |title: &'static str| Block::default().borders(Borders::ALL).title(title);
+        f.render_widget(
+            Paragraph::new(screen_lines(self.chip.framebuffer())).block(block("screen")),
+            screen,
+        );
+        f.render_widget(
+            Paragraph::new(self.register_lines()).block(block("registers")),
+            registers,
+        );
+        f.render_widget(
+            Paragraph::new(self.stack_lines()).block(block("stack")),
+            stack,
+        );
+        let rows = code.height.saturating_sub(2) as usize;
+        f.render_widget(
+            Paragraph::new(self.disassembly(rows)).block(block("code")),
+            code,
+        );
+        let map_rows = self.chip.ram.buf.len() / memmap::CELL / memmap::ROW;
+        let [map, ram] =
+            Layout::vertical([Constraint::Length(map_rows as u16 + 2), Constraint::Min(3)])
+                .areas(ram);
+        f.render_widget(Paragraph::new(self.map_lines()).block(block("map")), map);
+        let rows = ram.height.saturating_sub(2) as usize;
+        f.render_widget(Paragraph::new(self.hexdump(rows)).block(block("ram")), ram);
+        let mut text = format!("cycle {}  {}", self.cycle, self.status);
+        if let Some(step) = &self.last {
+            if let Some(op) = step.opcode {
+                text += &format!("  last: {:03x} {}", step.addr, op);
+            }
+        }
+        f.render_widget(Paragraph::new(text), status);
+    }
+
+    fn register_lines(&self) -> Vec<Line<'static>> {
+        let r = self.chip.registers();
+        let mut lines: Vec<Line> =
+            r.v.chunks(4)
+                .enumerate()
+                .map(|(row, vs)| {
+                    let cells: Vec<String> = vs
+                        .iter()
+                        .enumerate()
+                        .map(|(n, v)| format!("V{:X}={:02x}", row * 4 + n, v))
+                        .collect();
+                    Line::from(cells.join(" "))
+                })
+                .collect();
+        lines.push(Line::from(format!("I={:03x}  PC={:03x}", r.i, r.pc)));
+        lines.push(Line::from(format!(
+            "DT={:02x}  ST={:02x}  SP={}",
+            r.dt, r.st, r.sp
+        )));
+        lines
+    }
+
+    fn stack_lines(&self) -> Vec<Line<'static>> {
+        let r = self.chip.registers();
+        r.stack[..r.sp as usize]
+            .iter()
+            .rev()
+            .map(|a| Line::from(format!("{:03x}", a)))
+            .collect()
+    }
+
+    // `rows` instructions around the cursor: `>` marks PC and `*` breakpoints.
+    fn disassembly(&self, rows: usize) -> Vec<Line<'static>> {
+        let ram = &self.chip.ram.buf;
+        let start = (self.cursor as usize).saturating_sub(rows / 2 * 2);
+        let end = (start + rows * 2).min(ram.len());
+        let pc = self.chip.pc();
+        let mut breakpoints: Vec<u16> = self.chip.breakpoints().collect();
+        breakpoints.extend(self.conditional.list().iter().map(|b| b.1));
+        disasm::disassemble(&ram[start..end], start as u16)
+            .take(rows)
+            .map(|l| {
+                let mark = match (l.addr == pc, breakpoints.contains(&l.addr)) {
+                    (true, true) => ">*",
+                    (true, false) => "> ",
+                    (false, true) => " *",
+                    (false, false) => "  ",
+                };
+                let line = Line::from(format!("{}{}", mark, l));
+                match l.addr == self.cursor {
+                    true => line.style(Style::default().add_modifier(Modifier::REVERSED)),
+                    false => line,
+                }
+            })
+            .collect()
+    }
+
+    // a row of cells per `memmap::ROW * CELL` bytes: the letter of the region in its color, bold
+    // when accessed by the last step or frame; the selected cell is highlighted.
+    fn map_lines(&self) -> Vec<Line<'static>> {
+        let cells: Vec<Span> = memmap::cells(self.chip)
+            .enumerate()
+            .map(|(n, start)| {
+                let region = Region::of(self.chip, start);
+                let color = match region {
+                    Region::Pc | Region::I => Color::Yellow,
+                    Region::Written => Color::Red,
+                    Region::Code => Color::Green,
+                    Region::Data => Color::Cyan,
+                    Region::Font => Color::Magenta,
+                    Region::Untouched => Color::DarkGray,
+                };
+                let mut style = Style::default().fg(color);
+                if memmap::recent(self.chip, start) {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                if self.map_focus && n == self.map_cell {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Span::styled(region.letter().to_string(), style)
+            })
+            .collect();
+        cells
+            .chunks(memmap::ROW)
+            .map(|row| Line::from(row.to_vec()))
+            .collect()
+    }
+
+    fn hexdump(&self, rows: usize) -> Vec<Line<'static>> {
+        let ram = &self.chip.ram.buf;
+        (self.hexdump_at..ram.len())
+            .step_by(HEXDUMP_ROW)
+            .take(rows)
+            .map(|a| {
+                let bytes: Vec<String> = ram[a..a + HEXDUMP_ROW]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                Line::from(format!("{:03x}: {}", a, bytes.join(" ")))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,270 @@
+// SDL2 frontend: a window scaled to any size, real key releases and a square-wave beep.
+//
+// Keys: the 4x4 block 1234/qwer/asdf/zxcv (by position, whatever the keyboard layout) is the
+// CHIP-8 keypad; M mutes and unmutes the beep, Esc quits.
+use chip8::{Chip, FrameBuffer, Keypad, Palette};
+use clap::Parser;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::Scancode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use std::fs::File;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// ROM file
+    #[clap(short, long)]
+    rom: String,
+    /// instructions per second (defaults to the platform's speed)
+    #[clap(short, long)]
+    cpu_hz: Option<u32>,
+    /// initial window size in window pixels per CHIP-8 pixel (the window can be resized)
+    #[clap(long, default_value = "10")]
+    scale: u32,
+    /// start muted (M toggles the beep)
+    #[clap(long)]
+    mute: bool,
+    /// pitch of the beep
+    #[clap(long, default_value = "440")]
+    beep_hz: f32,
+    /// loudness of the beep in percent of full scale
+    #[clap(long, default_value = "10")]
+    volume: u8,
+    /// seed of the random numbers (CXNN), for reproducible runs
+    #[clap(long)]
+    seed: Option<u64>,
+    /// colors for background, plane 1, plane 2 and both planes (`#rrggbb,...` as in Octo)
+    #[clap(long)]
+    palette: Option<Palette>,
+    /// quirks to enable: vf-reset, load-store, shift, jump, clipping, display-wait, half-scroll, index-overflow, row-collisions (comma separated)
+    #[clap(long)]
+    quirks: Option<chip8::Quirks>,
+    /// interpreter to emulate: chip8, vip, schip, xochip, eti-660, dream-6800 (program start, screen size, font, speed and quirks)
+    #[clap(long)]
+    platform: Option<chip8::Platform>,
+}
+
+const FRAME: Duration = Duration::from_micros(16_667);
+const MUTE: Scancode = Scancode::M;
+
+// the CHIP-8 hex keypad by the position of the keys on a QWERTY keyboard.
+const KEYPAD: [(Scancode, u8); 16] = [
+    (Scancode::Num1, 0x1),
+    (Scancode::Num2, 0x2),
+    (Scancode::Num3, 0x3),
+    (Scancode::Num4, 0xC),
+    (Scancode::Q, 0x4),
+    (Scancode::W, 0x5),
+    (Scancode::E, 0x6),
+    (Scancode::R, 0xD),
+    (Scancode::A, 0x7),
+    (Scancode::S, 0x8),
+    (Scancode::D, 0x9),
+    (Scancode::F, 0xE),
+    (Scancode::Z, 0xA),
+    (Scancode::X, 0x0),
+    (Scancode::C, 0xB),
+    (Scancode::V, 0xF),
+];
+
+fn keypad_key(code: Scancode) -> Option<u8> {
+    KEYPAD.iter().find(|(c, _)| *c == code).map(|(_, k)| *k)
+}
+
+struct Window {
+    canvas: WindowCanvas,
+    palette: Palette,
+}
+
+impl chip8::Display for Window {
+    // the renderer scales the logical screen to the window, keeping the aspect ratio.
+    fn present(&mut self, frame: &FrameBuffer) {
+        let (w, h) = (frame.width() as u32, frame.height() as u32);
+        if self.canvas.logical_size() != (w, h) {
+            let _ = self.canvas.set_logical_size(w, h);
+        }
+        let rgb = |i: usize| {
+            let c = self.palette.0[i];
+            Color::RGB(c.0, c.1, c.2)
+        };
+        self.canvas.set_draw_color(rgb(0));
+        self.canvas.clear();
+        for color in 1..self.palette.0.len() {
+            let rects: Vec<Rect> = (0..frame.height())
+                .flat_map(|y| (0..frame.width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| frame.pixel(x, y) as usize == color)
+                .map(|(x, y)| Rect::new(x as i32, y as i32, 1, 1))
+                .collect();
+            if !rects.is_empty() {
+                self.canvas.set_draw_color(rgb(color));
+                let _ = self.canvas.fill_rects(&rects);
+            }
+        }
+        self.canvas.present();
+    }
+    fn set_palette(&mut self, palette: &Palette) {
+        self.palette = *palette;
+    }
+}
+
+struct SquareWave {
+    phase: f32,
+    step: f32,
+    amplitude: f32,
+    // set by the mute hotkey; the wave keeps its phase while silent
+    muted: Arc<AtomicBool>,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+    fn callback(&mut self, out: &mut [f32]) {
+        let amplitude = match self.muted.load(Ordering::Relaxed) {
+            true => 0.0,
+            false => self.amplitude,
+        };
+        for s in out.iter_mut() {
+            *s = if self.phase < 0.5 {
+                amplitude
+            } else {
+                -amplitude
+            };
+            self.phase = (self.phase + self.step) % 1.0;
+        }
+    }
+}
+
+struct Beeper(AudioDevice<SquareWave>);
+
+impl chip8::Audio for Beeper {
+    fn start_beep(&mut self) {
+        self.0.resume();
+    }
+    fn stop_beep(&mut self) {
+        self.0.pause();
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let mut rom = Vec::new();
+    File::open(&args.rom)
+        .and_then(|mut f| f.read_to_end(&mut rom))
+        .unwrap_or_else(|e| panic!("{}: {}", args.rom, e));
+    let platform = args.platform.unwrap_or_default();
+    let cpu_hz = args.cpu_hz.unwrap_or(platform.cpu_hz);
+
+    let sdl = sdl2::init().unwrap();
+    let video = sdl.video().unwrap();
+    let window = video
+        .window(
+            "rusty-chip8",
+            chip8::WIDTH as u32 * args.scale,
+            chip8::HEIGHT as u32 * args.scale,
+        )
+        .position_centered()
+        .resizable()
+        .build()
+        .unwrap();
+    let canvas = window.into_canvas().build().unwrap();
+    let display = Window {
+        canvas,
+        palette: Palette::default(),
+    };
+    let keypad = Keypad::new();
+    let mut chip = Chip::new(Box::new(display), Box::new(keypad.clone()));
+    chip.set_platform(&platform);
+    chip.set_quirks(args.quirks.unwrap_or(platform.quirks));
+    if let Some(p) = &args.palette {
+        chip.set_palette(p);
+    }
+    if let Some(s) = args.seed {
+        chip.set_seed(s);
+    }
+    let muted = Arc::new(AtomicBool::new(args.mute));
+    let spec = AudioSpecDesired {
+        freq: Some(44_100),
+        channels: Some(1),
+        samples: None,
+    };
+    let wave = |spec: sdl2::audio::AudioSpec| SquareWave {
+        phase: 0.0,
+        step: args.beep_hz / spec.freq as f32,
+        amplitude: args.volume.min(100) as f32 / 100.0,
+        muted: muted.clone(),
+    };
+    match sdl
+        .audio()
+        .and_then(|audio| audio.open_playback(None, &spec, wave))
+    {
+        Ok(device) => chip.set_audio(Box::new(Beeper(device))),
+        Err(e) => eprintln!("no audio: {}", e),
+    }
+    if let Err(e) = chip.load_slice(&rom) {
+        eprintln!("{}: {}", args.rom, e);
+        std::process::exit(1);
+    }
+
+    let mut events = sdl.event_pump().unwrap();
+    let cycles_per_frame = (cpu_hz / 60).max(1);
+    let mut next_frame = Instant::now();
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    scancode: Some(Scancode::Escape),
+                    ..
+                } => return,
+                Event::KeyDown {
+                    scancode: Some(MUTE),
+                    repeat: false,
+                    ..
+                } => {
+                    muted.fetch_xor(true, Ordering::Relaxed);
+                }
+                Event::KeyDown {
+                    scancode: Some(code),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(k) = keypad_key(code) {
+                        keypad.press(k);
+                    }
+                }
+                Event::KeyUp {
+                    scancode: Some(code),
+                    ..
+                } => {
+                    if let Some(k) = keypad_key(code) {
+                        keypad.release(k);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for _ in 0..cycles_per_frame {
+            match chip.step() {
+                Ok(step) if step.opcode == Some(chip8::opcode::Opcode::Exit) => return,
+                Ok(_) => {}
+                Err(trap) => {
+                    eprintln!("{}", trap);
+                    std::process::exit(1);
+                }
+            }
+        }
+        chip.tick_60hz();
+        next_frame += FRAME;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::script::{self, Action, Event};
+use chip8::Chip;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+
+// executed instructions kept for the report
+const TRACE_LEN: usize = 256;
+const BLOCK: usize = 512;
+
+// collects what is needed to reproduce a run: settings, the last instructions and the key
+// events as a replay, and writes them with the machine state into a tar archive.
+pub struct Collector {
+    settings: Vec<(&'static str, String)>,
+    // (cycle, pc, opcode)
+    trace: VecDeque<(u64, u16, u16)>,
+    events: Vec<Event>,
+    // keys down before the last observed cycle, one bit per key
+    pressed: u16,
+    cycle: u64,
+}
+
+impl Collector {
+    pub fn new(settings: Vec<(&'static str, String)>) -> Self {
+        Collector {
+            settings,
+            trace: VecDeque::with_capacity(TRACE_LEN),
+            events: Vec::new(),
+            pressed: 0,
+            cycle: 0,
+        }
+    }
+    // records the instruction about to be executed and the key changes since the last cycle.
+    pub fn observe(&mut self, cycle: u64, chip: &Chip) {
+        self.cycle = cycle;
+        let pc = chip.pc();
+        let op = match chip.ram.buf.get(pc as usize..pc as usize + 2) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]),
+            None => 0,
+        };
+        if self.trace.len() == TRACE_LEN {
+            self.trace.pop_front();
+        }
+        self.trace.push_back((cycle, pc, op));
+        let pressed = chip.keyboard.state();
+        self.events
+            .extend(script::key_changes(cycle, self.pressed, pressed));
+        self.pressed = pressed;
+    }
+    // writes report.txt, rom.ch8, trace.txt, state.txt, ram.bin, screen.pbm and replay.txt into
+    // `path`.
+    pub fn write(&self, path: &str, chip: &Chip, rom: &[u8], reason: &str) -> io::Result<()> {
+        let mut report = String::new();
+        let _ = writeln!(report, "reason: {}", reason);
+        let _ = writeln!(report, "cycle: {}", self.cycle);
+        let _ = writeln!(report, "version: {}", env!("CARGO_PKG_VERSION"));
+        for (k, v) in &self.settings {
+            let _ = writeln!(report, "{}: {}", k, v);
+        }
+        let mut trace = String::new();
+        for (cycle, pc, op) in &self.trace {
+            let _ = writeln!(trace, "{:>10} {:03x} {:04x}", cycle, pc, op);
+        }
+        let st = chip.save_state();
+        let mut state = String::new();
+        let _ = writeln!(state, "v: {:02x?}", st.v);
+        let _ = writeln!(state, "i: {:03x} pc: {:03x} sp: {}", st.i, st.pc, st.sp);
+        let _ = writeln!(state, "stack: {:03x?}", st.stack);
+        let _ = writeln!(state, "dt: {} st: {}", st.dt, st.st);
+        let _ = writeln!(state, "hires: {} halted: {}", st.hires, st.halted);
+        let _ = writeln!(state, "rpl: {:02x?}", st.rpl);
+        let _ = writeln!(state, "state hash: {:016x}", chip.state_hash());
+        let mut replay = String::new();
+        let seed = self.settings.iter().find(|(k, _)| *k == "seed");
+        if let Some((_, s)) = seed {
+            let _ = writeln!(replay, "# recorded with --seed {}", s);
+        }
+        for ev in &self.events {
+            let _ = writeln!(replay, "{}", ev);
+        }
+        let end = Event {
+            cycle: self.cycle,
+            action: Action::Hash(chip.state_hash()),
+        };
+        let _ = writeln!(replay, "{}", end);
+
+        let mut out = File::create(path)?;
+        tar_entry(&mut out, "report.txt", report.as_bytes())?;
+        tar_entry(&mut out, "rom.ch8", rom)?;
+        tar_entry(&mut out, "trace.txt", trace.as_bytes())?;
+        tar_entry(&mut out, "state.txt", state.as_bytes())?;
+        tar_entry(&mut out, "ram.bin", &st.ram)?;
+        tar_entry(&mut out, "screen.pbm", &chip.framebuffer().to_pbm())?;
+        tar_entry(&mut out, "replay.txt", replay.as_bytes())?;
+        out.write_all(&[0; BLOCK * 2])
+    }
+}
+
+// writes a file of a ustar archive.
+fn tar_entry(out: &mut dyn Write, name: &str, data: &[u8]) -> io::Result<()> {
+    let mut h = [0u8; BLOCK];
+    let mut field =
+        |offset: usize, value: &[u8]| h[offset..offset + value.len()].copy_from_slice(value);
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", data.len()).as_bytes());
+    field(136, b"00000000000\0");
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    let sum: u32 = h.iter().map(|b| *b as u32).sum();
+    h[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+    out.write_all(&h)?;
+    out.write_all(data)?;
+    out.write_all(&vec![0; (BLOCK - data.len() % BLOCK) % BLOCK])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::{Keypad, NullDisplay};
+
+    #[test]
+    fn observing_keys_leaves_taps_to_the_machine() {
+        let keypad = Keypad::new();
+        let mut chip = Chip::new(Box::new(NullDisplay), Box::new(keypad.clone()));
+        // SKP V0
+        chip.load_slice(&[0xE0, 0x9E]).unwrap();
+        let mut c = Collector::new(Vec::new());
+        keypad.press(0);
+        keypad.release(0);
+        c.observe(0, &chip);
+        chip.step().unwrap();
+        assert_eq!(chip.pc(), 0x204);
+        assert_eq!(c.events.len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+// images of the screen: screenshots (F2) as PNG, or PPM/PBM by file extension, and animated GIF
+// recordings (--record).
+use chip8::filter::Frame;
+use chip8::{FrameBuffer, Palette};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn frame(fb: &FrameBuffer, palette: &Palette) -> Frame {
+    Frame::from_indexed(fb.width(), fb.height(), |x, y| fb.pixel(x, y), palette)
+}
+
+// writes the screen to `path`: `.pbm` (black on white), `.ppm` or otherwise PNG, in `palette`.
+pub fn write_image(path: &Path, fb: &FrameBuffer, palette: &Palette) -> io::Result<()> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("pbm") => std::fs::write(path, fb.to_pbm()),
+        Some("ppm") => std::fs::write(path, frame(fb, palette).to_ppm()),
+        _ => frame(fb, palette)
+            .write_png(BufWriter::new(File::create(path)?))
+            .map_err(io::Error::other),
+    }
+}
+
+// writes `rusty-chip8-<unix time in ms>.png` into `dir` and returns its path.
+pub fn screenshot(dir: &Path, fb: &FrameBuffer, palette: &Palette) -> io::Result<PathBuf> {
+    let ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let path = dir.join(format!("rusty-chip8-{}.png", ms));
+    write_image(&path, fb, palette)?;
+    Ok(path)
+}
+
+// width of recordings; the screen is scaled by a whole factor up to it
+const GIF_WIDTH: usize = 512;
+// GIF delays are in 1/100 s and most viewers slow down frames shorter than 2/100 s
+const MIN_DELAY: u64 = 2;
+
+// one distinct screen of a recording and the frame it appeared in.
+struct Shot {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    start: u64,
+}
+
+// collects the screen of every 60Hz frame (`Chip::set_frame_hook`) for an animated GIF; repeated
+// screens are merged into one longer GIF frame.
+pub struct GifRecorder {
+    palette: Palette,
+    shots: Vec<Shot>,
+    frames: u64,
+}
+
+// the time of `frame` in 1/100 s.
+fn centis(frame: u64) -> u64 {
+    frame * 100 / 60
+}
+
+impl GifRecorder {
+    pub fn new(palette: Palette) -> Self {
+        GifRecorder {
+            palette,
+            shots: Vec::new(),
+            frames: 0,
+        }
+    }
+    pub fn push(&mut self, fb: &FrameBuffer) {
+        let frame = self.frames;
+        self.frames += 1;
+        let shot = Shot {
+            width: fb.width(),
+            height: fb.height(),
+            pixels: fb.pixels().to_vec(),
+            start: frame,
+        };
+        match self.shots.last_mut() {
+            Some(last) if last.pixels == shot.pixels && last.width == shot.width => {}
+            // too short to show: the newer screen replaces it
+            Some(last) if centis(frame) - centis(last.start) < MIN_DELAY => {
+                *last = Shot {
+                    start: last.start,
+                    ..shot
+                }
+            }
+            _ => self.shots.push(shot),
+        }
+    }
+    // writes the recorded frames to `path`, looping forever.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let width = self.shots.iter().map(|s| s.width).max().unwrap_or(1);
+        let height = self.shots.iter().map(|s| s.height).max().unwrap_or(1);
+        let scale = (GIF_WIDTH / width).max(1);
+        let (w, h) = (width * scale, height * scale);
+        let colors: Vec<u8> = self
+            .palette
+            .0
+            .iter()
+            .flat_map(|c| [c.0, c.1, c.2])
+            .collect();
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder =
+            gif::Encoder::new(file, w as u16, h as u16, &colors).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        for (i, shot) in self.shots.iter().enumerate() {
+            let end = self.shots.get(i + 1).map_or(self.frames, |s| s.start);
+            // lores and hires screens fill the same area
+            let mut buffer = Vec::with_capacity(w * h);
+            for y in 0..h {
+                let row = y * shot.height / h * shot.width;
+                buffer.extend((0..w).map(|x| shot.pixels[row + x * shot.width / w] & 3));
+            }
+            let frame = gif::Frame {
+                width: w as u16,
+                height: h as u16,
+                delay: (centis(end) - centis(shot.start)).max(MIN_DELAY) as u16,
+                buffer: Cow::Owned(buffer),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
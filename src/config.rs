@@ -0,0 +1,350 @@
+use crate::recent::config_dir;
+use chip8::keymap::KeyMap;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "config.toml";
+
+// named palettes (`--palette` syntax); `default` keeps the terminal colors.
+pub const THEMES: [(&str, Option<&str>); 5] = [
+    ("default", None),
+    ("amber", Some("#1a0f00,#ffb000")),
+    ("green", Some("#001400,#33ff66")),
+    ("lcd", Some("#9bbc0f,#0f380f")),
+    ("paper", Some("#f4f1e8,#202020,#8a8a8a,#505050")),
+];
+
+// checks a theme name (`--theme`).
+pub fn theme_name(name: &str) -> Result<String, String> {
+    match THEMES.iter().find(|(n, _)| *n == name) {
+        Some((n, _)) => Ok(n.to_string()),
+        None => {
+            let names: Vec<&str> = THEMES.iter().map(|(n, _)| *n).collect();
+            Err(format!(
+                "unknown theme `{}` (one of {})",
+                name,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+// the colors of a theme; None keeps the terminal colors.
+pub fn theme_palette(name: &str) -> Option<chip8::Palette> {
+    THEMES
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, p)| p.and_then(|p| p.parse().ok()))
+}
+
+// how the sound timer shows: the terminal bell at the start of a beep, a note in the status
+// line while it lasts, both or neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Sound {
+    Bell,
+    Note,
+    Both,
+    Off,
+}
+
+impl Sound {
+    pub fn bell(self) -> bool {
+        matches!(self, Sound::Bell | Sound::Both)
+    }
+    pub fn note(self) -> bool {
+        matches!(self, Sound::Note | Sound::Both)
+    }
+    // the sound without the bell (`--mute`).
+    pub fn muted(self) -> Sound {
+        match self {
+            Sound::Bell | Sound::Off => Sound::Off,
+            Sound::Note | Sound::Both => Sound::Note,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Sound::Bell => "bell",
+            Sound::Note => "note",
+            Sound::Both => "both",
+            Sound::Off => "off",
+        }
+    }
+}
+
+// defaults written by the first-run setup, used when neither the command line nor the settings
+// remembered for a ROM say otherwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    // where `-r` looks for ROMs given by name, and which ROMs are offered without `-r`
+    pub rom_dir: Option<String>,
+    // a KeyMap preset or keymap file
+    pub layout: String,
+    pub theme: String,
+    pub cpu_hz: u32,
+    pub keeptime_ms: u16,
+    // the quirks profile: a platform, and quirks replacing the platform's
+    pub platform: Option<chip8::Platform>,
+    pub quirks: Option<chip8::Quirks>,
+    // colors replacing the theme's
+    pub palette: Option<chip8::Palette>,
+    pub sound: Sound,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rom_dir: None,
+            layout: KeyMap::PRESETS[0].to_string(),
+            theme: THEMES[0].0.to_string(),
+            cpu_hz: 600,
+            keeptime_ms: 100,
+            platform: None,
+            quirks: None,
+            palette: None,
+            sound: Sound::Both,
+        }
+    }
+}
+
+impl Config {
+    pub fn layout(&self) -> KeyMap {
+        self.layout.parse().unwrap_or_else(|e| {
+            log::warn!("{}", e);
+            KeyMap::default()
+        })
+    }
+    pub fn palette(&self) -> Option<chip8::Palette> {
+        self.palette.or_else(|| theme_palette(&self.theme))
+    }
+    // resolves a ROM given by name against `rom_dir` when it is not a path to a file.
+    pub fn rom_path(&self, rom: &str) -> String {
+        match &self.rom_dir {
+            Some(dir) if !Path::new(rom).is_file() && Path::new(dir).join(rom).is_file() => {
+                Path::new(dir).join(rom).to_string_lossy().into_owned()
+            }
+            _ => rom.to_string(),
+        }
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(FILE_NAME))
+}
+
+// reads the config; None before the first-run setup. Bad values are skipped with a warning.
+pub fn load() -> Option<Config> {
+    let path = path()?;
+    let text = fs::read_to_string(&path).ok()?;
+    let (c, errors) = parse(&text);
+    for e in errors {
+        log::warn!("{}: {}", path.display(), e);
+    }
+    Some(c)
+}
+
+// reads a config given with `--config`, refusing bad values.
+pub fn load_file(path: &str) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    match parse(&text) {
+        (c, errors) if errors.is_empty() => Ok(c),
+        (_, errors) => Err(format!("{}: {}", path, errors.join("; "))),
+    }
+}
+
+// the config file as written: TOML keys for the fields of `Config`, the typed ones as their
+// command line spelling.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rom_dir: Option<String>,
+    layout: Option<String>,
+    theme: Option<String>,
+    cpu_hz: Option<u32>,
+    keeptime_ms: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quirks: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    palette: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sound: Option<String>,
+}
+
+// parses a TOML config over the defaults; returns the errors of the keys it skipped.
+fn parse(text: &str) -> (Config, Vec<String>) {
+    let mut c = Config::default();
+    let f: File = match toml::from_str(text) {
+        Ok(f) => f,
+        Err(e) => {
+            let line = e
+                .span()
+                .map_or(1, |s| text[..s.start].matches('\n').count() + 1);
+            return (c, vec![format!("line {}: {}", line, e.message().trim())]);
+        }
+    };
+    let mut errors = Vec::new();
+    let mut check = |key: &str, set: Result<(), String>| {
+        if let Err(e) = set {
+            errors.push(format!("{}: {}", key, e));
+        }
+    };
+    c.rom_dir = f.rom_dir;
+    if let Some(layout) = f.layout {
+        c.layout = layout;
+    }
+    if let Some(theme) = f.theme {
+        check("theme", theme_name(&theme).map(|t| c.theme = t));
+    }
+    c.cpu_hz = f.cpu_hz.unwrap_or(c.cpu_hz);
+    c.keeptime_ms = f.keeptime_ms.unwrap_or(c.keeptime_ms);
+    if let Some(p) = f.platform {
+        check("platform", p.parse().map(|p| c.platform = Some(p)));
+    }
+    if let Some(q) = f.quirks {
+        check("quirks", q.parse().map(|q| c.quirks = Some(q)));
+    }
+    if let Some(p) = f.palette {
+        check("palette", p.parse().map(|p| c.palette = Some(p)));
+    }
+    if let Some(s) = f.sound {
+        check(
+            "sound",
+            <Sound as clap::ArgEnum>::from_str(&s, false).map(|s| c.sound = s),
+        );
+    }
+    (c, errors)
+}
+
+fn to_toml(c: &Config) -> String {
+    let f = File {
+        rom_dir: c.rom_dir.clone(),
+        layout: Some(c.layout.clone()),
+        theme: Some(c.theme.clone()),
+        cpu_hz: Some(c.cpu_hz),
+        keeptime_ms: Some(c.keeptime_ms),
+        platform: c.platform.map(|p| p.name.to_string()),
+        quirks: c.quirks.map(|q| q.to_string()),
+        palette: c.palette.map(|p| p.to_string()),
+        sound: (c.sound != Sound::Both).then(|| c.sound.name().to_string()),
+    };
+    toml::to_string(&f).expect("the config is plain strings and numbers")
+}
+
+pub fn save(c: &Config) -> io::Result<()> {
+    let path = match path() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, to_toml(c))
+}
+
+// asks for the ROM directory, key layout, color theme and default speed on the terminal; the
+// other keys are kept from `previous`.
+pub fn wizard(previous: Config) -> Config {
+    let mut c = Config {
+        rom_dir: None,
+        ..previous
+    };
+    println!(
+        "rusty-chip8 setup (written to {:?})",
+        path().unwrap_or_default()
+    );
+    let dir = ask("ROM directory (empty for none)", "");
+    if !dir.is_empty() {
+        c.rom_dir = Some(dir);
+    }
+    let names = |l: &[&str]| l.join("/");
+    c.layout = choose("key layout", &KeyMap::PRESETS, &names(&KeyMap::PRESETS));
+    let themes: Vec<&str> = THEMES.iter().map(|(n, _)| *n).collect();
+    c.theme = choose("color theme", &themes, &names(&themes));
+    c.cpu_hz = loop {
+        match ask("default speed (instructions per second)", "600").parse() {
+            Ok(hz) if hz > 0 => break hz,
+            _ => println!("a positive number, please"),
+        }
+    };
+    c
+}
+
+// lists the files of `dir` and asks for one of them.
+pub fn pick_rom(dir: &str) -> Option<String> {
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+    roms.sort();
+    for (n, p) in roms.iter().enumerate() {
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        println!("{:>2}) {}", n + 1, name);
+    }
+    let n: usize = ask("select", "1").parse().ok()?;
+    roms.get(n.checked_sub(1)?)
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+fn choose(what: &str, options: &[&str], names: &str) -> String {
+    loop {
+        let answer = ask(&format!("{} ({})", what, names), options[0]);
+        if options.contains(&answer.as_str()) {
+            return answer;
+        }
+        println!("one of {}, please", names);
+    }
+}
+
+fn ask(question: &str, default: &str) -> String {
+    print!("{} [{}]: ", question, default);
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    let _ = io::stdin().lock().read_line(&mut line);
+    match line.trim() {
+        "" => default.to_string(),
+        s => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_configs_parse_back() {
+        let c = Config {
+            rom_dir: Some(r#"C:\Users\me\"roms""#.to_string()),
+            theme: "amber".to_string(),
+            cpu_hz: 1200,
+            platform: Some(chip8::Platform::SCHIP),
+            quirks: "shift,jump".parse().ok(),
+            palette: "#000000,#ffffff".parse().ok(),
+            sound: Sound::Off,
+            ..Config::default()
+        };
+        assert_eq!(parse(&to_toml(&c)), (c, Vec::new()));
+        assert_eq!(parse(&to_toml(&Config::default())).0, Config::default());
+    }
+
+    #[test]
+    fn reads_comments_and_skips_bad_values() {
+        let (c, errors) =
+            parse("# written by hand\ncpu_hz = 900 # faster\ntheme = 'green'\nsound = \"loud\"\n");
+        assert_eq!((c.cpu_hz, c.theme.as_str()), (900, "green"));
+        assert_eq!(c.sound, Sound::Both);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("sound: "), "{:?}", errors);
+        let (c, errors) = parse("cpu_hz = 900\nspeed = 2\n");
+        assert_eq!(c, Config::default());
+        assert!(
+            errors[0].starts_with("line 2: unknown field `speed`"),
+            "{:?}",
+            errors
+        );
+    }
+}
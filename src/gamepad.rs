@@ -0,0 +1,118 @@
+// gamepad input (`gamepad` feature): buttons and the d-pad of any connected controller hold
+// keypad keys until they are released.
+use gilrs::{Button, EventType, Gilrs};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// how often connected controllers are polled
+const POLL: Duration = Duration::from_millis(5);
+
+// names of the buttons in `--gamepad-map`.
+const BUTTONS: [(&str, Button); 17] = [
+    ("south", Button::South),
+    ("east", Button::East),
+    ("north", Button::North),
+    ("west", Button::West),
+    ("l1", Button::LeftTrigger),
+    ("l2", Button::LeftTrigger2),
+    ("r1", Button::RightTrigger),
+    ("r2", Button::RightTrigger2),
+    ("select", Button::Select),
+    ("start", Button::Start),
+    ("mode", Button::Mode),
+    ("l3", Button::LeftThumb),
+    ("r3", Button::RightThumb),
+    ("up", Button::DPadUp),
+    ("down", Button::DPadDown),
+    ("left", Button::DPadLeft),
+    ("right", Button::DPadRight),
+];
+
+// the d-pad on 2/8/4/6 (the arrows of most games), the face buttons on 5 (often fire), 4, 6
+// and 0xA, start on 0xF.
+const DEFAULT: [(Button, u8); 9] = [
+    (Button::DPadUp, 0x2),
+    (Button::DPadDown, 0x8),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::South, 0x5),
+    (Button::East, 0x6),
+    (Button::West, 0x4),
+    (Button::North, 0xA),
+    (Button::Start, 0xF),
+];
+
+// buttons and the keypad keys they hold.
+#[derive(Debug, Clone)]
+pub struct Mapping(Vec<(Button, u8)>);
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Mapping(DEFAULT.to_vec())
+    }
+}
+
+impl std::str::FromStr for Mapping {
+    type Err = String;
+    // parses comma separated `button=key` pairs (e.g. `south=5,up=2`) over the default mapping;
+    // `button=-` unmaps a button.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut m = Mapping::default();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, key) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected button=key: {}", pair))?;
+            let button = BUTTONS
+                .iter()
+                .find(|(n, _)| *n == name.trim())
+                .map(|(_, b)| *b)
+                .ok_or_else(|| format!("unknown button: {}", name))?;
+            m.0.retain(|(b, _)| *b != button);
+            if key.trim() == "-" {
+                continue;
+            }
+            let key = u8::from_str_radix(key.trim(), 16)
+                .ok()
+                .filter(|k| *k < 0x10)
+                .ok_or_else(|| format!("invalid key: {}", key))?;
+            m.0.push((button, key));
+        }
+        Ok(m)
+    }
+}
+
+impl Mapping {
+    fn key(&self, button: Button) -> Option<u8> {
+        self.0.iter().find(|(b, _)| *b == button).map(|(_, k)| *k)
+    }
+}
+
+// polls the controllers on a thread until `stopper` is stopped, calling `hold(key, down)` for
+// mapped buttons. Without controller support (e.g. no udev) it only prints why.
+pub fn spawn<F>(mapping: Mapping, stopper: chip8::Stopper, mut hold: F) -> JoinHandle<()>
+where
+    F: FnMut(u8, bool) + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(g) => g,
+            Err(e) => {
+                log::warn!("no gamepad support: {}", e);
+                return;
+            }
+        };
+        while !stopper.is_stopped() {
+            while let Some(ev) = gilrs.next_event() {
+                let (button, down) = match ev.event {
+                    EventType::ButtonPressed(b, _) => (b, true),
+                    EventType::ButtonReleased(b, _) => (b, false),
+                    _ => continue,
+                };
+                if let Some(k) = mapping.key(button) {
+                    hold(k, down);
+                }
+            }
+            thread::sleep(POLL);
+        }
+    })
+}
@@ -0,0 +1,127 @@
+use crate::recent::config_dir;
+use chip8::keymap::KeyMap;
+use chip8::{Palette, Platform, Quirks};
+use std::fs;
+use std::io;
+
+const FILE_NAME: &str = "games.tsv";
+
+// the settings a ROM was last played with, reapplied when it is played again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub cpu_hz: u32,
+    pub keeptime_ms: u16,
+    pub quirks: Quirks,
+    pub palette: Option<Palette>,
+    pub platform: Platform,
+    // a keymap given for the ROM; None follows the setup's layout
+    pub keymap: Option<KeyMap>,
+}
+
+// settings of every ROM played so far, keyed by `recent::rom_hash`.
+fn load_all() -> Vec<(u64, Settings)> {
+    let text = match config_dir().map(|d| fs::read_to_string(d.join(FILE_NAME))) {
+        Some(Ok(t)) => t,
+        _ => return Vec::new(),
+    };
+    text.lines().filter_map(parse).collect()
+}
+
+fn parse(line: &str) -> Option<(u64, Settings)> {
+    let f: Vec<&str> = line.split('\t').collect();
+    // the platform and the keymap were added later; older lines have 5 or 6 fields.
+    if !(5..=7).contains(&f.len()) {
+        return None;
+    }
+    let settings = Settings {
+        cpu_hz: f[1].parse().ok()?,
+        keeptime_ms: f[2].parse().ok()?,
+        quirks: f[3].parse().ok()?,
+        palette: match f[4] {
+            "-" => None,
+            p => Some(p.parse().ok()?),
+        },
+        platform: match f.get(5) {
+            Some(p) => p.parse().ok()?,
+            None => Platform::default(),
+        },
+        keymap: match f.get(6) {
+            None | Some(&"-") => None,
+            Some(k) => Some(
+                KeyMap::preset(k)
+                    .map_or_else(|| KeyMap::parse(k), Ok)
+                    .ok()?,
+            ),
+        },
+    };
+    Some((u64::from_str_radix(f[0], 16).ok()?, settings))
+}
+
+fn line(hash: u64, s: &Settings) -> String {
+    let palette = s.palette.map_or("-".to_string(), |p| p.to_string());
+    let keymap = s.keymap.as_ref().map_or("-".to_string(), |k| k.to_string());
+    format!(
+        "{:016x}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        hash, s.cpu_hz, s.keeptime_ms, s.quirks, palette, s.platform.name, keymap
+    )
+}
+
+fn save_all(games: &[(u64, Settings)]) -> io::Result<()> {
+    let dir = match config_dir() {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+    fs::create_dir_all(&dir)?;
+    let text: String = games.iter().map(|(hash, s)| line(*hash, s)).collect();
+    fs::write(dir.join(FILE_NAME), text)
+}
+
+pub fn load(hash: u64) -> Option<Settings> {
+    load_all()
+        .into_iter()
+        .find(|(h, _)| *h == hash)
+        .map(|(_, s)| s)
+}
+
+// remembers `settings` for the ROM, replacing what was saved before.
+pub fn save(hash: u64, settings: Settings) -> io::Result<()> {
+    let mut games = load_all();
+    games.retain(|(h, _)| *h != hash);
+    games.push((hash, settings));
+    save_all(&games)
+}
+
+pub fn forget(hash: u64) -> io::Result<()> {
+    let mut games = load_all();
+    games.retain(|(h, _)| *h != hash);
+    save_all(&games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_keep_the_keymap() {
+        let mut keymap = KeyMap::default();
+        keymap.keypad[3] = "zx,.".to_string();
+        let mut s = Settings {
+            cpu_hz: 700,
+            keeptime_ms: 80,
+            quirks: "shift".parse().unwrap(),
+            palette: None,
+            platform: Platform::VIP,
+            keymap: Some(keymap),
+        };
+        assert_eq!(parse(line(7, &s).trim_end()), Some((7, s.clone())));
+        s.keymap = KeyMap::preset("azerty");
+        assert!(line(7, &s).ends_with("\tvip\tazerty\n"));
+        assert_eq!(parse(line(7, &s).trim_end()), Some((7, s.clone())));
+        // lines written before the keymap was remembered
+        s.keymap = None;
+        assert_eq!(
+            parse("0000000000000007\t700\t80\tshift\t-\tvip"),
+            Some((7, s))
+        );
+    }
+}
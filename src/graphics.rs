@@ -0,0 +1,187 @@
+use chip8::filter::Frame;
+use chip8::Rgb;
+use std::env;
+use std::fmt::Write;
+
+// pixels of a terminal bitmap per CHIP-8 pixel (sixel)
+const SIXEL_SCALE: usize = 8;
+// color registers available to a sixel image
+const SIXEL_COLORS: usize = 256;
+// bytes of base64 payload per kitty graphics escape
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+pub enum Protocol {
+    Auto,
+    Cells,
+    Sixel,
+    Kitty,
+}
+
+impl Protocol {
+    // resolves `Auto` by the environment the terminal advertises.
+    pub fn resolve(self) -> Self {
+        match self {
+            Protocol::Auto => detect(),
+            p => p,
+        }
+    }
+}
+
+fn detect() -> Protocol {
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some()
+        || term.contains("kitty")
+        || program == "WezTerm"
+        || program == "ghostty"
+    {
+        return Protocol::Kitty;
+    }
+    if ["mlterm", "foot", "yaft", "contour"]
+        .iter()
+        .any(|t| term.contains(t))
+        || program == "iTerm.app"
+    {
+        return Protocol::Sixel;
+    }
+    Protocol::Cells
+}
+
+// encodes a frame as one escape sequence drawn at the top-left corner.
+pub fn encode(protocol: Protocol, frame: &Frame) -> String {
+    let mut out = String::from("\x1b[1;1H");
+    match protocol {
+        Protocol::Sixel => sixel(&mut out, frame),
+        Protocol::Kitty => kitty(&mut out, frame),
+        _ => {}
+    }
+    out
+}
+
+// sixel color registers: the distinct colors of the frame, up to SIXEL_COLORS.
+// Further colors (e.g. of a decay filter) use the nearest register.
+fn sixel_registers(frame: &Frame) -> (Vec<Rgb>, Vec<u8>) {
+    let mut colors: Vec<Rgb> = Vec::new();
+    let index = frame
+        .pixels
+        .iter()
+        .map(|c| match colors.iter().position(|r| r == c) {
+            Some(i) => i as u8,
+            None if colors.len() < SIXEL_COLORS => {
+                colors.push(*c);
+                (colors.len() - 1) as u8
+            }
+            None => nearest(&colors, *c),
+        })
+        .collect();
+    (colors, index)
+}
+
+fn nearest(colors: &[Rgb], c: Rgb) -> u8 {
+    let dist = |r: &Rgb| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r.0, c.0) + d(r.1, c.1) + d(r.2, c.2)
+    };
+    (0..colors.len())
+        .min_by_key(|i| dist(&colors[*i]))
+        .unwrap_or(0) as u8
+}
+
+fn sixel(out: &mut String, frame: &Frame) {
+    let (colors, index) = sixel_registers(frame);
+    let pixel = |x: usize, y: usize| index[y * frame.width + x];
+    let (w, h) = (frame.width * SIXEL_SCALE, frame.height * SIXEL_SCALE);
+    let _ = write!(out, "\x1bPq\"1;1;{};{}", w, h);
+    for (i, c) in colors.iter().enumerate() {
+        let pct = |v: u8| v as u32 * 100 / 255;
+        let _ = write!(out, "#{};2;{};{};{}", i, pct(c.0), pct(c.1), pct(c.2));
+    }
+    for band in 0..h.div_ceil(6) {
+        for color in 0..colors.len() {
+            let _ = write!(out, "#{}", color);
+            let mut run: Option<(u8, usize)> = None;
+            for px in 0..w {
+                let mut bits = 0u8;
+                for b in 0..6 {
+                    let py = band * 6 + b;
+                    if py < h && pixel(px / SIXEL_SCALE, py / SIXEL_SCALE) as usize == color {
+                        bits |= 1 << b;
+                    }
+                }
+                run = match run {
+                    Some((c, n)) if c == bits => Some((c, n + 1)),
+                    Some((c, n)) => {
+                        push_sixel_run(out, c, n);
+                        Some((bits, 1))
+                    }
+                    None => Some((bits, 1)),
+                };
+            }
+            if let Some((c, n)) = run {
+                push_sixel_run(out, c, n);
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+}
+
+fn push_sixel_run(out: &mut String, bits: u8, n: usize) {
+    let c = (0x3f + bits) as char;
+    if n > 3 {
+        let _ = write!(out, "!{}{}", n, c);
+    } else {
+        (0..n).for_each(|_| out.push(c));
+    }
+}
+
+fn kitty(out: &mut String, frame: &Frame) {
+    let (w, h) = (frame.width, frame.height);
+    let mut rgb = Vec::with_capacity(w * h * 3);
+    for c in &frame.pixels {
+        rgb.extend_from_slice(&[c.0, c.1, c.2]);
+    }
+    let payload = base64(&rgb);
+    let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let more = if n + 1 < chunks.len() { 1 } else { 0 };
+        if n == 0 {
+            // one image id so that every frame replaces the previous one in place.
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=24,i=1,p=1,q=2,C=1,s={},v={},c={},r={},m={};",
+                w, h, w, h, more
+            );
+        } else {
+            let _ = write!(out, "\x1b_Gm={};", more);
+        }
+        out.push_str(std::str::from_utf8(chunk).expect("base64 is ascii"));
+        out.push_str("\x1b\\");
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for c in data.chunks(3) {
+        let n = (c[0] as u32) << 16
+            | (*c.get(1).unwrap_or(&0) as u32) << 8
+            | *c.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= c.len() {
+                out.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// nearest color of the xterm 256-color cube (16..=231).
+pub fn xterm256(c: chip8::Rgb) -> u8 {
+    let level = |v: u8| ((v as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(c.0) + 6 * level(c.1) + level(c.2)
+}
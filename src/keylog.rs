@@ -0,0 +1,69 @@
+use chip8::KeyCheck;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const MAX_ENTRIES: usize = 8;
+
+// a raw key event from the terminal and whether the ROM observed it.
+struct Entry {
+    raw: char,
+    key: Option<u8>,
+    at: Instant,
+    seen: Option<(&'static str, Duration)>,
+}
+
+// recent key events for the input echo / latency overlay.
+#[derive(Default)]
+pub struct KeyLog {
+    entries: VecDeque<Entry>,
+}
+
+impl KeyLog {
+    // records a key event as received from the terminal and its mapped hex key.
+    pub fn raw(&mut self, raw: char, key: Option<u8>) {
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(Entry {
+            raw,
+            key,
+            at: Instant::now(),
+            seen: None,
+        });
+    }
+    // marks the latest unobserved event of a key the ROM has seen down; returns whether one was marked.
+    pub fn observed(&mut self, check: KeyCheck) -> bool {
+        let (key, op) = match check {
+            KeyCheck::Pressed { key, down: true } => (key, "EX9E/EXA1"),
+            KeyCheck::Waited { key } => (key, "FX0A"),
+            _ => return false,
+        };
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.key == Some(key) && e.seen.is_none())
+        {
+            Some(e) => {
+                e.seen = Some((op, e.at.elapsed()));
+                true
+            }
+            None => false,
+        }
+    }
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{:<4}{:<5}{}", "raw", "key", "seen by")];
+        lines.extend(self.entries.iter().map(|e| {
+            let key = match e.key {
+                Some(k) => format!("{:X}", k),
+                None => "-".to_string(),
+            };
+            let seen = match e.seen {
+                Some((op, latency)) => format!("{} +{}ms", op, latency.as_millis()),
+                None => "-".to_string(),
+            };
+            format!("{:<4}{:<5}{}", e.raw, key, seen)
+        }));
+        lines.resize(MAX_ENTRIES + 1, String::new());
+        lines
+    }
+}
@@ -1,193 +1,1494 @@
-use chip8::Chip;
+mod bugreport;
+mod capture;
+mod config;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod games;
+mod graphics;
+mod keylog;
+mod recent;
+mod script;
+mod terminal;
+
+use chip8::filter::{Frame, FrameFilter, Pipeline, Scale};
+use chip8::keymap::{KeyMap, KEYPAD};
+use chip8::{romdb, usage};
+use chip8::{Chip, Platform, StopCondition};
 use clap::Parser;
-use rustbox::Key;
-use rustbox::{Color, RustBox};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use graphics::Protocol;
+use keylog::KeyLog;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::io::{IsTerminal, LineWriter, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
+use terminal::{Color, Terminal};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
+    /// ROM file, or a file name in the ROM directory (without it, a ROM of that directory is picked)
     #[clap(short, long)]
-    rom: String,
+    rom: Option<String>,
+    /// (required the first time a ROM is played; later runs reuse the last value)
     #[clap(short, long)]
-    keyboard_keeptime_ms: u16,
+    keyboard_keeptime_ms: Option<u16>,
+    /// (required the first time a ROM is played; later runs reuse the last value)
     #[clap(short, long)]
-    cpu_hz: u32,
+    cpu_hz: Option<u32>,
+    /// pick one of the recently played ROMs (with its settings)
+    #[clap(long)]
+    recent: bool,
+    /// relaunch the last played ROM (with its settings)
+    #[clap(long, conflicts_with = "recent")]
+    last: bool,
+    /// read the defaults from this file instead of the setup's config.toml (same keys; options given here still win)
+    #[clap(long, conflicts_with = "setup")]
+    config: Option<String>,
+    /// run the first-run setup again (ROM directory, key layout, color theme, default speed)
+    #[clap(long)]
+    setup: bool,
+    /// forget the settings remembered for the ROM (speed, keeptime, quirks, palette, platform, keymap)
+    #[clap(long)]
+    reset_settings: bool,
+    #[clap(short, long, arg_enum, default_value = "auto")]
+    graphics: Protocol,
+    /// pixels per character cell: space-cells (one), half-block (two stacked, square pixels) or braille (2x4)
+    #[clap(long, arg_enum, default_value = "space-cells")]
+    render_mode: RenderMode,
+    /// keyboard keys of the keypad: qwerty, azerty, qwertz, cosmac or a TOML/JSON keymap file (defaults to the setup's layout)
+    #[clap(long)]
+    keymap: Option<KeyMap>,
+    /// show the keypad map while a ROM waits for a key (FX0A)
+    #[clap(long)]
+    keypad_on_wait: bool,
+    /// show recent key events and how long the ROM took to observe them next to the screen
+    #[clap(long)]
+    key_overlay: bool,
+    /// run a second ROM right of the first with the same settings (character cells; keys 6789/yuio/hjkl/bnm, or the keymap's split rows)
+    #[clap(long, conflicts_with = "key-overlay")]
+    split: Option<String>,
+    /// inject key presses/releases from a script (`<cycle|frame f> <press|release> <key>` per line)
+    #[clap(long)]
+    input_script: Option<String>,
+    /// write `<frame> <hash>` of the framebuffer after every 60Hz frame to a file (`-` for stdout with --headless)
+    #[clap(long)]
+    frame_hashes: Option<String>,
+    /// write the keys (typed or scripted) and a state hash every second to a replay file
+    #[clap(long)]
+    write_replay: Option<String>,
+    /// seed of the random numbers (CXNN), for reproducible runs and replays
+    #[clap(long)]
+    seed: Option<u64>,
+    /// record the screen of the left machine and write it as an animated GIF to this path on exit
+    #[clap(long)]
+    record: Option<String>,
+    /// reload the ROM whenever its file changes on disk, e.g. after assembling it again
+    #[clap(long)]
+    watch: bool,
+    /// keep the SUPER-CHIP RPL flags (FX75/FX85, where games keep high scores) in this file between runs
+    #[clap(long)]
+    rpl_file: Option<String>,
+    /// directory of the screenshots taken with F2 (PNG)
+    #[clap(long, default_value = ".")]
+    screenshot_dir: String,
+    /// write a bug report archive (tar) to this path on a trap or when F12 is pressed
+    #[clap(long)]
+    bug_report: Option<String>,
+    /// show the first execution of each opcode whose behavior depends on interpreter quirks, and list them on exit
+    #[clap(long)]
+    warn_quirks: bool,
+    /// do not ring the terminal bell when the sound timer starts
+    #[clap(long)]
+    mute: bool,
+    /// feedback of the sound timer: bell (at the start of a beep), note (♪ in the status line while it lasts), both or off (defaults to the setup's, both)
+    #[clap(long, arg_enum)]
+    sound: Option<config::Sound>,
+    /// colors for background, plane 1, plane 2 and both planes (`#rrggbb,...` as in Octo)
+    #[clap(long)]
+    palette: Option<chip8::Palette>,
+    /// color theme for this run instead of the setup's: default (the terminal colors), amber, green, lcd, paper (--palette still wins)
+    #[clap(long, parse(try_from_str = config::theme_name))]
+    theme: Option<String>,
+    /// post-processing of bitmap output: decay[=percent], scale=n, border=n[:#rrggbb] (comma separated)
+    #[clap(long, default_value = "")]
+    filters: chip8::filter::Pipeline,
+    /// quirks to enable: vf-reset, load-store, shift, jump, clipping, display-wait, half-scroll, index-overflow, row-collisions (comma separated)
+    #[clap(long)]
+    quirks: Option<chip8::Quirks>,
+    /// recognize known ROMs by their SHA-1 and play them with their platform, quirks and speed (options given here still win)
+    #[clap(long)]
+    auto_config: bool,
+    /// a file of known ROMs besides the built-in ones (`<sha1> <platform> <quirks|-> <cpu hz> <title>` per line; implies --auto-config)
+    #[clap(long)]
+    rom_db: Option<String>,
+    /// interpreter to emulate: chip8, vip, schip, xochip, eti-660, dream-6800 (program start, screen size, font, speed and quirks)
+    #[clap(long)]
+    platform: Option<chip8::Platform>,
+    /// run the ROM for this many cycles without a terminal, as fast as possible, and print the frame and state hashes (only --cpu-hz, --platform, --quirks, --seed, --input-script and --frame-hashes apply; the seed defaults to 0)
+    #[clap(long)]
+    headless: Option<u64>,
+    /// with --headless, exit with 1 unless the framebuffer hash is this (hex)
+    #[clap(long, requires = "headless", parse(try_from_str = parse_hash))]
+    expect_frame_hash: Option<u64>,
+    /// with --headless, exit with 1 unless the state hash is this (hex)
+    #[clap(long, requires = "headless", parse(try_from_str = parse_hash))]
+    expect_state_hash: Option<u64>,
+    /// with --headless, print how often each opcode type and the hottest addresses were executed
+    #[clap(long, requires = "headless")]
+    profile: bool,
+    /// with --headless, stop at a jump to itself (the final loop of many ROMs) instead of running on
+    #[clap(long, requires = "headless")]
+    stop_on_self_jump: bool,
+    /// play with a gamepad too (d-pad 2/8/4/6, south 5, east 6, west 4, north A, start F)
+    #[cfg(feature = "gamepad")]
+    #[clap(long)]
+    gamepad: bool,
+    /// button=key pairs over the default gamepad mapping, e.g. `south=5,up=2` (`-` unmaps; implies --gamepad)
+    #[cfg(feature = "gamepad")]
+    #[clap(long)]
+    gamepad_map: Option<gamepad::Mapping>,
+}
+
+fn parse_hash(s: &str) -> Result<u64, std::num::ParseIntError> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
 }
 
 fn main() {
     env_logger::init();
     let args = Args::parse();
+    if args.frame_hashes.as_deref() == Some("-") && args.headless.is_none() {
+        eprintln!("--frame-hashes - needs --headless: the screen is drawn on stdout");
+        std::process::exit(2);
+    }
+    // the first run on a terminal asks for the defaults (ROM directory, keys, colors, speed).
+    // `--config` replaces the file, which then must not have mistakes.
+    let configured = match (&args.config, config::load()) {
+        (Some(path), _) => match config::load_file(path) {
+            Ok(c) => Some(c),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        (None, Some(c)) if !args.setup => Some(c),
+        (None, c) if args.setup || (args.headless.is_none() && std::io::stdin().is_terminal()) => {
+            let c = config::wizard(c.unwrap_or_default());
+            if let Err(e) = config::save(&c) {
+                eprintln!("could not write the config: {}", e);
+            }
+            Some(c)
+        }
+        (None, _) => None,
+    };
+    let config = configured.clone().unwrap_or_default();
+    let picked = if args.recent || args.last {
+        let entries = recent::load();
+        let e = if args.recent {
+            recent::pick(&entries)
+        } else {
+            entries.first().cloned()
+        };
+        match e {
+            Some(e) => Some(e),
+            None => {
+                eprintln!("no recent ROM selected");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let rom_path = match (&args.rom, &picked, &config.rom_dir) {
+        (Some(rom), _, _) => config.rom_path(rom),
+        (None, Some(e), _) => e.path.clone(),
+        (None, None, Some(dir)) => config::pick_rom(dir).unwrap_or_else(|| {
+            eprintln!("no ROM selected");
+            std::process::exit(1);
+        }),
+        (None, None, None) => {
+            eprintln!("--rom is required without a ROM directory (see --setup)");
+            std::process::exit(2);
+        }
+    };
+    let mut rom = Vec::new();
+    File::open(&rom_path)
+        .and_then(|mut f| f.read_to_end(&mut rom))
+        .unwrap();
+    let known = known_rom(&args, &rom);
+    if let Some(cycles) = args.headless {
+        run_headless(&args, &rom, known.as_ref(), cycles);
+    }
+    let rom_hash = recent::rom_hash(&rom);
+    // options given on the command line win over the settings remembered for the ROM.
+    let saved = if args.reset_settings {
+        let _ = games::forget(rom_hash);
+        None
+    } else {
+        games::load(rom_hash)
+    };
+    let missing = |option: &str| -> ! {
+        eprintln!("{} is required for a ROM played the first time", option);
+        std::process::exit(2);
+    };
+    // a platform given on the command line brings its speed and quirks; a known ROM comes next.
+    let platform = args
+        .platform
+        .or(known.as_ref().map(|k| k.platform))
+        .or_else(|| saved.as_ref().map(|s| s.platform))
+        .or(config.platform)
+        .unwrap_or_default();
+    // a ROM which cannot be loaded is neither remembered nor played.
+    if let Err(e) = Chip::headless().with_platform(&platform).load_slice(&rom) {
+        eprintln!("{}: {}", rom_path, e);
+        std::process::exit(1);
+    }
+    // the ROM of the second machine, read before the terminal is taken over.
+    let split_rom = args.split.as_ref().map(|path| {
+        let mut rom = Vec::new();
+        if let Err(e) = File::open(path).and_then(|mut f| f.read_to_end(&mut rom)) {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+        rom
+    });
+    // the instruction set which the ROM's reachable code uses, shown as a hint.
+    let variant = usage::detect(&rom, platform.start);
+    let rpl_flags = args.rpl_file.as_ref().map(|path| {
+        load_rpl(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        })
+    });
+    let cpu_hz = args
+        .cpu_hz
+        .or(args.platform.map(|p| p.cpu_hz))
+        .or(known.as_ref().map(|k| k.cpu_hz))
+        .or_else(|| saved.as_ref().map(|s| s.cpu_hz))
+        .or_else(|| picked.as_ref().map(|e| e.cpu_hz))
+        .or(configured.as_ref().map(|c| c.cpu_hz))
+        .unwrap_or_else(|| missing("--cpu-hz"));
+    let keeptime = args
+        .keyboard_keeptime_ms
+        .or_else(|| saved.as_ref().map(|s| s.keeptime_ms))
+        .or_else(|| picked.as_ref().map(|e| e.keeptime_ms))
+        .or(configured.as_ref().map(|c| c.keeptime_ms))
+        .unwrap_or_else(|| missing("--keyboard-keeptime-ms"));
+    let quirks = args
+        .quirks
+        .or(args.platform.map(|p| p.quirks))
+        .or(known.as_ref().map(|k| k.quirks))
+        .or_else(|| saved.as_ref().map(|s| s.quirks))
+        .or(config.quirks)
+        .unwrap_or(platform.quirks);
+    let palette = args
+        .palette
+        .or_else(|| saved.as_ref().and_then(|s| s.palette));
+    let keymap = args.keymap.clone().or_else(|| saved.and_then(|s| s.keymap));
+    // the theme is not remembered per ROM so that changing it applies to every ROM.
+    let shown_palette = match &args.theme {
+        Some(theme) if args.palette.is_none() => config::theme_palette(theme),
+        _ => palette.or_else(|| config.palette()),
+    };
+    let _ = games::save(
+        rom_hash,
+        games::Settings {
+            cpu_hz,
+            keeptime_ms: keeptime,
+            quirks,
+            palette,
+            platform,
+            keymap: keymap.clone(),
+        },
+    );
+    let started = Instant::now();
+    let settings = vec![
+        ("rom", rom_path.clone()),
+        ("rom hash", format!("{:016x}", rom_hash)),
+        ("cpu hz", cpu_hz.to_string()),
+        ("keeptime ms", keeptime.to_string()),
+        ("platform", platform.name.to_string()),
+        ("quirks", quirks.to_string()),
+        ("input script", format!("{:?}", args.input_script)),
+        (
+            "known as",
+            format!("{:?}", known.as_ref().map(|k| &k.title)),
+        ),
+        ("variant", variant.to_string()),
+    ];
+    let watched = args.watch.then(|| rom_path.clone());
+    // the ROM played before this one, which the relaunch hotkey switches to
+    let last_rom = recent::load()
+        .into_iter()
+        .map(|e| e.path)
+        .find(|path| *path != rom_path);
+    let entry = recent::Entry {
+        path: rom_path.clone(),
+        hash: rom_hash,
+        cpu_hz,
+        keeptime_ms: keeptime,
+        play_secs: 0,
+    };
+    let save_recent = move || {
+        let _ = recent::record(recent::Entry {
+            play_secs: started.elapsed().as_secs(),
+            ..entry
+        });
+    };
+    at_exit(Box::new(save_recent));
+    let mut player = args
+        .input_script
+        .as_ref()
+        .map(|path| load_player(path, cpu_hz));
+    // written replays and bug reports are only reproducible with a known seed.
+    let seed = match args.seed {
+        None if args.write_replay.is_some() || args.bug_report.is_some() => Some(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64),
+        ),
+        s => s,
+    };
+    let mut hash_frame = args.frame_hashes.as_deref().map(frame_hash_writer);
+    let mut recorder = args.write_replay.as_ref().map(|path| {
+        let mut r = script::Recorder::create(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        });
+        r.comment(&format!(
+            "recorded with --seed {}",
+            seed.unwrap_or_default()
+        ));
+        r
+    });
     let mut console = Console::new();
-    let mut chip = Chip::new(
-        Box::new(console.display()),
-        Box::new(console.keyboard(args.keyboard_keeptime_ms)),
+    // a panic on any thread cannot unwind the shared Terminal: restore the terminal, then report.
+    let report_panic = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        terminal::restore();
+        report_panic(info);
+        exit(101);
+    }));
+    let (relaunch_requests, relaunches) = mpsc::channel();
+    let controls = Controls {
+        speed: chip8::Speed::new(),
+        cpu_hz,
+        frame_step: chip8::FrameStep::new(),
+        report_requested: Arc::new(AtomicBool::new(false)),
+        screenshot_requested: Arc::new(AtomicBool::new(false)),
+        relaunch: relaunch_requests,
+        stopper: chip8::Stopper::new(),
+    };
+    let (speed, frame_step) = (controls.speed.clone(), controls.frame_step.clone());
+    let stopper = controls.stopper.clone();
+    // SIGINT, SIGTERM and SIGHUP stop the machines like Esc.
+    let (stop, resume) = (stopper.clone(), frame_step.clone());
+    let _ = ctrlc::set_handler(move || {
+        stop.stop();
+        resume.resume();
+    });
+    let report_requested = controls.report_requested.clone();
+    let screenshot_requested = controls.screenshot_requested.clone();
+    let (keyboard, split_keyboard) = console.keyboard(
+        &keymap.unwrap_or_else(|| config.layout()),
+        keeptime,
+        args.keypad_on_wait,
+        args.key_overlay,
+        controls,
+        args.split.is_some(),
+    );
+    #[cfg(feature = "gamepad")]
+    if args.gamepad || args.gamepad_map.is_some() {
+        let mapping = args.gamepad_map.clone().unwrap_or_default();
+        let state = keyboard.state.clone();
+        let pad = gamepad::spawn(mapping, stopper.clone(), move |key, down| {
+            state.hold(key, down)
+        });
+        console.threads.push(pad);
+    }
+    let keyboard: Box<dyn chip8::Keyboard> = match &player {
+        Some(p) => Box::new(p.keyboard(keyboard)),
+        None => Box::new(keyboard),
+    };
+    // both machines draw with character cells, bitmaps always start at the top-left corner.
+    let protocol = match args.split {
+        Some(_) => Protocol::Cells,
+        None => args.graphics.resolve(),
+    };
+    let display = console.display(
+        protocol,
+        args.render_mode,
+        args.filters,
+        0,
+        args.split.is_some(),
     );
-    let _ = chip.load(&mut File::open(args.rom).unwrap()).unwrap();
-    chip.run(args.cpu_hz);
+    let mut chip = Chip::new(Box::new(display), keyboard);
+    chip.set_speed(speed);
+    chip.set_frame_step(frame_step);
+    chip.set_stopper(stopper.clone());
+    chip.set_platform(&platform);
+    chip.set_quirks(quirks);
+    chip.warn_quirks(args.warn_quirks);
+    let sound = args.sound.unwrap_or(config.sound);
+    let sound = if args.mute { sound.muted() } else { sound };
+    if sound != config::Sound::Off {
+        let note = sound.note().then(|| console.console.clone());
+        chip.set_audio(Box::new(Speaker {
+            bell: sound.bell(),
+            note,
+        }));
+    }
+    if let Some(p) = &shown_palette {
+        chip.set_palette(p);
+    }
+    if let Err(e) = chip.load_slice(&rom) {
+        terminal::restore();
+        eprintln!("{}: {}", rom_path, e);
+        exit(1);
+    }
+    if let Some(flags) = rpl_flags {
+        chip.set_rpl_flags(flags);
+    }
+    if let Some(path) = watched {
+        let screen = console.console.clone();
+        let watcher = watch_rom(path, chip.controller(), stopper.clone(), screen);
+        console.threads.push(watcher);
+    }
+    if let Some(path) = last_rom {
+        let screen = console.console.clone();
+        let relauncher = relaunch_rom(path, chip.controller(), relaunches, screen);
+        console.threads.push(relauncher);
+    }
+    let mut split_machine = None;
+    if let (Some(rom), Some(keyboard)) = (split_rom, split_keyboard) {
+        let display = console.display(
+            Protocol::Cells,
+            args.render_mode,
+            Pipeline::new(),
+            SPLIT_COLUMN,
+            true,
+        );
+        let (speed, frame_step, stopper) = (chip.speed(), chip.frame_step(), chip.stopper());
+        let console = console.console.clone();
+        // the machine is not Send, so it is built on its own thread.
+        split_machine = Some(thread::spawn(move || {
+            let mut chip = Chip::new(Box::new(display), Box::new(keyboard));
+            chip.set_speed(speed);
+            chip.set_frame_step(frame_step);
+            chip.set_stopper(stopper);
+            chip.set_platform(&platform);
+            chip.set_quirks(quirks);
+            if let Some(p) = &shown_palette {
+                chip.set_palette(p);
+            }
+            if let Err(e) = chip.load_slice(&rom) {
+                show_status(&console, SPLIT_COLUMN, &e.to_string());
+                return;
+            }
+            if let Err(trap) = chip.run(cpu_hz) {
+                show_status(&console, SPLIT_COLUMN, &trap.to_string());
+            }
+        }));
+    }
+    let cycles_per_frame = (cpu_hz as u64 / 60).max(1);
+    if let Some(s) = seed {
+        chip.set_seed(s);
+    }
+    let mut collector = args.bug_report.as_ref().map(|_| {
+        let mut settings = settings;
+        settings.push(("seed", seed.unwrap_or_default().to_string()));
+        bugreport::Collector::new(settings)
+    });
+    let write_report = |c: &bugreport::Collector, chip: &Chip, reason: &str| {
+        let path = args.bug_report.as_deref().unwrap_or_default();
+        if let Err(e) = c.write(path, chip, &rom, reason) {
+            eprintln!("{}: {}", path, e);
+        }
+    };
+    let stamp_interval = cycles_per_frame * REPLAY_HASH_FRAMES;
+    let mut next_stamp = stamp_interval;
+    // why the machine was stopped early, reported once the terminal is restored
+    let mut failure = None;
+    let image_palette = shown_palette.unwrap_or_default();
+    let screen = console.console.clone();
+    let recording = (args.record.as_ref())
+        .map(|_| Rc::new(RefCell::new(capture::GifRecorder::new(image_palette))));
+    if recording.is_some() || hash_frame.is_some() {
+        let r = recording.clone();
+        chip.set_frame_hook(move |fb| {
+            if let Some(r) = &r {
+                r.borrow_mut().push(fb);
+            }
+            if let Some(h) = hash_frame.as_mut() {
+                h(fb);
+            }
+        });
+    }
+    match (&known, variant) {
+        (Some(k), _) => show_message(&screen, &format!("known ROM: {}", k.title)),
+        (None, usage::Level::Chip8) => {}
+        // without --platform, point to the preset made for them
+        (None, v) if args.platform.is_none() && platform == Platform::default() => {
+            let preset = match v {
+                usage::Level::XoChip => Platform::XOCHIP,
+                _ => Platform::SCHIP,
+            };
+            let text = format!("uses {} instructions: try --platform {}", v, preset.name);
+            show_message(&screen, &text)
+        }
+        (None, v) => show_message(&screen, &format!("uses {} instructions", v)),
+    }
+    // the quirk-sensitive opcodes executed, for --warn-quirks
+    let mut quirks_hit = Vec::new();
+    let result = chip.run_with(cpu_hz, |cycle, chip| {
+        // a wait for the next frame leaves the quirk of the instruction before it
+        if let Some(q) = chip.quirk_hit().filter(|q| !quirks_hit.contains(q)) {
+            show_message(
+                &screen,
+                &format!("quirk-sensitive opcode: {}", q.description()),
+            );
+            quirks_hit.push(q);
+        }
+        if screenshot_requested.swap(false, Ordering::Relaxed) {
+            let dir = Path::new(&args.screenshot_dir);
+            let text = match capture::screenshot(dir, chip.framebuffer(), &image_palette) {
+                Ok(path) => format!("saved {}", path.display()),
+                Err(e) => format!("screenshot: {}", e),
+            };
+            show_message(&screen, &text);
+        }
+        if let Some(c) = collector.as_mut() {
+            c.observe(cycle, chip);
+            if report_requested.swap(false, Ordering::Relaxed) {
+                write_report(c, chip, "requested by the user");
+            }
+        }
+        if let Some(p) = player.as_mut() {
+            if let Err(e) = p.apply(cycle, || chip.state_hash()) {
+                if let Some(c) = collector.as_ref() {
+                    write_report(c, chip, &e);
+                }
+                failure = Some(e);
+                stopper.stop();
+            }
+        }
+        // the keys typed and those of the input script alike
+        if let Some(r) = recorder.as_mut() {
+            r.keys(cycle, chip.keyboard.state());
+            if cycle >= next_stamp {
+                r.write(&script::Event {
+                    cycle,
+                    action: script::Action::Hash(chip.state_hash()),
+                });
+                next_stamp = cycle + stamp_interval;
+            }
+        }
+    });
+    if let Err(trap) = result {
+        if let Some(c) = collector.as_ref() {
+            write_report(c, &chip, &trap.to_string());
+        }
+        failure = Some(trap.to_string());
+    }
+    if let (Some(path), Some(r)) = (&args.record, &recording) {
+        if let Err(e) = r.borrow().write(Path::new(path)) {
+            failure.get_or_insert(format!("{}: {}", path, e));
+        }
+    }
+    if let Some(path) = &args.rpl_file {
+        if let Err(e) = std::fs::write(path, chip.rpl_flags()) {
+            failure.get_or_insert(format!("{}: {}", path, e));
+        }
+    }
+    // stop the second machine and the input threads, then give the terminal back.
+    stopper.stop();
+    chip.frame_step().resume();
+    drop(chip);
+    if let Some(t) = split_machine {
+        let _ = t.join();
+    }
+    console.close();
+    for q in quirks_hit {
+        eprintln!("quirk-sensitive opcode: {}", q.description());
+    }
+    if let Some(e) = failure {
+        eprintln!("{}", e);
+        exit(1);
+    }
+    exit(0);
+}
+
+// reads the flags kept by `--rpl-file`; a file which does not exist yet holds no flags.
+fn load_rpl(path: &str) -> std::io::Result<[u8; 8]> {
+    let mut flags = [0; 8];
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let n = bytes.len().min(flags.len());
+            flags[..n].copy_from_slice(&bytes[..n]);
+            Ok(flags)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(flags),
+        Err(e) => Err(e),
+    }
+}
+
+// `--watch`: reloads the ROM into the running machine whenever the modification time of its
+// file changes, until the machines are stopped.
+fn watch_rom(
+    path: String,
+    controller: chip8::ChipController,
+    stopper: chip8::Stopper,
+    screen: Arc<Terminal>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let modified = || std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut last = modified();
+        while !stopper.is_stopped() {
+            thread::sleep(WATCH_POLL);
+            let now = modified();
+            if now == last {
+                continue;
+            }
+            last = now;
+            let text = match std::fs::read(&path) {
+                // a paused machine reloads once resumed; the reply is dropped with the machine.
+                Ok(rom) => match controller.reload(rom).recv() {
+                    Ok(Ok(n)) => format!("reloaded {} ({} bytes)", path, n),
+                    Ok(Err(e)) => format!("{}: {}", path, e),
+                    Err(_) => return,
+                },
+                Err(e) => format!("{}: {}", path, e),
+            };
+            show_message(&screen, &text);
+        }
+    })
+}
+
+// loads the ROM at `path` and starts it over each time the relaunch hotkey is pressed; ends
+// with the input thread.
+fn relaunch_rom(
+    path: String,
+    controller: chip8::ChipController,
+    requests: mpsc::Receiver<()>,
+    screen: Arc<Terminal>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        for () in requests {
+            let text = match std::fs::read(&path) {
+                Ok(rom) => match controller.reload(rom).recv() {
+                    Ok(Ok(n)) => format!("relaunched {} ({} bytes)", path, n),
+                    Ok(Err(e)) => format!("{}: {}", path, e),
+                    Err(_) => return,
+                },
+                Err(e) => format!("{}: {}", path, e),
+            };
+            show_message(&screen, &text);
+        }
+    })
+}
+
+// `--auto-config`/`--rom-db`: the ROM's entry in the built-in database or the given file.
+fn known_rom(args: &Args, rom: &[u8]) -> Option<romdb::Entry> {
+    if !args.auto_config && args.rom_db.is_none() {
+        return None;
+    }
+    let mut db = romdb::RomDb::builtin();
+    if let Some(path) = &args.rom_db {
+        let added = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| db.extend(&text));
+        if let Err(e) = added {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+    db.lookup(rom).cloned()
+}
+
+// frames between the state hashes of a written replay
+const REPLAY_HASH_FRAMES: u64 = 60;
+// how often `--watch` looks at the ROM file
+const WATCH_POLL: Duration = Duration::from_millis(250);
+
+type ExitHook = Box<dyn FnOnce() + Send>;
+static EXIT_HOOKS: Mutex<Vec<ExitHook>> = Mutex::new(Vec::new());
+
+// `--frame-hashes`: writes `<frame> <hash>` at every 60Hz tick but the first, which comes before
+// the first instruction; exits when the file cannot be created.
+fn frame_hash_writer(path: &str) -> impl FnMut(&chip8::FrameBuffer) {
+    let mut out: Box<dyn Write> = match path {
+        "-" => Box::new(std::io::stdout()),
+        _ => match File::create(path) {
+            Ok(f) => Box::new(LineWriter::new(f)),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+    };
+    let mut frame = 0;
+    move |fb| {
+        if frame > 0 {
+            let _ = writeln!(out, "{} {:016x}", frame, fb.hash());
+        }
+        frame += 1;
+    }
+}
+
+// `--input-script`: the player of the script; exits when it cannot be read.
+fn load_player(path: &str, cpu_hz: u32) -> script::Player {
+    match script::load(path, cpu_hz) {
+        Ok(events) => script::Player::new(events),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// registers `f` to be called by `exit`.
+fn at_exit(f: ExitHook) {
+    EXIT_HOOKS.lock().unwrap().push(f);
+}
+
+// `--headless`: runs without terminal, threads or remembered settings, so that the hashes
+// only depend on the ROM and the options.
+fn run_headless(args: &Args, rom: &[u8], known: Option<&romdb::Entry>, cycles: u64) -> ! {
+    let platform = args
+        .platform
+        .or(known.map(|k| k.platform))
+        .unwrap_or_default();
+    let quirks = args
+        .quirks
+        .or(args.platform.map(|p| p.quirks))
+        .or(known.map(|k| k.quirks))
+        .unwrap_or(platform.quirks);
+    let mut builder = Chip::builder()
+        .platform(platform)
+        .quirks(quirks)
+        .seed(args.seed.unwrap_or_default());
+    let hz = args.cpu_hz.or(args.platform.map(|p| p.cpu_hz));
+    let hz = hz.or(known.map(|k| k.cpu_hz));
+    if let Some(hz) = hz {
+        builder = builder.cpu_hz(hz);
+    }
+    let mut player = args
+        .input_script
+        .as_ref()
+        .map(|path| load_player(path, hz.unwrap_or(platform.cpu_hz)));
+    if let Some(p) = &player {
+        builder = builder.keyboard(p.keyboard(chip8::NullKeyboard));
+    }
+    if let Some(k) = known {
+        println!("known as: {}", k.title);
+    }
+    let mut chip = builder.build();
+    if let Some(path) = &args.frame_hashes {
+        chip.set_frame_hook(frame_hash_writer(path));
+    }
+    chip.set_profiling(args.profile);
+    chip.set_stop_on_self_jump(args.stop_on_self_jump);
+    if let Err(e) = chip.load_slice(rom) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    // a replay which diverges stops the run at the end of the frame
+    let stopper = chip.stopper();
+    let mut diverged = None;
+    let run = chip.run_until_with(StopCondition::Cycles(cycles), |cycle, chip| {
+        if let Some(p) = player.as_mut() {
+            if let Err(e) = p.apply(cycle, || chip.state_hash()) {
+                diverged.get_or_insert(e);
+                stopper.stop();
+            }
+        }
+    });
+    if let Some(e) = diverged {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let stopped = match run {
+        Ok(s) => format!("{:?}", s),
+        Err(trap) => trap.to_string(),
+    };
+    let (frame, state) = (chip.framebuffer().hash(), chip.state_hash());
+    println!("stopped: {}", stopped);
+    println!("frame hash: {:016x}", frame);
+    println!("state hash: {:016x}", state);
+    if let Some(profile) = chip.profile() {
+        println!("{}", profile);
+    }
+    let mut code = 0;
+    for (name, expected, actual) in [
+        ("frame", args.expect_frame_hash, frame),
+        ("state", args.expect_state_hash, state),
+    ] {
+        if expected.is_some_and(|h| h != actual) {
+            eprintln!(
+                "{} hash mismatch: expected {:016x}",
+                name,
+                expected.unwrap()
+            );
+            code = 1;
+        }
+    }
+    std::process::exit(code)
+}
+
+// runs the hooks registered with `at_exit` and exits the process.
+fn exit(code: i32) -> ! {
+    let hooks: Vec<ExitHook> = EXIT_HOOKS.lock().unwrap().drain(..).collect();
+    for f in hooks {
+        f();
+    }
+    std::process::exit(code)
 }
 
-#[derive(Clone)]
 struct Console {
-    console: Arc<RustBox>,
+    console: Arc<Terminal>,
+    // input threads, which end once the machines are stopped
+    threads: Vec<JoinHandle<()>>,
 }
 
 impl Console {
     fn new() -> Self {
         let c = Console {
-            console: Arc::new(match RustBox::init(Default::default()) {
+            console: Arc::new(match Terminal::init() {
                 Result::Ok(v) => v,
                 Result::Err(e) => panic!("{}", e),
             }),
+            threads: Vec::new(),
         };
-        let cc = c.clone();
-        let con = cc.console.clone();
-        con.clear();
-        con.present();
+        c.console.clear();
+        c.console.present();
         c
     }
-    fn display(&mut self) -> Display {
+    // waits for the input threads and restores the terminal by dropping the last Terminal
+    // handle; every display and keyboard must have been dropped before.
+    fn close(self) {
+        for t in self.threads {
+            let _ = t.join();
+        }
+        drop(self.console);
+    }
+    // a display drawing at `origin` (column); a `shared` display only clears its own area.
+    fn display(
+        &mut self,
+        protocol: Protocol,
+        render: RenderMode,
+        filters: Pipeline,
+        origin: usize,
+        shared: bool,
+    ) -> Display {
         Display {
             console: self.console.clone(),
-            state: [[0; chip8::HEIGHT]; chip8::WIDTH],
+            protocol,
+            render,
+            palette: None,
+            filters: Mutex::new(filters),
+            origin,
+            shared,
+            shown_size: None,
         }
     }
-    fn keyboard(&mut self, keeptime: u16) -> Keyboard {
-        Keyboard::new(self.console.clone(), keeptime)
+    // returns the keyboard, and the keyboard of the right-hand machine with `split`.
+    fn keyboard(
+        &mut self,
+        keymap: &KeyMap,
+        keeptime: u16,
+        keypad_on_wait: bool,
+        key_overlay: bool,
+        controls: Controls,
+        split: bool,
+    ) -> (Keyboard, Option<Keyboard>) {
+        let (first, second, threads) = Keyboard::new(
+            self.console.clone(),
+            keymap,
+            keeptime,
+            keypad_on_wait,
+            key_overlay,
+            controls,
+            split,
+        );
+        self.threads.extend(threads);
+        (first, second)
     }
 }
 
 struct Display {
-    console: Arc<RustBox>,
-    state: [[u8; chip8::HEIGHT]; chip8::WIDTH],
+    console: Arc<Terminal>,
+    protocol: Protocol,
+    render: RenderMode,
+    palette: Option<chip8::Palette>,
+    filters: Mutex<Pipeline>,
+    // column of the left edge, and whether another machine shares the terminal (--split)
+    origin: usize,
+    shared: bool,
+    // size of the screen as last shown; None repaints every cell
+    shown_size: Option<(usize, usize)>,
 }
 
-impl chip8::Display for Display {
-    fn clear(&mut self) {
-        for l in self.state.iter_mut() {
-            for c in l.iter_mut() {
-                *c = 0;
+impl Display {
+    fn color(&self, pixel: u8) -> Color {
+        match &self.palette {
+            Some(p) => Color::AnsiValue(graphics::xterm256(p.0[pixel as usize & 3])),
+            None if pixel == 0 => Color::Reset,
+            None => Color::White,
+        }
+    }
+    // draws the whole framebuffer as a bitmap, low-resolution frames doubled to the size of
+    // high-resolution ones; returns false in character-cell mode.
+    fn present_bitmap(&self, frame: &chip8::FrameBuffer) -> bool {
+        if self.protocol == Protocol::Cells {
+            return false;
+        }
+        let mut out = std::io::stdout();
+        let palette = self.palette.unwrap_or_default();
+        let frame = Frame::from_indexed(
+            frame.width(),
+            frame.height(),
+            |x, y| frame.pixel(x, y),
+            &palette,
+        );
+        let frame = match chip8::HIRES_WIDTH / frame.width {
+            n if n > 1 => Scale(n).apply(frame),
+            _ => frame,
+        };
+        let frame = self.filters.lock().unwrap().process(frame);
+        let image = graphics::encode(self.protocol, &frame);
+        let _ = out.write_all(image.as_bytes());
+        let _ = out.flush();
+        true
+    }
+    // blanks the screen area, e.g. before switching resolutions.
+    fn blank(&self) {
+        if self.shared {
+            for x in 0..SCREEN_COLUMNS {
+                for y in 0..SCREEN_ROWS {
+                    self.console
+                        .print_char(self.origin + x, y, Color::Reset, Color::Reset, ' ');
+                }
             }
+        } else {
+            self.console.clear();
         }
-        self.console.clear();
         self.console.present();
     }
-    fn draw(&mut self, x: u8, y: u8, sprite: &[u8]) -> bool {
-        let mut conflict = false;
-        for (dy, s) in sprite.iter().enumerate() {
-            for dx in 0..8 {
-                let (tx, ty) = (
-                    (x as usize + dx) % chip8::WIDTH,
-                    (y as usize + dy) % chip8::HEIGHT,
-                );
-                let mut cur = self.state[tx][ty];
-                let passed = (s >> (7 - dx)) & 1;
-                if cur == 1 && passed == 1 {
-                    conflict = true
-                }
-                cur = cur ^ passed;
-                let color = if cur == 1 {
-                    Color::White
+}
+
+impl chip8::Display for Display {
+    fn set_palette(&mut self, palette: &chip8::Palette) {
+        self.palette = Some(*palette);
+        self.shown_size = None;
+    }
+    // prints the cells of the rows which changed since the last frame, or sends the whole bitmap.
+    fn present(&mut self, frame: &chip8::FrameBuffer) {
+        let size = (frame.width(), frame.height());
+        let repaint = self.shown_size != Some(size);
+        if repaint {
+            self.blank();
+        }
+        if !self.present_bitmap(frame) {
+            let (columns, rows) = self.render.pixels_per_cell(frame);
+            for y in 0..frame.height() / rows {
+                if !repaint && !(y * rows..(y + 1) * rows).any(|r| frame.row_dirty(r)) {
+                    continue;
+                }
+                for x in 0..frame.width() / columns {
+                    let (ch, fg, bg) = cell(frame, x, y, columns, rows);
+                    self.console
+                        .print_char(self.origin + x, y, self.color(fg), self.color(bg), ch);
+                }
+            }
+            self.console.present();
+        }
+        self.shown_size = Some(size);
+    }
+}
+
+// how many pixels a character cell shows; every mode fits the screen in SCREEN_COLUMNS x
+// SCREEN_ROWS cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ArgEnum)]
+enum RenderMode {
+    SpaceCells,
+    HalfBlock,
+    Braille,
+}
+
+impl RenderMode {
+    // the columns and rows of pixels in a cell: the fewest which fit, at least two rows with half
+    // blocks so that pixels look square, or always 2x4 braille dots.
+    fn pixels_per_cell(self, frame: &chip8::FrameBuffer) -> (usize, usize) {
+        let (columns, rows) = (
+            frame.width().div_ceil(SCREEN_COLUMNS),
+            frame.height().div_ceil(SCREEN_ROWS),
+        );
+        match self {
+            RenderMode::SpaceCells => (columns, rows),
+            RenderMode::HalfBlock => (columns, rows.max(2)),
+            RenderMode::Braille => (2, 4),
+        }
+    }
+}
+
+// block characters of the quadrants lit in bits 0 (top left), 1 (top right), 2 (bottom left)
+// and 3 (bottom right).
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+// the pixel of each dot of a braille character, by bit (U+2800 + the bits of the raised dots).
+const BRAILLE_DOTS: [(usize, usize); 8] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+];
+
+// the character and the foreground and background pixels of the cell at `x`, `y` showing
+// `columns` x `rows` pixels: a pixel per cell, two stacked pixels as a half block, 2x4 pixels as
+// braille dots or 2x2 pixels as quadrants, both in the brightest of their colors.
+fn cell(
+    frame: &chip8::FrameBuffer,
+    x: usize,
+    y: usize,
+    columns: usize,
+    rows: usize,
+) -> (char, u8, u8) {
+    let (x, y) = (x * columns, y * rows);
+    match (columns, rows) {
+        (1, 1) => (' ', 0, frame.pixel(x, y)),
+        (1, _) => ('▀', frame.pixel(x, y), frame.pixel(x, y + 1)),
+        (2, 4) => {
+            let mut dots = 0;
+            let mut fg = 0;
+            for (n, (dx, dy)) in BRAILLE_DOTS.iter().enumerate() {
+                let (x, y) = (x + dx, y + dy);
+                let p = if x < frame.width() && y < frame.height() {
+                    frame.pixel(x, y)
                 } else {
-                    Color::Default
+                    0
                 };
-                self.state[tx][ty] = cur;
-                self.console
-                    .print_char(tx, ty, rustbox::RB_NORMAL, Color::Default, color, ' ');
+                if p != 0 {
+                    dots |= 1 << n;
+                }
+                fg = fg.max(p);
             }
+            // an empty cell is a space like the empty quadrant
+            let ch = match dots {
+                0 => ' ',
+                _ => char::from_u32(0x2800 + dots).unwrap_or(' '),
+            };
+            (ch, fg, 0)
+        }
+        _ => {
+            let bottom = (y + rows - 1).min(frame.height() - 1);
+            let quadrants =
+                [(x, y), (x + 1, y), (x, bottom), (x + 1, bottom)].map(|(x, y)| frame.pixel(x, y));
+            let mask = quadrants
+                .iter()
+                .enumerate()
+                .fold(0, |m, (n, p)| if *p != 0 { m | 1 << n } else { m });
+            let fg = quadrants.iter().copied().max().unwrap_or(0);
+            (QUADRANTS[mask], fg, 0)
+        }
+    }
+}
+
+// rings the terminal bell at the start of each beep, since terminals cannot hold a tone, and
+// shows a note in the status line while the beep lasts.
+struct Speaker {
+    bell: bool,
+    note: Option<Arc<Terminal>>,
+}
+impl chip8::Audio for Speaker {
+    fn start_beep(&mut self) {
+        if self.bell {
+            let mut out = std::io::stdout();
+            let _ = out.write_all(b"\x07");
+            let _ = out.flush();
+        }
+        if let Some(console) = &self.note {
+            show_status(console, SOUND_COLUMN, NOTE);
+        }
+    }
+    fn stop_beep(&mut self) {
+        if let Some(console) = &self.note {
+            show_status(console, SOUND_COLUMN, " ");
         }
-        self.console.present();
-        conflict
     }
 }
 
 struct Keyboard {
     state: KeyState,
-    rx: mpsc::Receiver<u8>,
+    keypad: Option<Vec<String>>,
+    // column of the waiting indicator, below its screen
+    column: usize,
+}
+
+// cells of the screen; high-resolution frames are shown with half blocks or quadrants
+const SCREEN_COLUMNS: usize = chip8::WIDTH;
+const SCREEN_ROWS: usize = chip8::HEIGHT;
+// column of the right-hand machine of --split, right of the screen
+const SPLIT_COLUMN: usize = SCREEN_COLUMNS + 2;
+const WAITING: &str = "waiting for key...";
+// hotkeys changing the emulation speed by SPEED_STEP percent.
+const SPEED_DOWN: char = '-';
+const SPEED_UP: char = '=';
+const SPEED_STEP: u32 = 25;
+// hotkeys changing the clock (instructions per frame, the timers keep their pace) by HZ_STEP,
+// and toggling turbo (as fast as possible).
+const HZ_DOWN: char = '[';
+const HZ_UP: char = ']';
+const HZ_STEP: u32 = 60;
+const TURBO: KeyCode = KeyCode::Tab;
+// hotkeys pausing/resuming, advancing a paused machine by one frame and resetting it.
+const PAUSE: char = 'p';
+const FRAME_STEP: char = '.';
+const RESET: KeyCode = KeyCode::F(5);
+// switches to the ROM played before this one (see `recent`)
+const RELAUNCH: KeyCode = KeyCode::F(6);
+// columns of the indicators in the status line
+const SPEED_COLUMN: usize = WAITING.len() + 2;
+const PAUSE_COLUMN: usize = SPEED_COLUMN + 13;
+// requests a bug report (--bug-report)
+const BUG_REPORT: KeyCode = KeyCode::F(12);
+const REPORT_COLUMN: usize = PAUSE_COLUMN + 8;
+// writes a screenshot (--screenshot-dir)
+const SCREENSHOT: KeyCode = KeyCode::F(2);
+// row of messages, below the status line and the keypad map
+const MESSAGE_ROW: usize = SCREEN_ROWS + 6;
+const CLOCK_COLUMN: usize = REPORT_COLUMN + 7;
+// shown while the sound timer runs (--sound)
+const NOTE: &str = "♪";
+const SOUND_COLUMN: usize = CLOCK_COLUMN + 16;
+// column of the key overlay, right of the screen
+const OVERLAY_COLUMN: usize = SCREEN_COLUMNS + 2;
+// how often the input thread checks whether the machines were stopped
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+// handles of the hotkeys shared by the keyboard thread and the machines.
+#[derive(Clone)]
+struct Controls {
+    speed: chip8::Speed,
+    // the clock given to `run`, which the clock hotkeys start from
+    cpu_hz: u32,
+    frame_step: chip8::FrameStep,
+    report_requested: Arc<AtomicBool>,
+    screenshot_requested: Arc<AtomicBool>,
+    relaunch: mpsc::Sender<()>,
+    stopper: chip8::Stopper,
 }
 
 #[derive(Clone)]
 struct KeyState {
-    console: Arc<RustBox>,
+    console: Arc<Terminal>,
     pressed: Arc<Mutex<HashSet<u8>>>,
-    tx: mpsc::SyncSender<u8>,
+    // keys held on a device reporting releases (gamepad), kept past the keeptime
+    held: Arc<Mutex<HashSet<u8>>>,
+    // keys pressed since the machine last read them (`consumed`), so that no tap is missed
+    tapped: Arc<Mutex<HashSet<u8>>>,
+    log: Option<Arc<Mutex<KeyLog>>>,
 }
-impl Keyboard {
-    fn new(console: Arc<RustBox>, keeptime: u16) -> Self {
-        let mut key_map: HashMap<char, u8> = HashMap::new();
-        init_keyboard_map(&mut key_map);
-        let (tx, rx) = mpsc::sync_channel(0);
-        let k = KeyState {
+impl KeyState {
+    fn new(console: Arc<Terminal>, key_overlay: bool) -> Self {
+        KeyState {
             console,
-            tx,
             pressed: Arc::new(Mutex::new(HashSet::new())),
+            held: Arc::new(Mutex::new(HashSet::new())),
+            tapped: Arc::new(Mutex::new(HashSet::new())),
+            log: key_overlay.then(|| Arc::new(Mutex::new(KeyLog::default()))),
+        }
+    }
+    fn press(&self, key: u8) {
+        self.pressed.lock().unwrap().insert(key);
+        self.tapped.lock().unwrap().insert(key);
+    }
+    fn release(&self, key: u8) {
+        self.pressed.lock().unwrap().remove(&key);
+    }
+    #[cfg(feature = "gamepad")]
+    fn hold(&self, key: u8, down: bool) {
+        let mut m = self.held.lock().unwrap();
+        if down {
+            m.insert(key);
+            self.tapped.lock().unwrap().insert(key);
+        } else {
+            m.remove(&key);
+        }
+    }
+}
+
+impl Keyboard {
+    fn new(
+        console: Arc<Terminal>,
+        keymap: &KeyMap,
+        keeptime: u16,
+        keypad_on_wait: bool,
+        key_overlay: bool,
+        controls: Controls,
+        split: bool,
+    ) -> (Self, Option<Self>, Vec<JoinHandle<()>>) {
+        let key_map = keyboard_map(&keymap.keypad);
+        let split_map = split.then(|| keyboard_map(&keymap.split));
+        let keypad = |map: &HashMap<char, u8>, rows: &[String; 4]| {
+            keypad_on_wait.then(|| keypad_lines(map, rows))
         };
+        let k = KeyState::new(console.clone(), key_overlay);
+        let first_keypad = keypad(&key_map, &keymap.keypad);
+        let second = split_map.as_ref().map(|map| {
+            let state = KeyState::new(console.clone(), false);
+            Keyboard {
+                state,
+                keypad: keypad(map, &keymap.split),
+                column: SPLIT_COLUMN,
+            }
+        });
+        let second_state = second.as_ref().map(|s| s.state.clone());
         let d = Duration::from_millis(keeptime as u64);
         let kk = k.clone();
-        let kkk = k.clone();
-        thread::spawn(move || loop {
-            let now = Instant::now();
-            {
-                let mut m = kkk.pressed.lock().unwrap();
-                m.clear();
-            }
-            thread::sleep(d - (Instant::now() - now))
-        });
-        thread::spawn(move || loop {
-            let ev: rustbox::EventResult = { k.console.poll_event(false) };
-            match ev {
-                Ok(rustbox::Event::KeyEvent(Key::Esc)) => {
-                    std::process::exit(0);
-                }
-                Ok(rustbox::Event::KeyEvent(Key::Char(key))) => match key_map.get(&key) {
-                    None => {}
-                    Some(val) => {
-                        let mut m = k.pressed.lock().unwrap();
-                        m.insert(*val);
-                        let _ = k.tx.try_send(*val);
+        let mut states = vec![k.clone()];
+        states.extend(second_state.clone());
+        let Controls {
+            speed,
+            cpu_hz,
+            frame_step,
+            report_requested,
+            screenshot_requested,
+            relaunch,
+            stopper,
+        } = controls;
+        // without release events a key counts as held for the keeptime after it was pressed.
+        let stopped = stopper.clone();
+        let release = (!console.reports_releases()).then(|| {
+            thread::spawn(move || {
+                while !stopped.is_stopped() {
+                    let now = Instant::now();
+                    for s in &states {
+                        let mut m = s.pressed.lock().unwrap();
+                        m.clear();
                     }
-                },
+                    thread::sleep(d - (Instant::now() - now))
+                }
+            })
+        });
+        let input = thread::spawn(move || loop {
+            if stopper.is_stopped() {
+                return;
+            }
+            let key = match k.console.poll_event(INPUT_POLL) {
+                Ok(Some(Event::Key(key))) => key,
+                Ok(_) => continue,
                 Err(e) => panic!("{}", e),
+            };
+            match (key.kind, key.code) {
+                (KeyEventKind::Release, KeyCode::Char(key)) => {
+                    if let Some(val) = key_map.get(&key) {
+                        k.release(*val);
+                    }
+                    if let (Some(map), Some(s)) = (&split_map, &second_state) {
+                        if let Some(val) = map.get(&key) {
+                            s.release(*val);
+                        }
+                    }
+                }
+                (KeyEventKind::Release, _) => {}
+                (_, KeyCode::Esc) => {
+                    stopper.stop();
+                    frame_step.resume();
+                }
+                (_, KeyCode::Char('c')) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    stopper.stop();
+                    frame_step.resume();
+                }
+                (_, KeyCode::Char(SPEED_DOWN)) => {
+                    let p = speed.set_percent(speed.percent().saturating_sub(SPEED_STEP));
+                    show_status(&k.console, SPEED_COLUMN, &format!("speed: {:>3}%", p));
+                }
+                (_, KeyCode::Char(SPEED_UP | '+')) => {
+                    let p = speed.set_percent(speed.percent() + SPEED_STEP);
+                    show_status(&k.console, SPEED_COLUMN, &format!("speed: {:>3}%", p));
+                }
+                (_, KeyCode::Char(c @ (HZ_DOWN | HZ_UP))) => {
+                    let hz = speed.cpu_hz().unwrap_or(cpu_hz);
+                    let hz = match c {
+                        HZ_DOWN => hz.saturating_sub(HZ_STEP),
+                        _ => hz + HZ_STEP,
+                    };
+                    speed.set_cpu_hz(Some(hz));
+                    show_clock(&k.console, &speed, cpu_hz);
+                }
+                (_, TURBO) => {
+                    speed.set_turbo(!speed.turbo());
+                    show_clock(&k.console, &speed, cpu_hz);
+                }
+                (_, KeyCode::Char(PAUSE)) => {
+                    if frame_step.is_paused() {
+                        frame_step.resume();
+                        show_status(&k.console, PAUSE_COLUMN, "      ");
+                    } else {
+                        frame_step.pause();
+                        show_status(&k.console, PAUSE_COLUMN, "paused");
+                    }
+                }
+                (_, RESET) => {
+                    stopper.reset();
+                    // a paused machine resets at the start of the next stepped frame
+                    if frame_step.is_paused() {
+                        frame_step.step();
+                    }
+                }
+                (_, RELAUNCH) => {
+                    // a paused machine switches once resumed
+                    let _ = relaunch.send(());
+                }
+                (_, SCREENSHOT) => {
+                    screenshot_requested.store(true, Ordering::Relaxed);
+                }
+                (_, BUG_REPORT) => {
+                    report_requested.store(true, Ordering::Relaxed);
+                    show_status(&k.console, REPORT_COLUMN, "report");
+                }
+                (_, KeyCode::Char(FRAME_STEP)) => {
+                    frame_step.step();
+                    show_status(&k.console, PAUSE_COLUMN, "paused");
+                }
+                (_, KeyCode::Char(key)) => {
+                    k.log_key(|log| {
+                        log.raw(key, key_map.get(&key).copied());
+                        true
+                    });
+                    if let Some(val) = key_map.get(&key) {
+                        k.press(*val);
+                    }
+                    if let (Some(map), Some(s)) = (&split_map, &second_state) {
+                        if let Some(val) = map.get(&key) {
+                            s.press(*val);
+                        }
+                    }
+                }
                 _ => (),
             }
         });
-        Keyboard { state: kk, rx }
+        let first = Keyboard {
+            state: kk,
+            keypad: first_keypad,
+            column: 0,
+        };
+        let mut threads = vec![input];
+        threads.extend(release);
+        (first, second, threads)
     }
+
+    // shows (or erases) the "waiting for key" status line and the keypad map below the screen.
+    fn indicate_waiting(&self, waiting: bool) {
+        let console = &self.state.console;
+        let mut lines = vec![WAITING.to_string()];
+        if let Some(keypad) = &self.keypad {
+            lines.extend(keypad.iter().cloned());
+        }
+        for (i, line) in lines.iter().enumerate() {
+            let text = if waiting {
+                line.clone()
+            } else {
+                " ".repeat(line.chars().count())
+            };
+            console.print(self.column, SCREEN_ROWS + 1 + i, i == 0, &text);
+        }
+        console.present();
+    }
+}
+
+impl KeyState {
+    // updates the key log with `f` and redraws the overlay when `f` changed it;
+    // does nothing without --key-overlay.
+    fn log_key<F: FnOnce(&mut KeyLog) -> bool>(&self, f: F) {
+        let log = match &self.log {
+            Some(l) => l,
+            None => return,
+        };
+        let mut log = log.lock().unwrap();
+        if !f(&mut log) {
+            return;
+        }
+        for (i, line) in log.lines().iter().enumerate() {
+            self.console
+                .print(OVERLAY_COLUMN, i, false, &format!("{:<30}", line));
+        }
+        self.console.present();
+    }
+}
+
+// prints `text` at `column` of the status line below the screen.
+fn show_status(console: &Terminal, column: usize, text: &str) {
+    console.print(column, SCREEN_ROWS + 1, false, text);
+    console.present();
+}
+
+// prints a one-line message below the keypad map.
+fn show_message(console: &Terminal, text: &str) {
+    console.print(
+        0,
+        MESSAGE_ROW,
+        false,
+        &format!("{:<1$}", text, SPLIT_COLUMN * 2),
+    );
+    console.present();
+}
+
+fn show_clock(console: &Terminal, speed: &chip8::Speed, cpu_hz: u32) {
+    let text = match speed.turbo() {
+        true => "cpu: turbo".to_string(),
+        false => format!("cpu: {}Hz", speed.cpu_hz().unwrap_or(cpu_hz)),
+    };
+    show_status(console, CLOCK_COLUMN, &format!("{:<15}", text));
+}
+
+// maps the keys of `rows` to the keypad keys at the same positions of KEYPAD.
+fn keyboard_map(rows: &[String; 4]) -> HashMap<char, u8> {
+    rows.iter()
+        .zip(KEYPAD)
+        .flat_map(|(keys, hex)| keys.chars().zip(hex))
+        .collect()
+}
+
+fn keypad_lines(key_map: &HashMap<char, u8>, rows: &[String; 4]) -> Vec<String> {
+    rows.iter()
+        .map(|row| {
+            row.chars()
+                .map(|c| match key_map.get(&c) {
+                    Some(v) => format!("{}:{:X}", c, v),
+                    None => format!("{}:-", c),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
 }
 
 impl chip8::Keyboard for Keyboard {
     fn is_pressed(&self, key: u8) -> bool {
-        match self.state.pressed.lock().unwrap().get(&key) {
-            Some(_) => true,
-            None => false,
-        }
-    }
-    fn wait(&self) -> u8 {
-        loop {
-            if let Ok(k) = self.rx.recv() {
-                return k;
-            }
-        }
-    }
-}
-fn init_keyboard_map(key_map: &mut HashMap<char, u8>) {
-    key_map.insert('1', 0x1);
-    key_map.insert('2', 0x2);
-    key_map.insert('3', 0x3);
-    key_map.insert('q', 0x4);
-    key_map.insert('w', 0x5);
-    key_map.insert('e', 0x6);
-    key_map.insert('a', 0x7);
-    key_map.insert('s', 0x8);
-    key_map.insert('d', 0x9);
-    key_map.insert('z', 0xa);
-    key_map.insert('x', 0x0);
-    key_map.insert('c', 0xb);
-    key_map.insert('4', 0xc);
-    key_map.insert('v', 0xf);
+        self.state.tapped.lock().unwrap().contains(&key)
+            || self.state.pressed.lock().unwrap().contains(&key)
+            || self.state.held.lock().unwrap().contains(&key)
+    }
+    fn consumed(&self, keys: u16) {
+        (self.state.tapped.lock().unwrap()).retain(|k| keys & 1 << k == 0);
+    }
+    fn waiting(&self, waiting: bool) {
+        self.indicate_waiting(waiting);
+    }
+    fn observed(&self, check: chip8::KeyCheck) {
+        self.state.log_key(|log| log.observed(check));
+    }
 }
@@ -0,0 +1,105 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+const MAX_ENTRIES: usize = 10;
+const FILE_NAME: &str = "recent.tsv";
+
+// a recently played ROM with the settings it was played with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: String,
+    pub hash: u64,
+    pub cpu_hz: u32,
+    pub keeptime_ms: u16,
+    pub play_secs: u64,
+}
+
+// $XDG_CONFIG_HOME/rusty-chip8 or ~/.config/rusty-chip8
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("rusty-chip8"))
+}
+
+// returns the recent ROMs, most recent first.
+pub fn load() -> Vec<Entry> {
+    let text = match config_dir().map(|d| fs::read_to_string(d.join(FILE_NAME))) {
+        Some(Ok(t)) => t,
+        _ => return Vec::new(),
+    };
+    text.lines().filter_map(parse).collect()
+}
+
+fn parse(line: &str) -> Option<Entry> {
+    let f: Vec<&str> = line.split('\t').collect();
+    if f.len() != 5 {
+        return None;
+    }
+    Some(Entry {
+        path: f[0].to_string(),
+        hash: u64::from_str_radix(f[1], 16).ok()?,
+        cpu_hz: f[2].parse().ok()?,
+        keeptime_ms: f[3].parse().ok()?,
+        play_secs: f[4].parse().ok()?,
+    })
+}
+
+// moves `entry` to the top of the list, adding the play time of a previous entry for the same ROM.
+pub fn record(mut entry: Entry) -> io::Result<()> {
+    let dir = match config_dir() {
+        Some(d) => d,
+        None => return Ok(()),
+    };
+    let mut entries = load();
+    if let Some(pos) = entries.iter().position(|e| e.path == entry.path) {
+        entry.play_secs += entries.remove(pos).play_secs;
+    }
+    entries.insert(0, entry);
+    entries.truncate(MAX_ENTRIES);
+    fs::create_dir_all(&dir)?;
+    let text: String = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{}\t{:016x}\t{}\t{}\t{}\n",
+                e.path, e.hash, e.cpu_hz, e.keeptime_ms, e.play_secs
+            )
+        })
+        .collect();
+    fs::write(dir.join(FILE_NAME), text)
+}
+
+// lists the recent ROMs on stdout and reads a choice; an empty line picks the last played ROM.
+pub fn pick(entries: &[Entry]) -> Option<Entry> {
+    for (n, e) in entries.iter().enumerate() {
+        println!(
+            "{:>2}) {} ({}Hz, keeptime {}ms, played {}m{:02}s)",
+            n + 1,
+            e.path,
+            e.cpu_hz,
+            e.keeptime_ms,
+            e.play_secs / 60,
+            e.play_secs % 60
+        );
+    }
+    print!("select [1]: ");
+    let _ = io::stdout().flush();
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).ok()?;
+    let n = match line.trim() {
+        "" => 1,
+        s => s.parse::<usize>().ok()?,
+    };
+    entries.get(n.checked_sub(1)?).cloned()
+}
+
+// FNV-1a of the ROM image, used to tell apart different ROMs stored at the same path.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
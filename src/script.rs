@@ -0,0 +1,330 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::sync::{Arc, Mutex};
+
+// one line of an input script: an action taken when `cycle` cycles have been executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub cycle: u64,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Press(u8),
+    Release(u8),
+    // the expected `Chip::state_hash`
+    Hash(u64),
+}
+
+// formats the event as a script line.
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.action {
+            Action::Press(k) => write!(f, "{} press {:X}", self.cycle, k),
+            Action::Release(k) => write!(f, "{} release {:X}", self.cycle, k),
+            Action::Hash(h) => write!(f, "{} hash {:016x}", self.cycle, h),
+        }
+    }
+}
+
+// loads an input script.
+//
+// Each non-empty line is `<when> <press|release> <key>` where `when` is a cycle number
+// (`120`) or a frame number with an `f` suffix (`30f`, the cycle at which `run` starts that
+// frame at `hz`), and `key`
+// is a hex keypad key (`0`-`F`), or `<when> hash <state hash>` to verify the machine state.
+// Text after `#` is a comment.
+pub fn load(path: &str, hz: u32) -> Result<Vec<Event>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut events = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let ev = parse_line(line, hz).map_err(|e| format!("{}:{}: {}", path, n + 1, e))?;
+        events.push(ev);
+    }
+    events.sort_by_key(|e| e.cycle);
+    Ok(events)
+}
+
+fn parse_line(line: &str, hz: u32) -> Result<Event, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 3 {
+        return Err(format!(
+            "expected `<when> <press|release|hash> <arg>`: `{}`",
+            line
+        ));
+    }
+    let cycle = match fields[0].strip_suffix('f') {
+        // `run` carries the remainder of hz / 60 over, so frame n starts at n * hz / 60
+        Some(frame) => parse_num(frame)?
+            .checked_mul(u64::from(hz.max(60)))
+            .map(|c| c / 60)
+            .ok_or_else(|| format!("frame `{}` is too far", frame))?,
+        None => parse_num(fields[0])?,
+    };
+    let key = || {
+        u8::from_str_radix(fields[2], 16)
+            .ok()
+            .filter(|k| *k < 0x10)
+            .ok_or_else(|| format!("invalid key `{}`", fields[2]))
+    };
+    let action = match fields[1] {
+        "press" | "down" => Action::Press(key()?),
+        "release" | "up" => Action::Release(key()?),
+        "hash" => Action::Hash(
+            u64::from_str_radix(fields[2], 16)
+                .map_err(|_| format!("invalid hash `{}`", fields[2]))?,
+        ),
+        a => return Err(format!("unknown action `{}`", a)),
+    };
+    Ok(Event { cycle, action })
+}
+
+// the press and release events turning the keys `before` into `after` (bit n for key n).
+pub fn key_changes(cycle: u64, before: u16, after: u16) -> impl Iterator<Item = Event> {
+    (0..16u8)
+        .filter(move |k| (before ^ after) & 1 << k != 0)
+        .map(move |k| Event {
+            cycle,
+            action: match after & 1 << k != 0 {
+                true => Action::Press(k),
+                false => Action::Release(k),
+            },
+        })
+}
+
+fn parse_num(s: &str) -> Result<u64, String> {
+    s.parse().map_err(|_| format!("invalid number `{}`", s))
+}
+
+// replays script events into the keys shared with `Injected`.
+pub struct Player {
+    events: Vec<Event>,
+    next: usize,
+    pressed: Arc<Mutex<HashSet<u8>>>,
+}
+
+impl Player {
+    pub fn new(events: Vec<Event>) -> Self {
+        Player {
+            events,
+            next: 0,
+            pressed: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+    // applies every event scheduled at or before `cycle`.
+    // Fails at the first hash event which does not match `state_hash()`.
+    pub fn apply<H: Fn() -> u64>(&mut self, cycle: u64, state_hash: H) -> Result<(), String> {
+        while let Some(ev) = self.events.get(self.next) {
+            if ev.cycle > cycle {
+                break;
+            }
+            let mut pressed = self.pressed.lock().unwrap();
+            match ev.action {
+                Action::Press(k) => {
+                    pressed.insert(k);
+                }
+                Action::Release(k) => {
+                    pressed.remove(&k);
+                }
+                Action::Hash(expected) => {
+                    let h = state_hash();
+                    if h != expected {
+                        return Err(format!(
+                            "replay diverged at cycle {}: state hash {:016x}, expected {:016x}",
+                            cycle, h, expected
+                        ));
+                    }
+                }
+            }
+            self.next += 1;
+        }
+        Ok(())
+    }
+    pub fn keyboard<K: chip8::Keyboard>(&self, inner: K) -> Injected<K> {
+        Injected {
+            inner,
+            pressed: self.pressed.clone(),
+        }
+    }
+}
+
+// keyboard which reports both the real keys and the keys injected by a `Player`.
+pub struct Injected<K> {
+    inner: K,
+    pressed: Arc<Mutex<HashSet<u8>>>,
+}
+
+impl<K: chip8::Keyboard> chip8::Keyboard for Injected<K> {
+    fn is_pressed(&self, key: u8) -> bool {
+        self.pressed.lock().unwrap().contains(&key) || self.inner.is_pressed(key)
+    }
+    fn consumed(&self, keys: u16) {
+        self.inner.consumed(keys)
+    }
+    fn waiting(&self, waiting: bool) {
+        self.inner.waiting(waiting)
+    }
+    fn observed(&self, check: chip8::KeyCheck) {
+        self.inner.observed(check)
+    }
+}
+
+// writes a replay: the keys the machine saw and the state hash at regular intervals.
+pub struct Recorder {
+    out: LineWriter<File>,
+    // the keys of the last `keys`
+    keys: u16,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Recorder {
+            out: LineWriter::new(File::create(path)?),
+            keys: 0,
+        })
+    }
+    pub fn write(&mut self, ev: &Event) {
+        let _ = writeln!(self.out, "{}", ev);
+    }
+    // writes the presses and releases since the last call, with `Keyboard::state` before `cycle`.
+    pub fn keys(&mut self, cycle: u64, keys: u16) {
+        for ev in key_changes(cycle, self.keys, keys) {
+            self.write(&ev);
+        }
+        self.keys = keys;
+    }
+    pub fn comment(&mut self, text: &str) {
+        let _ = writeln!(self.out, "# {}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8::Keyboard;
+
+    #[test]
+    fn frames_start_where_run_starts_them() {
+        // 500 Hz runs frames of 8, 8 and 9 cycles
+        let at = |when: &str| parse_line(&format!("{} press 5", when), 500).map(|e| e.cycle);
+        assert_eq!(at("3f"), Ok(25));
+        assert_eq!(at("30f"), Ok(250));
+        assert_eq!(at("240"), Ok(240));
+        assert_eq!(parse_line("2f up a", 30).map(|e| e.cycle), Ok(2));
+        assert!(at("18446744073709551615f").is_err());
+    }
+
+    #[test]
+    fn parses_actions() {
+        assert_eq!(
+            parse_line("10 down f", 600),
+            Ok(Event {
+                cycle: 10,
+                action: Action::Press(0xF)
+            })
+        );
+        assert_eq!(
+            parse_line("10 hash 00000000000000ff", 600).map(|e| e.action),
+            Ok(Action::Hash(0xff))
+        );
+        assert!(parse_line("10 press 10", 600).is_err());
+        assert!(parse_line("10 tap 1", 600).is_err());
+        assert!(parse_line("10 press", 600).is_err());
+        // lines print as they parse
+        let ev = Event {
+            cycle: 7,
+            action: Action::Release(0xA),
+        };
+        assert_eq!(parse_line(&ev.to_string(), 600), Ok(ev));
+    }
+
+    #[test]
+    fn player_holds_the_keys_and_checks_hashes() {
+        let events = vec![
+            Event {
+                cycle: 2,
+                action: Action::Press(1),
+            },
+            Event {
+                cycle: 4,
+                action: Action::Hash(9),
+            },
+            Event {
+                cycle: 5,
+                action: Action::Release(1),
+            },
+        ];
+        let mut p = Player::new(events);
+        let keys = p.keyboard(chip8::NullKeyboard);
+        p.apply(1, || 9).unwrap();
+        assert!(!keys.is_pressed(1));
+        p.apply(3, || 9).unwrap();
+        assert!(keys.is_pressed(1));
+        p.apply(5, || 9).unwrap();
+        assert!(!keys.is_pressed(1));
+        let mut p = Player::new(vec![Event {
+            cycle: 0,
+            action: Action::Hash(9),
+        }]);
+        assert!(p
+            .apply(0, || 8)
+            .unwrap_err()
+            .contains("diverged at cycle 0"));
+    }
+
+    #[test]
+    fn replays_recorded_keys() {
+        // LD V1, K ; ADD V2, V1 ; JP 0x200
+        let rom = [0xF1, 0x0A, 0x82, 0x14, 0x12, 0x00];
+        let path = std::env::temp_dir().join(format!("rusty-chip8-{}.replay", std::process::id()));
+        let path = path.to_str().unwrap();
+        let keypad = chip8::Keypad::new();
+        let mut chip = chip8::Chip::new(Box::new(chip8::NullDisplay), Box::new(keypad.clone()));
+        chip.load_slice(&rom).unwrap();
+        let mut r = Recorder::create(path).unwrap();
+        for cycle in 0..300 {
+            match cycle {
+                20 => keypad.press(3),
+                40 => keypad.release(3),
+                // a tap between two cycles
+                100 => {
+                    keypad.press(7);
+                    keypad.release(7);
+                }
+                150 => keypad.press(0xA),
+                160 => keypad.release(0xA),
+                _ => {}
+            }
+            r.keys(cycle, chip.keyboard.state());
+            chip.step().unwrap();
+        }
+        let hash = chip.state_hash();
+        r.write(&Event {
+            cycle: 300,
+            action: Action::Hash(hash),
+        });
+        drop(r);
+        assert_eq!(chip.registers().v[2], 3 + 7 + 0xA);
+
+        let mut p = Player::new(load(path, 600).unwrap());
+        let _ = fs::remove_file(path);
+        let mut replay = chip8::Chip::new(
+            Box::new(chip8::NullDisplay),
+            Box::new(p.keyboard(chip8::NullKeyboard)),
+        );
+        replay.load_slice(&rom).unwrap();
+        for cycle in 0..300 {
+            p.apply(cycle, || replay.state_hash()).unwrap();
+            replay.step().unwrap();
+        }
+        assert_eq!(p.apply(300, || replay.state_hash()), Ok(()));
+        assert_eq!(replay.state_hash(), hash);
+    }
+}
@@ -0,0 +1,194 @@
+// the terminal screen on crossterm: a grid of character cells shared by the displays and the
+// input thread; `present` writes the cells changed since the previous call.
+use crossterm::event::{
+    self, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Attribute, Print, SetAttribute, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub use crossterm::style::Color;
+
+// the keyboard enhancement flags were pushed and must be popped on restore
+static ENHANCED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Cell {
+    ch: char,
+    fg: Color,
+    bg: Color,
+    bold: bool,
+}
+
+const BLANK: Cell = Cell {
+    ch: ' ',
+    fg: Color::Reset,
+    bg: Color::Reset,
+    bold: false,
+};
+
+struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    // the cells on the terminal; None after a resize repaints everything
+    shown: Option<Vec<Cell>>,
+}
+
+impl Grid {
+    fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        if x < self.width && y < self.height {
+            self.cells[y * self.width + x] = cell;
+        }
+    }
+}
+
+pub struct Terminal {
+    grid: Mutex<Grid>,
+    // the terminal reports key releases (kitty keyboard protocol)
+    releases: bool,
+}
+
+impl Terminal {
+    // switches to the alternate screen in raw mode; dropping the Terminal switches back.
+    pub fn init() -> io::Result<Terminal> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+            ENHANCED.store(true, Ordering::SeqCst);
+        }
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        Ok(Terminal {
+            grid: Mutex::new(Grid {
+                width,
+                height,
+                cells: vec![BLANK; width * height],
+                shown: None,
+            }),
+            releases,
+        })
+    }
+    pub fn reports_releases(&self) -> bool {
+        self.releases
+    }
+    pub fn print_char(&self, x: usize, y: usize, fg: Color, bg: Color, ch: char) {
+        let cell = Cell {
+            ch,
+            fg,
+            bg,
+            bold: false,
+        };
+        self.grid.lock().unwrap().set(x, y, cell);
+    }
+    pub fn print(&self, x: usize, y: usize, bold: bool, text: &str) {
+        let mut grid = self.grid.lock().unwrap();
+        for (i, ch) in text.chars().enumerate() {
+            grid.set(x + i, y, Cell { bold, ch, ..BLANK });
+        }
+    }
+    pub fn clear(&self) {
+        self.grid.lock().unwrap().cells.fill(BLANK);
+    }
+    pub fn present(&self) {
+        let mut grid = self.grid.lock().unwrap();
+        let mut out = io::stdout().lock();
+        let mut last: Option<Cell> = None;
+        let mut at = None;
+        for (i, cell) in grid.cells.iter().enumerate() {
+            if grid.shown.as_ref().is_some_and(|s| s[i] == *cell) {
+                continue;
+            }
+            let (x, y) = (i % grid.width, i / grid.width);
+            if at != Some((x, y)) {
+                let _ = queue!(out, cursor::MoveTo(x as u16, y as u16));
+            }
+            if last.is_none_or(|l| (l.fg, l.bg, l.bold) != (cell.fg, cell.bg, cell.bold)) {
+                let bold = match cell.bold {
+                    true => Attribute::Bold,
+                    false => Attribute::NormalIntensity,
+                };
+                let _ = queue!(
+                    out,
+                    SetAttribute(bold),
+                    SetForegroundColor(cell.fg),
+                    SetBackgroundColor(cell.bg)
+                );
+                last = Some(*cell);
+            }
+            let _ = queue!(out, Print(cell.ch));
+            at = Some((x + 1, y));
+        }
+        if last.is_some() {
+            let _ = queue!(out, SetAttribute(Attribute::Reset));
+            let _ = out.flush();
+        }
+        grid.shown = Some(grid.cells.clone());
+    }
+    // waits up to `timeout` for an input event; a resize keeps the cells and repaints them.
+    pub fn poll_event(&self, timeout: Duration) -> io::Result<Option<Event>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        let ev = event::read()?;
+        if let Event::Resize(w, h) = ev {
+            self.resize(w as usize, h as usize);
+        }
+        Ok(Some(ev))
+    }
+    fn resize(&self, width: usize, height: usize) {
+        {
+            let mut grid = self.grid.lock().unwrap();
+            let mut cells = vec![BLANK; width * height];
+            for y in 0..height.min(grid.height) {
+                for x in 0..width.min(grid.width) {
+                    cells[y * width + x] = grid.cells[y * grid.width + x];
+                }
+            }
+            *grid = Grid {
+                width,
+                height,
+                cells,
+                shown: None,
+            };
+        }
+        let _ = execute!(io::stdout(), terminal::Clear(terminal::ClearType::All));
+        self.present();
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// leaves the alternate screen and raw mode; also safe to call from a panic hook while the
+// Terminal is still shared by other threads.
+pub fn restore() {
+    if ENHANCED.swap(false, Ordering::SeqCst) {
+        let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(
+        io::stdout(),
+        SetAttribute(Attribute::Reset),
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    );
+    let _ = terminal::disable_raw_mode();
+}